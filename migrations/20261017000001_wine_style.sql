@@ -0,0 +1,5 @@
+-- NULL means the style hasn't been recorded yet
+ALTER TABLE wines ADD COLUMN style TEXT
+  CHECK (style IN ('red', 'white', 'rose', 'sparkling', 'fortified', 'dessert'));
+
+CREATE INDEX wines_style ON wines(style);
@@ -0,0 +1,122 @@
+CREATE TABLE producers (
+  producer_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+-- Countries have no parent, regions belong to a country and appellations to a region. The
+-- parent's kind is checked by the application when regions are added.
+CREATE TABLE regions (
+  region_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  parent_id INTEGER REFERENCES regions(region_id),
+  kind TEXT NOT NULL CHECK (kind IN ('country', 'region', 'appellation')),
+  name TEXT NOT NULL,
+  CHECK ((kind = 'country') = (parent_id IS NULL))
+);
+
+CREATE UNIQUE INDEX regions_name ON regions(COALESCE(parent_id, 0), name);
+
+ALTER TABLE wines ADD COLUMN producer_id INTEGER REFERENCES producers(producer_id);
+ALTER TABLE wines ADD COLUMN region_id INTEGER REFERENCES regions(region_id);
+
+INSERT INTO regions (kind, name) VALUES
+('country', 'Argentina'),
+('country', 'Australia'),
+('country', 'Austria'),
+('country', 'Chile'),
+('country', 'France'),
+('country', 'Germany'),
+('country', 'Hungary'),
+('country', 'Italy'),
+('country', 'New Zealand'),
+('country', 'Portugal'),
+('country', 'South Africa'),
+('country', 'Spain'),
+('country', 'Sweden'),
+('country', 'USA');
+
+INSERT INTO regions (parent_id, kind, name)
+SELECT c.region_id, 'region', v.column2
+FROM (VALUES
+  ('Argentina', 'Mendoza'),
+  ('Argentina', 'Salta'),
+  ('Australia', 'Barossa Valley'),
+  ('Australia', 'Margaret River'),
+  ('Australia', 'McLaren Vale'),
+  ('Austria', 'Burgenland'),
+  ('Austria', 'Niederösterreich'),
+  ('Austria', 'Wachau'),
+  ('Chile', 'Casablanca Valley'),
+  ('Chile', 'Central Valley'),
+  ('France', 'Alsace'),
+  ('France', 'Bordeaux'),
+  ('France', 'Burgundy'),
+  ('France', 'Champagne'),
+  ('France', 'Jura'),
+  ('France', 'Languedoc-Roussillon'),
+  ('France', 'Loire'),
+  ('France', 'Provence'),
+  ('France', 'Rhône'),
+  ('Germany', 'Mosel'),
+  ('Germany', 'Pfalz'),
+  ('Germany', 'Rheingau'),
+  ('Germany', 'Rheinhessen'),
+  ('Hungary', 'Tokaj'),
+  ('Italy', 'Piedmont'),
+  ('Italy', 'Sicily'),
+  ('Italy', 'Tuscany'),
+  ('Italy', 'Veneto'),
+  ('New Zealand', 'Central Otago'),
+  ('New Zealand', 'Marlborough'),
+  ('Portugal', 'Douro'),
+  ('Portugal', 'Vinho Verde'),
+  ('South Africa', 'Stellenbosch'),
+  ('South Africa', 'Swartland'),
+  ('Spain', 'Galicia'),
+  ('Spain', 'Priorat'),
+  ('Spain', 'Ribera del Duero'),
+  ('Spain', 'Rioja'),
+  ('Spain', 'Jerez'),
+  ('Sweden', 'Skåne'),
+  ('USA', 'California'),
+  ('USA', 'Oregon'),
+  ('USA', 'Washington')
+) v
+JOIN regions c ON c.kind = 'country' AND c.name = v.column1;
+
+INSERT INTO regions (parent_id, kind, name)
+SELECT r.region_id, 'appellation', v.column2
+FROM (VALUES
+  ('Bordeaux', 'Margaux'),
+  ('Bordeaux', 'Pauillac'),
+  ('Bordeaux', 'Pessac-Léognan'),
+  ('Bordeaux', 'Pomerol'),
+  ('Bordeaux', 'Saint-Émilion'),
+  ('Bordeaux', 'Saint-Julien'),
+  ('Bordeaux', 'Sauternes'),
+  ('Burgundy', 'Beaujolais'),
+  ('Burgundy', 'Chablis'),
+  ('Burgundy', 'Côte de Beaune'),
+  ('Burgundy', 'Côte de Nuits'),
+  ('Burgundy', 'Mâconnais'),
+  ('Loire', 'Muscadet'),
+  ('Loire', 'Sancerre'),
+  ('Loire', 'Vouvray'),
+  ('Rhône', 'Châteauneuf-du-Pape'),
+  ('Rhône', 'Côte-Rôtie'),
+  ('Rhône', 'Côtes du Rhône'),
+  ('Rhône', 'Hermitage'),
+  ('Piedmont', 'Barbaresco'),
+  ('Piedmont', 'Barolo'),
+  ('Tuscany', 'Bolgheri'),
+  ('Tuscany', 'Brunello di Montalcino'),
+  ('Tuscany', 'Chianti Classico'),
+  ('Veneto', 'Amarone della Valpolicella'),
+  ('Veneto', 'Prosecco'),
+  ('Veneto', 'Soave'),
+  ('Sicily', 'Etna'),
+  ('Galicia', 'Rías Baixas'),
+  ('California', 'Napa Valley'),
+  ('California', 'Sonoma'),
+  ('Oregon', 'Willamette Valley')
+) v
+JOIN regions r ON r.kind = 'region' AND r.name = v.column1;
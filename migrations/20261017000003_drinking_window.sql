@@ -0,0 +1,3 @@
+-- First and last year the wine is expected to drink well; either end may be open
+ALTER TABLE wines ADD COLUMN drink_from INT;
+ALTER TABLE wines ADD COLUMN drink_until INT;
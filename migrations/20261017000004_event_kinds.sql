@@ -0,0 +1,6 @@
+-- Events recorded before kinds existed were either a purchase or a consumption
+ALTER TABLE wine_inventory_events ADD COLUMN kind TEXT NOT NULL DEFAULT 'correction'
+  CHECK (kind IN ('purchase', 'consumption', 'gift', 'loss', 'correction', 'transfer'));
+
+UPDATE wine_inventory_events
+SET kind = CASE WHEN bottles > 0 THEN 'purchase' ELSE 'consumption' END;
@@ -0,0 +1,5 @@
+-- Price per bottle in the currency's minor unit (e.g. cents). Only purchases have one.
+ALTER TABLE wine_inventory_events ADD COLUMN unit_price INTEGER CHECK (unit_price >= 0);
+-- ISO 4217 code, set together with unit_price
+ALTER TABLE wine_inventory_events ADD COLUMN currency TEXT CHECK (length(currency) = 3);
+ALTER TABLE wine_inventory_events ADD COLUMN vendor TEXT;
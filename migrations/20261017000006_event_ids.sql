@@ -0,0 +1,26 @@
+-- Give inventory events a primary key so single events can be edited and deleted. SQLite
+-- can't add one to an existing table, so the table is rebuilt; event_id keeps the old rowid.
+CREATE TABLE wine_inventory_events_new (
+  event_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  wine_id INTEGER NOT NULL,
+  dt DATETIME NOT NULL,
+  bottles INT NOT NULL,
+  kind TEXT NOT NULL DEFAULT 'correction'
+    CHECK (kind IN ('purchase', 'consumption', 'gift', 'loss', 'correction', 'transfer')),
+  -- Price per bottle in the currency's minor unit (e.g. cents). Only purchases have one.
+  unit_price INTEGER CHECK (unit_price >= 0),
+  -- ISO 4217 code, set together with unit_price
+  currency TEXT CHECK (length(currency) = 3),
+  vendor TEXT,
+  FOREIGN KEY (wine_id) REFERENCES wines(wine_id)
+);
+
+INSERT INTO wine_inventory_events_new
+  (event_id, wine_id, dt, bottles, kind, unit_price, currency, vendor)
+SELECT rowid, wine_id, dt, bottles, kind, unit_price, currency, vendor
+FROM wine_inventory_events;
+
+DROP TABLE wine_inventory_events;
+ALTER TABLE wine_inventory_events_new RENAME TO wine_inventory_events;
+
+CREATE INDEX wine_inventory_events_wine_id ON wine_inventory_events (wine_id, dt);
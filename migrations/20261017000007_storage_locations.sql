@@ -0,0 +1,28 @@
+-- Where bottles are kept: a location (the cellar, a fridge) holds racks, and each rack is a
+-- grid of slots that hold one bottle each.
+CREATE TABLE locations (
+  location_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+CREATE TABLE racks (
+  rack_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  location_id INTEGER NOT NULL REFERENCES locations(location_id),
+  name TEXT NOT NULL COLLATE NOCASE,
+  row_count INT NOT NULL CHECK (row_count BETWEEN 1 AND 100),
+  column_count INT NOT NULL CHECK (column_count BETWEEN 1 AND 100),
+  UNIQUE (location_id, name)
+);
+
+-- One row per bottle that has been put in a slot. Slots are numbered from 1 and checked
+-- against the rack's size by the application. Bottles in stock without a row here haven't
+-- been placed anywhere.
+CREATE TABLE bottles (
+  rack_id INTEGER NOT NULL REFERENCES racks(rack_id),
+  row_num INT NOT NULL,
+  col_num INT NOT NULL,
+  wine_id INTEGER NOT NULL REFERENCES wines(wine_id),
+  PRIMARY KEY (rack_id, row_num, col_num)
+);
+
+CREATE INDEX bottles_wine_id ON bottles (wine_id);
@@ -0,0 +1,15 @@
+-- A bottle moved from one slot to another. Moves don't change how many bottles are in stock,
+-- so they aren't inventory events.
+CREATE TABLE bottle_moves (
+  move_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  wine_id INTEGER NOT NULL REFERENCES wines(wine_id),
+  dt DATETIME NOT NULL,
+  from_rack_id INTEGER NOT NULL REFERENCES racks(rack_id),
+  from_row INT NOT NULL,
+  from_col INT NOT NULL,
+  to_rack_id INTEGER NOT NULL REFERENCES racks(rack_id),
+  to_row INT NOT NULL,
+  to_col INT NOT NULL
+);
+
+CREATE INDEX bottle_moves_wine_id ON bottle_moves (wine_id, dt);
@@ -0,0 +1,15 @@
+-- One row per bottle opened and tasted, alongside the single editable note in wines.comment.
+-- The score is out of `scale`, the score scale configured when the tasting was recorded, so
+-- changing the scale later doesn't change what old scores mean.
+CREATE TABLE tastings (
+  tasting_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  wine_id INTEGER NOT NULL REFERENCES wines(wine_id),
+  tasted_on DATE NOT NULL,
+  score INT NOT NULL CHECK (score >= 0),
+  scale INT NOT NULL CHECK (scale > 0),
+  note TEXT,
+  occasion TEXT,
+  CHECK (score <= scale)
+);
+
+CREATE INDEX tastings_wine_id ON tastings (wine_id, tasted_on);
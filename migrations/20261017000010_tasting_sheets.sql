@@ -0,0 +1,46 @@
+-- Structured tasting sheets, modelled on the systematic approach to tasting: the palate's
+-- structure on a 1 (low) to 5 (high) scale and the aromas picked from a vocabulary. A sheet
+-- is optional, so it lives in its own table keyed by the tasting. Each level is NULL when it
+-- wasn't assessed, e.g. tannin in most white wines.
+CREATE TABLE tasting_sheets (
+  tasting_id INTEGER PRIMARY KEY REFERENCES tastings(tasting_id),
+  sweetness INT CHECK (sweetness BETWEEN 1 AND 5),
+  acidity INT CHECK (acidity BETWEEN 1 AND 5),
+  tannin INT CHECK (tannin BETWEEN 1 AND 5),
+  body INT CHECK (body BETWEEN 1 AND 5),
+  finish INT CHECK (finish BETWEEN 1 AND 5)
+);
+
+-- The aroma vocabulary, grouped into families for the form.
+CREATE TABLE aromas (
+  aroma_id INTEGER PRIMARY KEY,
+  family TEXT NOT NULL,
+  name TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+INSERT INTO aromas (family, name) VALUES
+  ('Citrus fruit', 'Lemon'), ('Citrus fruit', 'Lime'), ('Citrus fruit', 'Grapefruit'),
+  ('Citrus fruit', 'Orange peel'),
+  ('Green fruit', 'Green apple'), ('Green fruit', 'Pear'), ('Green fruit', 'Gooseberry'),
+  ('Stone fruit', 'Peach'), ('Stone fruit', 'Apricot'),
+  ('Tropical fruit', 'Pineapple'), ('Tropical fruit', 'Mango'), ('Tropical fruit', 'Passion fruit'),
+  ('Red fruit', 'Redcurrant'), ('Red fruit', 'Cranberry'), ('Red fruit', 'Raspberry'),
+  ('Red fruit', 'Strawberry'), ('Red fruit', 'Red cherry'),
+  ('Black fruit', 'Blackcurrant'), ('Black fruit', 'Blackberry'), ('Black fruit', 'Black cherry'),
+  ('Black fruit', 'Plum'),
+  ('Dried fruit', 'Fig'), ('Dried fruit', 'Raisin'), ('Dried fruit', 'Prune'),
+  ('Floral', 'Blossom'), ('Floral', 'Rose'), ('Floral', 'Violet'),
+  ('Herbaceous', 'Green pepper'), ('Herbaceous', 'Grass'), ('Herbaceous', 'Mint'),
+  ('Herbaceous', 'Eucalyptus'),
+  ('Spice', 'Black pepper'), ('Spice', 'Liquorice'), ('Spice', 'Cinnamon'), ('Spice', 'Clove'),
+  ('Oak', 'Vanilla'), ('Oak', 'Cedar'), ('Oak', 'Smoke'), ('Oak', 'Toast'), ('Oak', 'Coconut'),
+  ('Autolytic', 'Brioche'), ('Autolytic', 'Biscuit'),
+  ('Earth', 'Mushroom'), ('Earth', 'Forest floor'), ('Earth', 'Wet stones'),
+  ('Mature', 'Leather'), ('Mature', 'Tobacco'), ('Mature', 'Honey'), ('Mature', 'Petrol'),
+  ('Mature', 'Tar');
+
+CREATE TABLE tasting_aromas (
+  tasting_id INTEGER NOT NULL REFERENCES tastings(tasting_id),
+  aroma_id INTEGER NOT NULL REFERENCES aromas(aroma_id),
+  PRIMARY KEY (tasting_id, aroma_id)
+);
@@ -0,0 +1,105 @@
+-- Full-text index over each wine's name, producer, note, tasting notes, grapes and pairings,
+-- one FTS5 row per wine with rowid = wine_id. The triggers below re-index a wine from
+-- wine_search_source whenever anything that feeds it changes, so the index never needs to be
+-- maintained by the application (and a restore rebuilds it as the rows are inserted).
+CREATE VIEW wine_search_source AS
+SELECT w.wine_id,
+       w.name,
+       p.name AS producer,
+       w.comment AS note,
+       (SELECT group_concat(concat_ws(' ', t.occasion, t.note), char(10))
+        FROM tastings t WHERE t.wine_id = w.wine_id) AS tastings,
+       (SELECT group_concat(g.grape_name, ', ')
+        FROM wine_grapes g WHERE g.wine_id = w.wine_id) AS grapes,
+       (SELECT group_concat(fp.food, ', ')
+        FROM wine_food_pairings fp WHERE fp.wine_id = w.wine_id) AS pairings
+FROM wines w
+LEFT JOIN producers p ON p.producer_id = w.producer_id;
+
+-- remove_diacritics so that "cotes" finds "Côtes".
+CREATE VIRTUAL TABLE wine_search USING fts5(
+  name, producer, note, tastings, grapes, pairings,
+  tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+SELECT * FROM wine_search_source;
+
+CREATE TRIGGER wines_search_insert AFTER INSERT ON wines BEGIN
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = NEW.wine_id;
+END;
+
+CREATE TRIGGER wines_search_update AFTER UPDATE OF name, producer_id, comment ON wines BEGIN
+  DELETE FROM wine_search WHERE rowid = OLD.wine_id;
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = NEW.wine_id;
+END;
+
+CREATE TRIGGER wines_search_delete AFTER DELETE ON wines BEGIN
+  DELETE FROM wine_search WHERE rowid = OLD.wine_id;
+END;
+
+CREATE TRIGGER producers_search_update AFTER UPDATE OF name ON producers BEGIN
+  DELETE FROM wine_search
+  WHERE rowid IN (SELECT wine_id FROM wines WHERE producer_id = NEW.producer_id);
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source
+  WHERE wine_id IN (SELECT wine_id FROM wines WHERE producer_id = NEW.producer_id);
+END;
+
+-- The child tables re-index the wine they belong to. Updates re-index both the old and the
+-- new wine in case a row moved.
+CREATE TRIGGER wine_grapes_search_insert AFTER INSERT ON wine_grapes BEGIN
+  DELETE FROM wine_search WHERE rowid = NEW.wine_id;
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = NEW.wine_id;
+END;
+
+CREATE TRIGGER wine_grapes_search_update AFTER UPDATE ON wine_grapes BEGIN
+  DELETE FROM wine_search WHERE rowid IN (OLD.wine_id, NEW.wine_id);
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id IN (OLD.wine_id, NEW.wine_id);
+END;
+
+CREATE TRIGGER wine_grapes_search_delete AFTER DELETE ON wine_grapes BEGIN
+  DELETE FROM wine_search WHERE rowid = OLD.wine_id;
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = OLD.wine_id;
+END;
+
+CREATE TRIGGER wine_food_pairings_search_insert AFTER INSERT ON wine_food_pairings BEGIN
+  DELETE FROM wine_search WHERE rowid = NEW.wine_id;
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = NEW.wine_id;
+END;
+
+CREATE TRIGGER wine_food_pairings_search_update AFTER UPDATE ON wine_food_pairings BEGIN
+  DELETE FROM wine_search WHERE rowid IN (OLD.wine_id, NEW.wine_id);
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id IN (OLD.wine_id, NEW.wine_id);
+END;
+
+CREATE TRIGGER wine_food_pairings_search_delete AFTER DELETE ON wine_food_pairings BEGIN
+  DELETE FROM wine_search WHERE rowid = OLD.wine_id;
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = OLD.wine_id;
+END;
+
+CREATE TRIGGER tastings_search_insert AFTER INSERT ON tastings BEGIN
+  DELETE FROM wine_search WHERE rowid = NEW.wine_id;
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = NEW.wine_id;
+END;
+
+CREATE TRIGGER tastings_search_update AFTER UPDATE ON tastings BEGIN
+  DELETE FROM wine_search WHERE rowid IN (OLD.wine_id, NEW.wine_id);
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id IN (OLD.wine_id, NEW.wine_id);
+END;
+
+CREATE TRIGGER tastings_search_delete AFTER DELETE ON tastings BEGIN
+  DELETE FROM wine_search WHERE rowid = OLD.wine_id;
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = OLD.wine_id;
+END;
@@ -0,0 +1,20 @@
+-- Local user accounts. Passwords are stored as argon2 PHC strings, which carry their own
+-- salt and parameters. No AUTOINCREMENT: accounts belong to the running instance, not the
+-- cellar, so restoring a backup leaves them (and sqlite_sequence) alone.
+CREATE TABLE users (
+  user_id INTEGER PRIMARY KEY,
+  username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+  password_hash TEXT NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Logged in browsers. The cookie holds a random token; only its SHA-256 is stored, so a
+-- copy of the database (e.g. a backup) can't be used to take over a session.
+CREATE TABLE sessions (
+  token_hash TEXT PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(user_id),
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at DATETIME NOT NULL
+);
+
+CREATE INDEX sessions_user_id ON sessions (user_id);
@@ -0,0 +1,100 @@
+-- Several households can share one instance: each has a cellar that owns its wines (and
+-- through them the events, bottles, tastings, pairings and images) and its storage
+-- locations. Users are granted a role per cellar. What was in the database becomes the
+-- first cellar, owned by every existing account.
+--
+-- Wine names and locations become unique per cellar, which SQLite can only do by rebuilding
+-- the tables. Foreign keys can't be switched off inside the migration's transaction, so they
+-- are deferred instead: dropping a table leaves the rows referencing it dangling until the
+-- rebuilt table, under the same name, has its rows back.
+PRAGMA defer_foreign_keys = ON;
+
+CREATE TABLE cellars (
+  cellar_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO cellars (cellar_id, name) VALUES (1, 'Cellar');
+
+-- Owners manage the members, editors change the cellar's contents and viewers only look.
+CREATE TABLE cellar_members (
+  cellar_id INTEGER NOT NULL REFERENCES cellars(cellar_id),
+  user_id INTEGER NOT NULL REFERENCES users(user_id),
+  role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+  PRIMARY KEY (cellar_id, user_id)
+);
+
+CREATE INDEX cellar_members_user_id ON cellar_members (user_id);
+
+INSERT INTO cellar_members (cellar_id, user_id, role) SELECT 1, user_id, 'owner' FROM users;
+
+-- Administrators manage the accounts and cellars and take and restore backups of the whole
+-- instance. Everyone could manage accounts before.
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE users SET is_admin = TRUE;
+
+-- The cellar the browser last switched to
+ALTER TABLE sessions ADD COLUMN cellar_id INTEGER REFERENCES cellars(cellar_id);
+
+CREATE TEMP TABLE wines_copy AS SELECT * FROM wines;
+
+-- Drops the search triggers on wines too; the index itself is keyed by wine_id and stays
+DROP TABLE wines;
+
+CREATE TABLE wines (
+  wine_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  cellar_id INTEGER NOT NULL REFERENCES cellars(cellar_id),
+  name TEXT NOT NULL,
+  year INT NOT NULL,
+  image BLOB,
+  comment TEXT,
+  comment_updated_at DATETIME,
+  style TEXT CHECK (style IN ('red', 'white', 'rose', 'sparkling', 'fortified', 'dessert')),
+  producer_id INTEGER REFERENCES producers(producer_id),
+  region_id INTEGER REFERENCES regions(region_id),
+  drink_from INT,
+  drink_until INT,
+  UNIQUE (cellar_id, name, year)
+);
+
+INSERT INTO wines
+  (wine_id, cellar_id, name, year, image, comment, comment_updated_at, style, producer_id,
+   region_id, drink_from, drink_until)
+SELECT wine_id, 1, name, year, image, comment, comment_updated_at, style, producer_id,
+       region_id, drink_from, drink_until
+FROM wines_copy;
+
+DROP TABLE wines_copy;
+
+CREATE INDEX wines_style ON wines (style);
+
+CREATE TRIGGER wines_search_insert AFTER INSERT ON wines BEGIN
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = NEW.wine_id;
+END;
+
+CREATE TRIGGER wines_search_update AFTER UPDATE OF name, producer_id, comment ON wines BEGIN
+  DELETE FROM wine_search WHERE rowid = OLD.wine_id;
+  INSERT INTO wine_search (rowid, name, producer, note, tastings, grapes, pairings)
+  SELECT * FROM wine_search_source WHERE wine_id = NEW.wine_id;
+END;
+
+CREATE TRIGGER wines_search_delete AFTER DELETE ON wines BEGIN
+  DELETE FROM wine_search WHERE rowid = OLD.wine_id;
+END;
+
+CREATE TEMP TABLE locations_copy AS SELECT * FROM locations;
+DROP TABLE locations;
+
+CREATE TABLE locations (
+  location_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  cellar_id INTEGER NOT NULL REFERENCES cellars(cellar_id),
+  name TEXT NOT NULL COLLATE NOCASE,
+  UNIQUE (cellar_id, name)
+);
+
+INSERT INTO locations (location_id, cellar_id, name)
+SELECT location_id, 1, name FROM locations_copy;
+
+DROP TABLE locations_copy;
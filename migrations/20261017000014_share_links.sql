@@ -0,0 +1,13 @@
+-- Read-only links to a cellar for people without an account. As with sessions, only the
+-- SHA-256 of the token in the link is stored. Links are revoked by deleting them, and stop
+-- working after expires_at if it is set.
+CREATE TABLE share_links (
+  share_id INTEGER PRIMARY KEY,
+  cellar_id INTEGER NOT NULL REFERENCES cellars(cellar_id),
+  token_hash TEXT NOT NULL UNIQUE,
+  label TEXT NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at DATETIME
+);
+
+CREATE INDEX share_links_cellar_id ON share_links (cellar_id);
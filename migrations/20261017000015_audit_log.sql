@@ -0,0 +1,21 @@
+-- Who changed what in a cellar, and when. before and after are JSON snapshots of what was
+-- changed, NULL when it was added or deleted. wine_id has no foreign key and the wine's name
+-- is copied: the history of a deleted wine is what the log is most often read for. The
+-- username is copied too, so entries outlive their accounts. dt is local time, like the
+-- dates of inventory events.
+CREATE TABLE audit_log (
+  audit_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  cellar_id INTEGER NOT NULL REFERENCES cellars(cellar_id),
+  dt DATETIME NOT NULL,
+  username TEXT NOT NULL,
+  entity TEXT NOT NULL CHECK (entity IN ('wine', 'grapes', 'pairing', 'event', 'image', 'comment')),
+  entity_id INTEGER NOT NULL,
+  wine_id INTEGER NOT NULL,
+  wine TEXT NOT NULL,
+  action TEXT NOT NULL CHECK (action IN ('add', 'update', 'delete')),
+  before TEXT,
+  after TEXT
+);
+
+CREATE INDEX audit_log_cellar_id ON audit_log (cellar_id, audit_id);
+CREATE INDEX audit_log_wine_id ON audit_log (wine_id, audit_id);
@@ -0,0 +1,31 @@
+-- Deleting a wine moves it to the trash: deleted_at is set and the wine is hidden, with its
+-- grapes, pairings, events and bottles, until it is restored or purged for good. A trashed
+-- wine keeps its name and vintage, which stay unique in the cellar.
+ALTER TABLE wines ADD COLUMN deleted_at DATETIME;
+
+CREATE INDEX wines_deleted_at ON wines (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- Restoring and purging are logged as actions of their own, which the CHECK has to allow.
+-- Nothing references audit_log, so it can simply be rebuilt.
+CREATE TABLE audit_log_new (
+  audit_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  cellar_id INTEGER NOT NULL REFERENCES cellars(cellar_id),
+  dt DATETIME NOT NULL,
+  username TEXT NOT NULL,
+  entity TEXT NOT NULL CHECK (entity IN ('wine', 'grapes', 'pairing', 'event', 'image', 'comment')),
+  entity_id INTEGER NOT NULL,
+  wine_id INTEGER NOT NULL,
+  wine TEXT NOT NULL,
+  action TEXT NOT NULL CHECK (action IN ('add', 'update', 'delete', 'restore', 'purge')),
+  before TEXT,
+  after TEXT
+);
+
+INSERT INTO audit_log_new SELECT * FROM audit_log;
+
+DROP TABLE audit_log;
+
+ALTER TABLE audit_log_new RENAME TO audit_log;
+
+CREATE INDEX audit_log_cellar_id ON audit_log (cellar_id, audit_id);
+CREATE INDEX audit_log_wine_id ON audit_log (wine_id, audit_id);
@@ -0,0 +1,29 @@
+-- Tastings, bottles going in and out of racks, and the locations and racks themselves are
+-- logged too. Locations and racks don't belong to a wine, so their entries have no wine_id or
+-- wine. Bottles have no id of their own and are logged under their wine's. The CHECK has to
+-- allow the new entities, so the table is rebuilt like in the wine_trash migration.
+CREATE TABLE audit_log_new (
+  audit_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  cellar_id INTEGER NOT NULL REFERENCES cellars(cellar_id),
+  dt DATETIME NOT NULL,
+  username TEXT NOT NULL,
+  entity TEXT NOT NULL CHECK (entity IN ('wine', 'grapes', 'pairing', 'event', 'image', 'comment',
+                                         'tasting', 'bottle', 'location', 'rack')),
+  entity_id INTEGER NOT NULL,
+  wine_id INTEGER,
+  wine TEXT,
+  action TEXT NOT NULL CHECK (action IN ('add', 'update', 'delete', 'restore', 'purge')),
+  before TEXT,
+  after TEXT,
+  CHECK ((wine_id IS NULL) = (wine IS NULL)),
+  CHECK ((wine_id IS NULL) = (entity IN ('location', 'rack')))
+);
+
+INSERT INTO audit_log_new SELECT * FROM audit_log;
+
+DROP TABLE audit_log;
+
+ALTER TABLE audit_log_new RENAME TO audit_log;
+
+CREATE INDEX audit_log_cellar_id ON audit_log (cellar_id, audit_id);
+CREATE INDEX audit_log_wine_id ON audit_log (wine_id, audit_id);
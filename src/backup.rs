@@ -0,0 +1,255 @@
+//! Scheduled database snapshots. When `WINE_BACKUP_DIR` is set, a background task writes a
+//! timestamped `VACUUM INTO` snapshot to that directory on an interval and prunes old ones,
+//! keeping the newest snapshot of each of the last few days and weeks.
+
+use anyhow::Context;
+
+use crate::db;
+
+const FILE_PREFIX: &str = "wine-cellar-";
+const FILE_SUFFIX: &str = ".db";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+/// Added to a snapshot's name while it is being written.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    pub dir: std::path::PathBuf,
+    pub interval: std::time::Duration,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+pub(crate) fn env_or<T>(name: &str, default: T) -> anyhow::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match std::env::var(name) {
+        Ok(v) => v.parse().with_context(|| format!("Invalid {name}: {v}")),
+        Err(_) => Ok(default),
+    }
+}
+
+impl Config {
+    /// Reads the schedule from the environment. Returns `None` when `WINE_BACKUP_DIR` isn't
+    /// set, which disables scheduled backups.
+    pub(crate) fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(dir) = std::env::var("WINE_BACKUP_DIR") else {
+            return Ok(None);
+        };
+        let hours: u64 = env_or("WINE_BACKUP_INTERVAL_HOURS", 24)?;
+        anyhow::ensure!(hours > 0, "WINE_BACKUP_INTERVAL_HOURS must be at least 1");
+        Ok(Some(Self {
+            dir: dir.into(),
+            interval: std::time::Duration::from_secs(hours * 60 * 60),
+            keep_daily: env_or("WINE_BACKUP_KEEP_DAILY", 7)?,
+            keep_weekly: env_or("WINE_BACKUP_KEEP_WEEKLY", 4)?,
+        }))
+    }
+}
+
+/// What the admin page shows about scheduled backups.
+#[derive(Debug, Default)]
+pub(crate) struct Status {
+    pub config: Option<Config>,
+    pub last_success: Option<chrono::NaiveDateTime>,
+    pub last_error: Option<(chrono::NaiveDateTime, String)>,
+}
+
+pub(crate) type StatusHandle = std::sync::Arc<std::sync::Mutex<Status>>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Snapshot {
+    pub path: std::path::PathBuf,
+    pub taken_at: chrono::NaiveDateTime,
+    pub size: u64,
+}
+
+impl Snapshot {
+    pub(crate) fn file_name(&self) -> std::borrow::Cow<'_, str> {
+        self.path.file_name().unwrap_or_default().to_string_lossy()
+    }
+}
+
+fn parse_file_name(name: &str) -> Option<chrono::NaiveDateTime> {
+    let ts = name.strip_prefix(FILE_PREFIX)?.strip_suffix(FILE_SUFFIX)?;
+    chrono::NaiveDateTime::parse_from_str(ts, TIMESTAMP_FORMAT).ok()
+}
+
+/// Lists the snapshots in `dir`, newest first. Files not named like a snapshot are ignored.
+pub(crate) async fn list_snapshots(dir: &std::path::Path) -> std::io::Result<Vec<Snapshot>> {
+    let mut snapshots = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let Some(taken_at) = parse_file_name(&entry.file_name().to_string_lossy()) else {
+            continue;
+        };
+        snapshots.push(Snapshot {
+            path: entry.path(),
+            taken_at,
+            size: entry.metadata().await?.len(),
+        });
+    }
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.taken_at));
+    Ok(snapshots)
+}
+
+/// Picks the snapshots to delete from `snapshots` (sorted newest first). Keeps the newest
+/// snapshot overall, the newest of each of the last `keep_daily` days that have a snapshot,
+/// and the newest of each of the last `keep_weekly` ISO weeks that have one.
+fn to_prune(snapshots: &[Snapshot], keep_daily: usize, keep_weekly: usize) -> Vec<&Snapshot> {
+    use chrono::Datelike;
+
+    let mut keep = vec![false; snapshots.len()];
+    if let Some(newest) = keep.first_mut() {
+        *newest = true;
+    }
+    let mut days = Vec::new();
+    let mut weeks = Vec::new();
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        let day = snapshot.taken_at.date();
+        if days.len() < keep_daily && !days.contains(&day) {
+            days.push(day);
+            keep[i] = true;
+        }
+        let week = day.iso_week();
+        if weeks.len() < keep_weekly && !weeks.contains(&week) {
+            weeks.push(week);
+            keep[i] = true;
+        }
+    }
+    snapshots
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| !keep)
+        .map(|(s, _)| s)
+        .collect()
+}
+
+/// Deletes the snapshots in `dir` that a crash or a failed backup left half written.
+async fn remove_partials(dir: &std::path::Path) -> std::io::Result<()> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(FILE_PREFIX) && name.ends_with(PARTIAL_SUFFIX) {
+            tracing::info!("Removing unfinished backup {}", entry.path().display());
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes one snapshot and prunes old ones. The snapshot is written under a temporary name
+/// and renamed, so a failed backup never looks like a complete one; what earlier failed runs
+/// left is deleted first.
+#[tracing::instrument(skip(db))]
+async fn backup_once(db: &sqlx::SqlitePool, config: &Config) -> anyhow::Result<()> {
+    tokio::fs::create_dir_all(&config.dir)
+        .await
+        .with_context(|| format!("Create {}", config.dir.display()))?;
+    remove_partials(&config.dir).await?;
+    let name = format!(
+        "{FILE_PREFIX}{}{FILE_SUFFIX}",
+        chrono::Local::now().format(TIMESTAMP_FORMAT)
+    );
+    let path = config.dir.join(&name);
+    let partial = config.dir.join(format!("{name}{PARTIAL_SUFFIX}"));
+    db::backup_to(db, &partial).await?;
+    tokio::fs::rename(&partial, &path).await?;
+    tracing::info!("Wrote backup {}", path.display());
+
+    let snapshots = list_snapshots(&config.dir).await?;
+    for snapshot in to_prune(&snapshots, config.keep_daily, config.keep_weekly) {
+        tracing::info!("Pruning backup {}", snapshot.path.display());
+        tokio::fs::remove_file(&snapshot.path).await?;
+    }
+    Ok(())
+}
+
+/// Runs forever, taking a snapshot right away and then every `config.interval`.
+pub(crate) async fn run(db: sqlx::SqlitePool, config: Config, status: StatusHandle) {
+    let mut interval = tokio::time::interval(config.interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let res = backup_once(&db, &config).await;
+        let now = chrono::Local::now().naive_local();
+        let mut status = status.lock().expect("backup status lock poisoned");
+        match res {
+            Ok(()) => status.last_success = Some(now),
+            Err(e) => {
+                tracing::error!("Scheduled backup failed: {e:#}");
+                status.last_error = Some((now, format!("{e:#}")));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(ts: &str) -> Snapshot {
+        Snapshot {
+            path: format!("{FILE_PREFIX}{ts}{FILE_SUFFIX}").into(),
+            taken_at: parse_file_name(&format!("{FILE_PREFIX}{ts}{FILE_SUFFIX}")).unwrap(),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert!(parse_file_name("wine-cellar-20260331-120000.db").is_some());
+        assert!(parse_file_name("wine-cellar-20260331-120000.db.partial").is_none());
+        assert!(parse_file_name("data.db").is_none());
+    }
+
+    #[test]
+    fn test_to_prune() {
+        // Noon every day of March 2026, plus an extra early snapshot on the last day
+        let mut snapshots: Vec<_> = (1..=31)
+            .rev()
+            .map(|day| snapshot(&format!("202603{day:02}-120000")))
+            .collect();
+        snapshots.insert(1, snapshot("20260331-000000"));
+
+        let pruned: Vec<_> = to_prune(&snapshots, 3, 4)
+            .iter()
+            .map(|s| s.taken_at.format("%d %H").to_string())
+            .collect();
+        let kept: Vec<_> = snapshots
+            .iter()
+            .map(|s| s.taken_at.format("%d %H").to_string())
+            .filter(|s| !pruned.contains(s))
+            .collect();
+        // The last 3 days, then the newest of the weeks starting Mar 23, 16 and 9
+        assert_eq!(kept, ["31 12", "30 12", "29 12", "22 12", "15 12"]);
+        assert!(pruned.contains(&"31 00".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_remove_partials() {
+        let dir = db::scratch_path("backup-dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let names = [
+            "wine-cellar-20260331-120000.db",
+            "wine-cellar-20260331-130000.db.partial",
+            "notes.partial",
+        ];
+        for name in names {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        remove_partials(&dir).await.unwrap();
+        let left: Vec<_> = names.iter().filter(|n| dir.join(n).exists()).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(left, [&names[0], &names[2]]);
+    }
+
+    #[test]
+    fn test_to_prune_keeps_newest() {
+        let snapshots = [snapshot("20260331-120000")];
+        assert!(to_prune(&snapshots, 0, 0).is_empty());
+    }
+}
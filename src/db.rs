@@ -2,6 +2,7 @@ use std::str::FromStr;
 
 use anyhow::Context;
 
+#[derive(serde::Serialize, Debug)]
 pub(crate) struct FoodPairing {
     pub id: i64,
     pub food: String,
@@ -15,20 +16,625 @@ pub(crate) struct WineWithPairings {
     pub matched_pairings: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(serde::Serialize, Debug)]
 pub(crate) struct Wine {
     pub wine_id: i64,
     pub name: String,
     pub year: i64,
     pub has_image: bool,
+    pub style: Option<WineStyle>,
+    pub producer: Option<String>,
+    pub region_id: Option<i64>,
+    pub drink_from: Option<i64>,
+    pub drink_until: Option<i64>,
     pub comment: Option<String>,
     pub comment_updated_at: Option<chrono::NaiveDateTime>,
 }
 
-#[derive(sqlx::FromRow, Debug)]
+/// The user-editable fields of a wine, shared by [`add_wine`] and [`update_wine`].
+#[derive(Debug)]
+pub(crate) struct WineFields<'a> {
+    pub name: &'a str,
+    pub year: i64,
+    pub style: Option<WineStyle>,
+    pub producer_id: Option<i64>,
+    pub region_id: Option<i64>,
+    pub drink_from: Option<i64>,
+    pub drink_until: Option<i64>,
+}
+
+/// The colour/style of a wine, stored lowercase in `wines.style`. Wines added before styles
+/// existed have none.
+#[derive(sqlx::Type, serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum WineStyle {
+    Red,
+    White,
+    Rose,
+    Sparkling,
+    Fortified,
+    Dessert,
+}
+
+impl WineStyle {
+    pub(crate) const ALL: [Self; 6] = [
+        Self::Red,
+        Self::White,
+        Self::Rose,
+        Self::Sparkling,
+        Self::Fortified,
+        Self::Dessert,
+    ];
+
+    /// The value stored in the database and used in forms and query strings.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Red => "red",
+            Self::White => "white",
+            Self::Rose => "rose",
+            Self::Sparkling => "sparkling",
+            Self::Fortified => "fortified",
+            Self::Dessert => "dessert",
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Red => "Red",
+            Self::White => "White",
+            Self::Rose => "Rosé",
+            Self::Sparkling => "Sparkling",
+            Self::Fortified => "Fortified",
+            Self::Dessert => "Dessert",
+        }
+    }
+}
+
+impl FromStr for WineStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|style| style.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown wine style: {s}"))
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct Producer {
+    pub producer_id: i64,
+    pub name: String,
+}
+
+/// Level in the region hierarchy: countries contain regions, which contain appellations.
+#[derive(sqlx::Type, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RegionKind {
+    Country,
+    Region,
+    Appellation,
+}
+
+impl RegionKind {
+    /// The kind of region that can be added below this one, if any.
+    pub(crate) fn child(self) -> Option<Self> {
+        match self {
+            Self::Country => Some(Self::Region),
+            Self::Region => Some(Self::Appellation),
+            Self::Appellation => None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct Region {
+    pub region_id: i64,
+    pub parent_id: Option<i64>,
+    pub kind: RegionKind,
+    pub name: String,
+}
+
+/// Narrows down the rows returned by [`wine_rows`]. A wine must match every filter that is
+/// set; fields left as `None`, empty or `false` don't filter.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct WineFilter {
+    /// Matches wines made from any of these grapes.
+    pub grapes: Vec<String>,
+    pub style: Option<WineStyle>,
+    pub producer_id: Option<i64>,
+    /// Matches wines from this region or any region below it.
+    pub region_id: Option<i64>,
+    /// Earliest vintage.
+    pub year_from: Option<i64>,
+    /// Latest vintage.
+    pub year_to: Option<i64>,
+    /// Only wines with at least one bottle in the cellar.
+    pub in_stock: bool,
+    pub has_image: bool,
+    pub has_note: bool,
+    /// Matches wines with a food pairing containing this (case-insensitive).
+    pub pairing: Option<String>,
+}
+
+/// What the wine table can be sorted by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WineSort {
+    #[default]
+    Name,
+    Year,
+    Bottles,
+    /// The latest inventory event.
+    LastActivity,
+    Score,
+}
+
+impl WineSort {
+    pub(crate) const ALL: [Self; 5] = [
+        Self::Name,
+        Self::Year,
+        Self::Bottles,
+        Self::LastActivity,
+        Self::Score,
+    ];
+
+    /// Also what [`query_wine_rows`] matches on.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Year => "year",
+            Self::Bottles => "bottles",
+            Self::LastActivity => "activity",
+            Self::Score => "score",
+        }
+    }
+}
+
+impl FromStr for WineSort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|sort| sort.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown sort: {s}"))
+    }
+}
+
+/// The order of the rows returned by [`wine_rows_page`]. Ties are broken by name and year,
+/// and wines without a score or any activity come last either way.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct WineOrder {
+    pub sort: WineSort,
+    pub descending: bool,
+}
+
+/// Everything the wine table shows for one wine, fetched together by [`wine_rows`].
+#[derive(Debug)]
+pub(crate) struct WineRow {
+    pub wine_id: i64,
+    pub name: String,
+    pub year: i64,
+    pub style: Option<WineStyle>,
+    pub producer: Option<String>,
+    /// The wine's region followed by its parents, e.g. "Pauillac, Bordeaux, France".
+    pub origin: Option<String>,
+    pub drink_from: Option<i64>,
+    pub drink_until: Option<i64>,
+    pub has_image: bool,
+    pub comment: Option<String>,
+    pub bottles: i64,
+    pub grapes: Vec<String>,
+    pub pairings: Vec<String>,
+    /// Average tasting score as a fraction of each tasting's scale, between 0 and 1.
+    pub score: Option<f64>,
+    /// When the latest inventory event happened.
+    pub last_activity: Option<chrono::NaiveDateTime>,
+}
+
+/// Separator used with `group_concat` when aggregating grapes and pairings. The ASCII unit
+/// separator can't be typed into a form, so it never shows up in the values themselves.
+const LIST_SEPARATOR: char = '\u{1f}';
+
+/// Escapes `%`, `_` and `\` for a `LIKE ... ESCAPE '\'` pattern.
+fn like_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn split_list(list: Option<String>) -> Vec<String> {
+    list.map(|l| l.split(LIST_SEPARATOR).map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+#[derive(sqlx::FromRow, serde::Serialize, Debug)]
 pub(crate) struct WineInvEvent {
+    pub event_id: i64,
+    pub dt: chrono::NaiveDateTime,
+    pub kind: EventKind,
+    pub bottles: i64,
+    /// Price per bottle in minor units (e.g. cents) of `currency`.
+    pub unit_price: Option<i64>,
+    pub currency: Option<String>,
+    pub vendor: Option<String>,
+}
+
+/// A price per bottle: `amount` is in the minor unit (e.g. cents) of the ISO 4217 `currency`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Price {
+    pub amount: i64,
+    pub currency: String,
+}
+
+/// A change to a wine's bottle count, see [`add_wine_event`].
+#[derive(Debug)]
+pub(crate) struct NewWineEvent<'a> {
+    pub kind: EventKind,
+    /// Positive when bottles come in, negative when they go out.
+    pub bottles: i64,
     pub dt: chrono::NaiveDateTime,
+    /// Only purchases have a price.
+    pub price: Option<Price>,
+    pub vendor: Option<&'a str>,
+    /// Slots the bottles are put in, or taken from when they go out. Bottles without a slot
+    /// aren't placed anywhere.
+    pub slots: &'a [Slot],
+}
+
+impl NewWineEvent<'_> {
+    pub(crate) fn new(kind: EventKind, bottles: i64, dt: chrono::NaiveDateTime) -> Self {
+        Self {
+            kind,
+            bottles,
+            dt,
+            price: None,
+            vendor: None,
+            slots: &[],
+        }
+    }
+}
+
+/// What a wine's purchases in one currency cost, see [`wine_costs`].
+#[derive(Debug, Clone)]
+pub(crate) struct WineCost {
+    pub wine_id: i64,
+    pub name: String,
+    pub year: i64,
+    /// Bottles in the cellar now.
     pub bottles: i64,
+    pub currency: String,
+    /// Bottles bought with a price in this currency, and what they cost in minor units.
+    pub priced_bottles: i64,
+    pub total_cost: i64,
+    /// Bottles bought with a price in any currency.
+    pub all_priced_bottles: i64,
+}
+
+/// `num / den` rounded to the nearest integer, for non-negative `num` and positive `den`.
+fn div_round(num: i64, den: i64) -> i64 {
+    (num + den / 2) / den
+}
+
+impl WineCost {
+    /// Weighted average price per bottle of the purchases in this currency.
+    pub(crate) fn average_cost(&self) -> i64 {
+        div_round(self.total_cost, self.priced_bottles)
+    }
+
+    /// What the bottles in the cellar cost, in this currency. When a wine was bought in several
+    /// currencies, its bottles are split between them in proportion to the priced purchases,
+    /// so each bottle is only counted once.
+    pub(crate) fn value(&self) -> i64 {
+        div_round(
+            self.bottles.max(0) * self.total_cost,
+            self.all_priced_bottles,
+        )
+    }
+}
+
+/// A place bottles are kept, such as the cellar or a fridge.
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct Location {
+    pub location_id: i64,
+    pub name: String,
+}
+
+/// A grid of slots in a location. Rows and columns are numbered from 1.
+#[derive(serde::Serialize, Debug, Clone)]
+pub(crate) struct Rack {
+    pub rack_id: i64,
+    pub location_id: i64,
+    pub location: String,
+    pub name: String,
+    pub row_count: i64,
+    pub column_count: i64,
+}
+
+/// One bottle's place in a rack. Written as `rack_id:row:col` in forms.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Slot {
+    pub rack_id: i64,
+    pub row: i64,
+    pub col: i64,
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.rack_id, self.row, self.col)
+    }
+}
+
+impl std::str::FromStr for Slot {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let mut parts = s.split(':').map(str::parse);
+            let slot = Slot {
+                rack_id: parts.next()?.ok()?,
+                row: parts.next()?.ok()?,
+                col: parts.next()?.ok()?,
+            };
+            parts.next().is_none().then_some(slot)
+        };
+        parse().ok_or_else(|| anyhow::anyhow!("Invalid slot: {s}"))
+    }
+}
+
+/// A bottle in a slot: where it is and which wine it is.
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct PlacedBottle {
+    pub rack_id: i64,
+    pub row: i64,
+    pub col: i64,
+    pub location_id: i64,
+    pub location: String,
+    pub rack: String,
+    pub wine_id: i64,
+    pub name: String,
+    pub year: i64,
+    pub style: Option<WineStyle>,
+}
+
+impl PlacedBottle {
+    pub(crate) fn slot(&self) -> Slot {
+        Slot {
+            rack_id: self.rack_id,
+            row: self.row,
+            col: self.col,
+        }
+    }
+}
+
+/// A bottle moved between slots, with the racks written as "Location › Rack".
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct BottleMove {
+    pub move_id: i64,
+    pub dt: chrono::NaiveDateTime,
+    pub from: Slot,
+    pub from_rack: String,
+    pub to: Slot,
+    pub to_rack: String,
+}
+
+/// A bottle of a wine that was opened and tasted.
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct Tasting {
+    pub tasting_id: i64,
+    pub wine_id: i64,
+    pub tasted_on: chrono::NaiveDate,
+    /// Out of `scale`.
+    pub score: i64,
+    pub scale: i64,
+    pub note: Option<String>,
+    pub occasion: Option<String>,
+    pub sheet: Option<TastingSheet>,
+}
+
+/// A tasting to record, see [`add_tasting`].
+#[derive(Debug)]
+pub(crate) struct NewTasting<'a> {
+    pub tasted_on: chrono::NaiveDate,
+    pub score: i64,
+    pub scale: i64,
+    pub note: Option<&'a str>,
+    pub occasion: Option<&'a str>,
+    pub sheet: Option<&'a TastingSheet>,
+}
+
+/// The structured part of a tasting: the palate's structure on a 1 (low) to 5 (high) scale,
+/// `None` where it wasn't assessed, and aromas from the [`aromas`] vocabulary.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct TastingSheet {
+    pub sweetness: Option<i64>,
+    pub acidity: Option<i64>,
+    pub tannin: Option<i64>,
+    pub body: Option<i64>,
+    pub finish: Option<i64>,
+    #[serde(default)]
+    pub aromas: Vec<String>,
+}
+
+impl TastingSheet {
+    pub(crate) fn level(&self, dimension: SheetDimension) -> Option<i64> {
+        match dimension {
+            SheetDimension::Sweetness => self.sweetness,
+            SheetDimension::Acidity => self.acidity,
+            SheetDimension::Tannin => self.tannin,
+            SheetDimension::Body => self.body,
+            SheetDimension::Finish => self.finish,
+        }
+    }
+
+    pub(crate) fn set_level(&mut self, dimension: SheetDimension, level: Option<i64>) {
+        *match dimension {
+            SheetDimension::Sweetness => &mut self.sweetness,
+            SheetDimension::Acidity => &mut self.acidity,
+            SheetDimension::Tannin => &mut self.tannin,
+            SheetDimension::Body => &mut self.body,
+            SheetDimension::Finish => &mut self.finish,
+        } = level;
+    }
+
+    /// Whether nothing was filled in, in which case no sheet is stored.
+    pub(crate) fn is_empty(&self) -> bool {
+        SheetDimension::ALL.iter().all(|&d| self.level(d).is_none()) && self.aromas.is_empty()
+    }
+
+    /// Checks the levels and that every aroma is in `vocabulary`.
+    pub(crate) fn validate(&self, vocabulary: &[Aroma]) -> anyhow::Result<()> {
+        for dimension in SheetDimension::ALL {
+            if let Some(level) = self.level(dimension) {
+                anyhow::ensure!(
+                    (1..=5).contains(&level),
+                    "{} must be between 1 and 5",
+                    dimension.label()
+                );
+            }
+        }
+        for aroma in &self.aromas {
+            anyhow::ensure!(
+                vocabulary
+                    .iter()
+                    .any(|a| a.name.eq_ignore_ascii_case(aroma)),
+                "Unknown aroma: {aroma}"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The structural dimensions on a [`TastingSheet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SheetDimension {
+    Sweetness,
+    Acidity,
+    Tannin,
+    Body,
+    Finish,
+}
+
+impl SheetDimension {
+    pub(crate) const ALL: [Self; 5] = [
+        Self::Sweetness,
+        Self::Acidity,
+        Self::Tannin,
+        Self::Body,
+        Self::Finish,
+    ];
+
+    /// The column and form field name.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Sweetness => "sweetness",
+            Self::Acidity => "acidity",
+            Self::Tannin => "tannin",
+            Self::Body => "body",
+            Self::Finish => "finish",
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Sweetness => "Sweetness",
+            Self::Acidity => "Acidity",
+            Self::Tannin => "Tannin",
+            Self::Body => "Body",
+            Self::Finish => "Finish",
+        }
+    }
+
+    /// What levels 1 to 5 are called.
+    pub(crate) fn levels(self) -> [&'static str; 5] {
+        match self {
+            Self::Sweetness => ["Dry", "Off-dry", "Medium", "Sweet", "Luscious"],
+            Self::Acidity | Self::Tannin => ["Low", "Medium-", "Medium", "Medium+", "High"],
+            Self::Body => ["Light", "Medium-", "Medium", "Medium+", "Full"],
+            Self::Finish => ["Short", "Medium-", "Medium", "Medium+", "Long"],
+        }
+    }
+}
+
+/// An aroma from the vocabulary offered on the tasting sheet.
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct Aroma {
+    pub aroma_id: i64,
+    pub family: String,
+    pub name: String,
+}
+
+/// Why the number of bottles changed, stored lowercase in `wine_inventory_events.kind`.
+#[derive(sqlx::Type, serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EventKind {
+    Purchase,
+    Consumption,
+    /// Received (positive) or given away (negative).
+    Gift,
+    /// Broken, corked or otherwise lost.
+    Loss,
+    /// Fixes the count after a stock-take; CSV imports are recorded as corrections.
+    Correction,
+    /// Moved to (positive) or from (negative) somewhere else.
+    Transfer,
+}
+
+impl EventKind {
+    pub(crate) const ALL: [Self; 6] = [
+        Self::Purchase,
+        Self::Consumption,
+        Self::Gift,
+        Self::Loss,
+        Self::Correction,
+        Self::Transfer,
+    ];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Purchase => "purchase",
+            Self::Consumption => "consumption",
+            Self::Gift => "gift",
+            Self::Loss => "loss",
+            Self::Correction => "correction",
+            Self::Transfer => "transfer",
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Purchase => "Purchase",
+            Self::Consumption => "Consumption",
+            Self::Gift => "Gift",
+            Self::Loss => "Loss",
+            Self::Correction => "Correction",
+            Self::Transfer => "Transfer",
+        }
+    }
+
+    /// Whether events of this kind add (`Some(true)`) or remove (`Some(false)`) bottles, or
+    /// `None` when they can go either way.
+    pub(crate) fn adds_bottles(self) -> Option<bool> {
+        match self {
+            Self::Purchase => Some(true),
+            Self::Consumption | Self::Loss => Some(false),
+            Self::Gift | Self::Correction | Self::Transfer => None,
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown event kind: {s}"))
+    }
 }
 
 pub(crate) async fn connect() -> anyhow::Result<sqlx::SqlitePool> {
@@ -44,21 +650,367 @@ pub(crate) async fn connect() -> anyhow::Result<sqlx::SqlitePool> {
     Ok(db)
 }
 
+/// The migrations embedded at compile time; a database is only compatible with this build if
+/// its applied migrations are a prefix of these.
+pub(crate) static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
+
+/// Returns a unique path in the system temp directory for scratch database files (backups on
+/// their way to the client, uploaded restores). The caller is responsible for removing it.
+pub(crate) fn scratch_path(label: &str) -> std::path::PathBuf {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    std::env::temp_dir().join(format!(
+        "wine-cellar-{label}-{}-{}-{n}.db",
+        std::process::id(),
+        chrono::Local::now().timestamp_micros()
+    ))
+}
+
+/// An uploaded backup that can't be restored. The message is safe to show to the user.
+#[derive(Debug)]
+pub(crate) struct InvalidBackup(String);
+
+impl std::fmt::Display for InvalidBackup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidBackup {}
+
+/// An inventory change that was rejected. The message is safe to show to the user.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum InventoryError {
+    NoBottles,
+    NotEnoughBottles {
+        in_stock: i64,
+        requested: i64,
+    },
+    /// Editing or deleting an event would leave this many (negative) bottles in stock.
+    StockBelowZero(i64),
+    /// The wine's events would leave this many (negative) bottles in stock at the end of a
+    /// day, even though the total comes out right.
+    StockBelowZeroOn {
+        date: chrono::NaiveDate,
+        in_stock: i64,
+    },
+    /// Fewer bottles would be in stock than are placed in racks.
+    BottlesInRacks {
+        in_stock: i64,
+        placed: i64,
+    },
+    TooManySlots,
+    /// Only this many of the wine's bottles in stock aren't in a rack.
+    NotEnoughUnplaced(i64),
+    NoSuchSlot(Slot),
+    SlotTaken(Slot),
+    /// The slot holds a bottle of the named wine, which is in the trash.
+    SlotInTrash {
+        slot: Slot,
+        wine: String,
+    },
+    /// The slot doesn't hold a bottle of the wine.
+    NotInSlot(Slot),
+    WrongDirection(EventKind),
+    PriceNotAllowed(EventKind),
+}
+
+impl std::fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoBottles => f.write_str("The number of bottles must be at least 1"),
+            Self::NotEnoughBottles {
+                in_stock,
+                requested,
+            } => write!(
+                f,
+                "Can't remove {requested} bottle(s), only {in_stock} in stock"
+            ),
+            Self::StockBelowZero(in_stock) => write!(
+                f,
+                "That would leave {in_stock} bottle(s) in stock; fix the later events first"
+            ),
+            Self::StockBelowZeroOn { date, in_stock } => write!(
+                f,
+                "That would leave {in_stock} bottle(s) in stock on {date}; check the events' dates"
+            ),
+            Self::BottlesInRacks { in_stock, placed } => write!(
+                f,
+                "{placed} bottle(s) are in racks but only {in_stock} would be in stock; \
+                 pick the slots the bottles were taken from"
+            ),
+            Self::TooManySlots => f.write_str("More slots were picked than there are bottles"),
+            Self::NotEnoughUnplaced(unplaced) => {
+                write!(f, "Only {unplaced} bottle(s) aren't in a rack yet")
+            }
+            Self::NoSuchSlot(slot) => write!(
+                f,
+                "Rack {} has no row {}, column {}",
+                slot.rack_id, slot.row, slot.col
+            ),
+            Self::SlotTaken(slot) => write!(
+                f,
+                "Row {}, column {} of rack {} already holds a bottle",
+                slot.row, slot.col, slot.rack_id
+            ),
+            Self::SlotInTrash { slot, wine } => write!(
+                f,
+                "Row {}, column {} of rack {} holds a bottle of {wine}, which is in the trash; \
+                 restore it and move the bottle, or delete the wine for good",
+                slot.row, slot.col, slot.rack_id
+            ),
+            Self::NotInSlot(slot) => write!(
+                f,
+                "Row {}, column {} of rack {} doesn't hold a bottle of this wine",
+                slot.row, slot.col, slot.rack_id
+            ),
+            Self::WrongDirection(kind) => match kind.adds_bottles() {
+                Some(true) => write!(f, "A {} must add bottles", kind.as_str()),
+                _ => write!(f, "A {} must remove bottles", kind.as_str()),
+            },
+            Self::PriceNotAllowed(kind) => write!(f, "A {} can't have a price", kind.as_str()),
+        }
+    }
+}
+
+impl std::error::Error for InventoryError {}
+
+/// Writes a consistent snapshot of the whole database to `path` using `VACUUM INTO`. Safe to
+/// run while the server is serving requests; the snapshot is a standalone file without WAL.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn backup_to(db: &sqlx::SqlitePool, path: &std::path::Path) -> anyhow::Result<()> {
+    let path = path.to_str().context("Backup path is not valid UTF-8")?;
+    sqlx::query!("VACUUM INTO $1", path).execute(db).await?;
+    Ok(())
+}
+
+/// Checks that `path` is an intact SQLite database whose applied migrations are a prefix of
+/// [`MIGRATOR`], then migrates it forward so its schema matches ours exactly.
+#[tracing::instrument]
+async fn prepare_restore(path: &std::path::Path) -> anyhow::Result<()> {
+    let invalid = |msg: &str| InvalidBackup(msg.to_owned());
+    let opts = sqlx::sqlite::SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(false);
+    let upload = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(opts)
+        .await
+        .map_err(|_| invalid("Not an SQLite database"))?;
+    let res = check_and_migrate(&upload).await;
+    upload.close().await;
+    res
+}
+
+async fn check_and_migrate(upload: &sqlx::SqlitePool) -> anyhow::Result<()> {
+    use sqlx::migrate::Migrate;
+
+    let invalid = |msg: &str| InvalidBackup(msg.to_owned());
+    let mut conn = upload.acquire().await?;
+
+    // Plain query: this runs against the uploaded file, not the schema sqlx checks at compile time
+    let check: String = sqlx::query_scalar("PRAGMA quick_check")
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|_| invalid("Not an SQLite database"))?;
+    if check != "ok" {
+        return Err(invalid("The database file is corrupt").into());
+    }
+
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .map_err(|_| invalid("Not a wine cellar database"))?;
+    let known: Vec<_> = MIGRATOR
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .collect();
+    if applied.len() > known.len() {
+        return Err(invalid("The backup was made by a newer version of wine cellar").into());
+    }
+    for (applied, known) in applied.iter().zip(&known) {
+        if applied.version != known.version || applied.checksum != known.checksum {
+            return Err(invalid(&format!(
+                "The backup's migration {} does not match this version of wine cellar",
+                applied.version
+            ))
+            .into());
+        }
+    }
+
+    drop(conn);
+    MIGRATOR.run(upload).await?;
+    Ok(())
+}
+
+/// Statements that replace each table's contents with the attached `restore` database's.
+/// Parents come before children so inserts satisfy foreign keys; deletes run in reverse.
+/// Every table created by a migration must be listed here, which a test checks, except the
+/// `wine_search` index, which its triggers rebuild as the rows are inserted, and the accounts
+/// (`users` and `sessions`), which belong to the running instance rather than to the cellars.
+/// Cellar memberships are only restored for accounts that still exist. Share links are not
+/// restored either: a restore revokes them all, so a link revoked since the backup stays
+/// revoked.
+const RESTORE_TABLES: &[(&str, &str)] = &[
+    (
+        "DELETE FROM sqlite_sequence",
+        "INSERT INTO sqlite_sequence SELECT * FROM restore.sqlite_sequence",
+    ),
+    (
+        "DELETE FROM grapes",
+        "INSERT INTO grapes SELECT * FROM restore.grapes",
+    ),
+    (
+        "DELETE FROM producers",
+        "INSERT INTO producers SELECT * FROM restore.producers",
+    ),
+    (
+        "DELETE FROM regions",
+        "INSERT INTO regions SELECT * FROM restore.regions",
+    ),
+    (
+        "DELETE FROM cellars",
+        "INSERT INTO cellars SELECT * FROM restore.cellars",
+    ),
+    (
+        "DELETE FROM audit_log",
+        "INSERT INTO audit_log SELECT * FROM restore.audit_log",
+    ),
+    (
+        "DELETE FROM cellar_members",
+        "INSERT INTO cellar_members SELECT * FROM restore.cellar_members
+         WHERE user_id IN (SELECT user_id FROM users)",
+    ),
+    (
+        "DELETE FROM wines",
+        "INSERT INTO wines SELECT * FROM restore.wines",
+    ),
+    (
+        "DELETE FROM wine_grapes",
+        "INSERT INTO wine_grapes SELECT * FROM restore.wine_grapes",
+    ),
+    (
+        "DELETE FROM wine_inventory_events",
+        "INSERT INTO wine_inventory_events SELECT * FROM restore.wine_inventory_events",
+    ),
+    (
+        "DELETE FROM wine_food_pairings",
+        "INSERT INTO wine_food_pairings SELECT * FROM restore.wine_food_pairings",
+    ),
+    (
+        "DELETE FROM locations",
+        "INSERT INTO locations SELECT * FROM restore.locations",
+    ),
+    (
+        "DELETE FROM racks",
+        "INSERT INTO racks SELECT * FROM restore.racks",
+    ),
+    (
+        "DELETE FROM bottles",
+        "INSERT INTO bottles SELECT * FROM restore.bottles",
+    ),
+    (
+        "DELETE FROM bottle_moves",
+        "INSERT INTO bottle_moves SELECT * FROM restore.bottle_moves",
+    ),
+    (
+        "DELETE FROM tastings",
+        "INSERT INTO tastings SELECT * FROM restore.tastings",
+    ),
+    (
+        "DELETE FROM aromas",
+        "INSERT INTO aromas SELECT * FROM restore.aromas",
+    ),
+    (
+        "DELETE FROM tasting_sheets",
+        "INSERT INTO tasting_sheets SELECT * FROM restore.tasting_sheets",
+    ),
+    (
+        "DELETE FROM tasting_aromas",
+        "INSERT INTO tasting_aromas SELECT * FROM restore.tasting_aromas",
+    ),
+];
+
+/// Replaces the contents of the live database with the backup at `path`. The backup is
+/// validated (and migrated forward) first; the swap itself happens in one transaction, so
+/// readers see either the old cellars or the restored ones. Admins become owners of any
+/// restored cellar that none of the current accounts owns. Fails with [`InvalidBackup`] when
+/// the file can't be restored.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn restore_from(
+    db: &sqlx::SqlitePool,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    prepare_restore(path).await?;
+    let path = path.to_str().context("Restore path is not valid UTF-8")?;
+
+    // The statements below use plain queries: they reference the attached `restore` schema,
+    // which sqlx can't check at compile time. All SQL is static.
+    let mut conn = db.acquire().await?;
+    sqlx::query("ATTACH DATABASE $1 AS restore")
+        .bind(path)
+        .execute(&mut *conn)
+        .await?;
+    let res = async {
+        let mut trans = sqlx::Connection::begin(&mut *conn).await?;
+        // Sessions outlive the restore, but the cellars they were switched to may not
+        sqlx::query!("UPDATE sessions SET cellar_id = NULL")
+            .execute(&mut *trans)
+            .await?;
+        sqlx::query!("DELETE FROM share_links")
+            .execute(&mut *trans)
+            .await?;
+        for (delete, _) in RESTORE_TABLES.iter().rev() {
+            sqlx::query(delete).execute(&mut *trans).await?;
+        }
+        for (_, insert) in RESTORE_TABLES {
+            sqlx::query(insert).execute(&mut *trans).await?;
+        }
+        sqlx::query!(
+            "INSERT INTO cellar_members (cellar_id, user_id, role)
+             SELECT c.cellar_id, u.user_id, 'owner' FROM cellars c, users u
+             WHERE u.is_admin
+               AND NOT EXISTS (SELECT 1 FROM cellar_members m
+                               WHERE m.cellar_id = c.cellar_id AND m.role = 'owner')
+             ON CONFLICT (cellar_id, user_id) DO UPDATE SET role = 'owner'"
+        )
+        .execute(&mut *trans)
+        .await?;
+        trans.commit().await?;
+        anyhow::Ok(())
+    }
+    .await;
+    sqlx::query("DETACH DATABASE restore")
+        .execute(&mut *conn)
+        .await?;
+    res
+}
+
 #[tracing::instrument(skip(db))]
-pub(crate) async fn wines(db: &sqlx::SqlitePool) -> anyhow::Result<Vec<Wine>> {
+pub(crate) async fn wines(db: &sqlx::SqlitePool, cellar_id: i64) -> anyhow::Result<Vec<Wine>> {
     let res = sqlx::query!(
-        "SELECT wine_id, name, year, image IS NOT NULL AS has_image, comment, comment_updated_at from wines"
+        r#"SELECT w.wine_id AS "wine_id!", w.name, w.year, w.style AS "style: WineStyle",
+                  p.name AS "producer?", w.region_id, w.drink_from, w.drink_until,
+                  w.image IS NOT NULL AS "has_image!: bool", w.comment, w.comment_updated_at
+           FROM wines w
+           LEFT JOIN producers p ON p.producer_id = w.producer_id
+           WHERE w.cellar_id = $1 AND w.deleted_at IS NULL
+           ORDER BY w.name COLLATE NOCASE, w.year, w.wine_id"#,
+        cellar_id
     )
     .fetch_all(db)
     .await?
     .into_iter()
-    // Not sure why we need this conversation here, but not below where we fetch a single
-    // wine
     .map(|r| Wine {
-        wine_id: r.wine_id, //.wine_id.expect("Will always have id"),
+        wine_id: r.wine_id,
         name: r.name,
         year: r.year,
-        has_image: r.has_image != 0,
+        style: r.style,
+        producer: r.producer,
+        region_id: r.region_id,
+        drink_from: r.drink_from,
+        drink_until: r.drink_until,
+        has_image: r.has_image,
         comment: r.comment,
         comment_updated_at: r.comment_updated_at,
     })
@@ -66,11 +1018,216 @@ pub(crate) async fn wines(db: &sqlx::SqlitePool) -> anyhow::Result<Vec<Wine>> {
     Ok(res)
 }
 
+/// Fetches the wine table rows (all the cellar's wines, or only `wine_id` when given, narrowed
+/// down by `filter`) with bottle counts, grapes and food pairings aggregated in a single query, so
+/// rendering the table costs one round trip regardless of the number of wines. A negative
+/// `limit` means no limit.
+#[tracing::instrument(skip(db))]
+async fn query_wine_rows(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: Option<i64>,
+    filter: &WineFilter,
+    order: WineOrder,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<WineRow>> {
+    // The grapes go in as a JSON array, so the list can be any length in a static query
+    let grapes = serde_json::to_string(&filter.grapes)?;
+    let pairing = filter
+        .pairing
+        .as_deref()
+        .map(|p| format!("%{}%", like_escape(p)));
+    let sort = order.sort.as_str();
+    let res = sqlx::query!(
+        r#"SELECT w.wine_id AS "wine_id!", w.name, w.year, w.style AS "style: WineStyle",
+                  p.name AS "producer?",
+                  NULLIF(concat_ws(', ', r1.name, r2.name, r3.name), '') AS "origin: String",
+                  w.drink_from, w.drink_until,
+                  w.image IS NOT NULL AS "has_image!: bool", w.comment,
+                  COALESCE(inv.bottles, 0) AS "bottles!: i64",
+                  g.grapes AS "grapes: String", fp.pairings AS "pairings: String",
+                  t.score AS "score: f64",
+                  inv.last_activity AS "last_activity: chrono::NaiveDateTime"
+           FROM wines w
+           LEFT JOIN producers p ON p.producer_id = w.producer_id
+           LEFT JOIN regions r1 ON r1.region_id = w.region_id
+           LEFT JOIN regions r2 ON r2.region_id = r1.parent_id
+           LEFT JOIN regions r3 ON r3.region_id = r2.parent_id
+           LEFT JOIN (SELECT wine_id, SUM(bottles) AS bottles, MAX(dt) AS last_activity
+                      FROM wine_inventory_events
+                      GROUP BY wine_id) inv ON inv.wine_id = w.wine_id
+           LEFT JOIN (SELECT wine_id,
+                             group_concat(grape_name, char(31) ORDER BY grape_name) AS grapes
+                      FROM wine_grapes
+                      GROUP BY wine_id) g ON g.wine_id = w.wine_id
+           LEFT JOIN (SELECT wine_id, group_concat(food, char(31) ORDER BY id) AS pairings
+                      FROM wine_food_pairings
+                      GROUP BY wine_id) fp ON fp.wine_id = w.wine_id
+           LEFT JOIN (SELECT wine_id, AVG(CAST(score AS REAL) / scale) AS score
+                      FROM tastings
+                      GROUP BY wine_id) t ON t.wine_id = w.wine_id
+           WHERE w.cellar_id = $16 AND w.deleted_at IS NULL
+             AND ($1 IS NULL OR w.wine_id = $1)
+             AND ($2 IS NULL OR w.style = $2)
+             AND ($3 IS NULL OR w.producer_id = $3)
+             AND ($4 IS NULL OR w.region_id IN (
+                 WITH RECURSIVE sub(region_id) AS (
+                     SELECT $4
+                     UNION ALL
+                     SELECT r.region_id FROM regions r JOIN sub ON r.parent_id = sub.region_id
+                 )
+                 SELECT region_id FROM sub))
+             AND (NOT $5 OR COALESCE(inv.bottles, 0) > 0)
+             AND (json_array_length($6) = 0 OR EXISTS (
+                 SELECT 1 FROM wine_grapes wg
+                 WHERE wg.wine_id = w.wine_id
+                   AND wg.grape_name IN (SELECT value FROM json_each($6))))
+             AND ($7 IS NULL OR w.year >= $7)
+             AND ($8 IS NULL OR w.year <= $8)
+             AND (NOT $9 OR w.image IS NOT NULL)
+             AND (NOT $10 OR w.comment IS NOT NULL)
+             AND ($11 IS NULL OR EXISTS (
+                 SELECT 1 FROM wine_food_pairings wfp
+                 WHERE wfp.wine_id = w.wine_id AND wfp.food LIKE $11 ESCAPE '\'))
+           ORDER BY
+             CASE WHEN $12 = 'name' AND NOT $13 THEN w.name END COLLATE NOCASE ASC,
+             CASE WHEN $12 = 'name' AND $13 THEN w.name END COLLATE NOCASE DESC,
+             CASE WHEN $12 = 'year' AND NOT $13 THEN w.year END ASC,
+             CASE WHEN $12 = 'year' AND $13 THEN w.year END DESC,
+             CASE WHEN $12 = 'bottles' AND NOT $13 THEN COALESCE(inv.bottles, 0) END ASC,
+             CASE WHEN $12 = 'bottles' AND $13 THEN COALESCE(inv.bottles, 0) END DESC,
+             CASE WHEN $12 = 'activity' AND NOT $13 THEN inv.last_activity END ASC NULLS LAST,
+             CASE WHEN $12 = 'activity' AND $13 THEN inv.last_activity END DESC NULLS LAST,
+             CASE WHEN $12 = 'score' AND NOT $13 THEN t.score END ASC NULLS LAST,
+             CASE WHEN $12 = 'score' AND $13 THEN t.score END DESC NULLS LAST,
+             w.name COLLATE NOCASE, w.year, w.wine_id
+           LIMIT $14 OFFSET $15"#,
+        wine_id,
+        filter.style,
+        filter.producer_id,
+        filter.region_id,
+        filter.in_stock,
+        grapes,
+        filter.year_from,
+        filter.year_to,
+        filter.has_image,
+        filter.has_note,
+        pairing,
+        sort,
+        order.descending,
+        limit,
+        offset,
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|r| WineRow {
+        wine_id: r.wine_id,
+        name: r.name,
+        year: r.year,
+        style: r.style,
+        producer: r.producer,
+        origin: r.origin,
+        drink_from: r.drink_from,
+        drink_until: r.drink_until,
+        has_image: r.has_image,
+        comment: r.comment,
+        bottles: r.bottles,
+        grapes: split_list(r.grapes),
+        pairings: split_list(r.pairings),
+        score: r.score,
+        last_activity: r.last_activity,
+    })
+    .collect();
+    Ok(res)
+}
+
+/// All the wines matching `filter`, by name.
+pub(crate) async fn wine_rows(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    filter: &WineFilter,
+) -> anyhow::Result<Vec<WineRow>> {
+    query_wine_rows(db, cellar_id, None, filter, WineOrder::default(), -1, 0).await
+}
+
+/// Up to `limit` of the wines matching `filter` in `order`, skipping the first `offset`.
+pub(crate) async fn wine_rows_page(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    filter: &WineFilter,
+    order: WineOrder,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<WineRow>> {
+    query_wine_rows(db, cellar_id, None, filter, order, limit, offset).await
+}
+
+pub(crate) async fn wine_row(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: i64,
+) -> anyhow::Result<WineRow> {
+    query_wine_rows(
+        db,
+        cellar_id,
+        Some(wine_id),
+        &WineFilter::default(),
+        WineOrder::default(),
+        -1,
+        0,
+    )
+    .await?
+    .pop()
+    .ok_or(sqlx::Error::RowNotFound.into())
+}
+
+/// Fails with [`sqlx::Error::RowNotFound`] unless the wine is in the cellar, and not in its
+/// trash. Everything else about a wine is reached through its wine_id, so this is what keeps
+/// cellars apart and trashed wines out of reach.
+async fn check_wine(
+    db: impl sqlx::SqliteExecutor<'_>,
+    cellar_id: i64,
+    wine_id: i64,
+) -> anyhow::Result<()> {
+    sqlx::query_scalar!(
+        "SELECT wine_id FROM wines WHERE wine_id = $1 AND cellar_id = $2 AND deleted_at IS NULL",
+        wine_id,
+        cellar_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(())
+}
+
 #[tracing::instrument(skip(db))]
-pub(crate) async fn get_wine(db: &sqlx::SqlitePool, id: i64) -> anyhow::Result<Wine> {
+pub(crate) async fn get_wine(
+    db: impl sqlx::SqliteExecutor<'_>,
+    cellar_id: i64,
+    id: i64,
+) -> anyhow::Result<Wine> {
+    find_wine(db, cellar_id, id, false).await
+}
+
+/// The wine if it is in the cellar and, depending on `trashed`, in its trash or not.
+async fn find_wine(
+    db: impl sqlx::SqliteExecutor<'_>,
+    cellar_id: i64,
+    id: i64,
+    trashed: bool,
+) -> anyhow::Result<Wine> {
     let res = sqlx::query!(
-        "SELECT wine_id, name, year, image IS NOT NULL AS has_image, comment, comment_updated_at from wines WHERE wine_id=$1",
-        id
+        r#"SELECT w.wine_id, w.name, w.year, w.style AS "style: WineStyle", p.name AS "producer?",
+                  w.region_id, w.drink_from, w.drink_until, w.image IS NOT NULL AS has_image,
+                  w.comment, w.comment_updated_at
+           FROM wines w
+           LEFT JOIN producers p ON p.producer_id = w.producer_id
+           WHERE w.wine_id=$1 AND w.cellar_id=$2 AND (w.deleted_at IS NOT NULL) = $3"#,
+        id,
+        cellar_id,
+        trashed
     )
     .fetch_one(db)
     .await?;
@@ -78,6 +1235,11 @@ pub(crate) async fn get_wine(db: &sqlx::SqlitePool, id: i64) -> anyhow::Result<W
         wine_id: res.wine_id,
         name: res.name,
         year: res.year,
+        style: res.style,
+        producer: res.producer,
+        region_id: res.region_id,
+        drink_from: res.drink_from,
+        drink_until: res.drink_until,
         has_image: res.has_image != 0,
         comment: res.comment,
         comment_updated_at: res.comment_updated_at,
@@ -87,11 +1249,16 @@ pub(crate) async fn get_wine(db: &sqlx::SqlitePool, id: i64) -> anyhow::Result<W
 #[tracing::instrument(skip(db))]
 pub(crate) async fn wine_inventory_events(
     db: &sqlx::SqlitePool,
+    cellar_id: i64,
     wine_id: i64,
 ) -> anyhow::Result<Vec<WineInvEvent>> {
+    check_wine(db, cellar_id, wine_id).await?;
     let res = sqlx::query_as!(
         WineInvEvent,
-        "SELECT dt,bottles from wine_inventory_events WHERE wine_id=$1",
+        r#"SELECT event_id AS "event_id!", dt, kind AS "kind: EventKind", bottles,
+                  unit_price, currency, vendor
+           FROM wine_inventory_events
+           WHERE wine_id=$1 ORDER BY dt, event_id"#,
         wine_id
     )
     .fetch_all(db)
@@ -100,287 +1267,4732 @@ pub(crate) async fn wine_inventory_events(
     Ok(res)
 }
 
+/// Gets one inventory event, scoped to wine_id so an event can't be read through another wine.
 #[tracing::instrument(skip(db))]
-pub(crate) async fn add_wine(db: &sqlx::SqlitePool, name: &str, year: i64) -> anyhow::Result<Wine> {
-    let wine_id = sqlx::query_scalar!(
-        "INSERT INTO wines (name, year) VALUES ($1, $2) RETURNING wine_id",
-        name,
-        year
+pub(crate) async fn get_wine_event(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: i64,
+    event_id: i64,
+) -> anyhow::Result<WineInvEvent> {
+    check_wine(db, cellar_id, wine_id).await?;
+    find_event(db, wine_id, event_id).await
+}
+
+async fn find_event(
+    db: impl sqlx::SqliteExecutor<'_>,
+    wine_id: i64,
+    event_id: i64,
+) -> anyhow::Result<WineInvEvent> {
+    let res = sqlx::query_as!(
+        WineInvEvent,
+        r#"SELECT event_id AS "event_id!", dt, kind AS "kind: EventKind", bottles,
+                  unit_price, currency, vendor
+           FROM wine_inventory_events
+           WHERE event_id=$1 AND wine_id=$2"#,
+        event_id,
+        wine_id
     )
     .fetch_one(db)
     .await?;
-    get_wine(db, wine_id).await
+    Ok(res)
 }
 
-#[tracing::instrument(skip(db))]
-pub(crate) async fn delete_wine(db: &sqlx::SqlitePool, wine_id: i64) -> anyhow::Result<()> {
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn add_wine(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine: &WineFields<'_>,
+) -> anyhow::Result<Wine> {
     let mut trans = db.begin().await?;
-    sqlx::query!("DELETE FROM wine_food_pairings WHERE wine_id=$1", wine_id)
-        .execute(&mut *trans)
-        .await?;
-    sqlx::query!("DELETE FROM wine_grapes WHERE wine_id=$1", wine_id)
-        .execute(&mut *trans)
-        .await?;
+    let wine_id = sqlx::query_scalar!(
+        r#"INSERT INTO wines
+             (name, year, style, producer_id, region_id, drink_from, drink_until, cellar_id)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING wine_id AS "wine_id!""#,
+        wine.name,
+        wine.year,
+        wine.style,
+        wine.producer_id,
+        wine.region_id,
+        wine.drink_from,
+        wine.drink_until,
+        cellar_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let res = get_wine(&mut *trans, cellar_id, wine_id).await?;
+    let change = Change::new(AuditEntity::Wine, wine_id, wine_id).after(Some(&res))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(res)
+}
+
+/// Replaces a wine's name, vintage, style, producer, region and drinking window. Inventory
+/// events, grapes, pairings, note and image are untouched. Returns a DB error (unique
+/// constraint) if another wine in the cellar already has the same name and year.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn update_wine(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    wine: &WineFields<'_>,
+) -> anyhow::Result<Wine> {
+    let mut trans = db.begin().await?;
+    let before = get_wine(&mut *trans, cellar_id, wine_id).await?;
     sqlx::query!(
-        "DELETE FROM wine_inventory_events WHERE wine_id=$1",
+        "UPDATE wines SET name=$2, year=$3, style=$4, producer_id=$5, region_id=$6,
+                          drink_from=$7, drink_until=$8
+         WHERE wine_id=$1 AND cellar_id=$9",
+        wine_id,
+        wine.name,
+        wine.year,
+        wine.style,
+        wine.producer_id,
+        wine.region_id,
+        wine.drink_from,
+        wine.drink_until,
+        cellar_id
+    )
+    .execute(&mut *trans)
+    .await?;
+    let res = get_wine(&mut *trans, cellar_id, wine_id).await?;
+    let change = Change::new(AuditEntity::Wine, wine_id, wine_id)
+        .before(Some(&before))?
+        .after(Some(&res))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(res)
+}
+
+/// Moves the wine to the trash, from which [`restore_wine`] takes it back. Until then it is
+/// left out of everything but [`trash`], and [`purge_wine`] or [`purge_trash`] delete it for
+/// good. The audit log keeps the wine as it was, with its grapes, pairings and events.
+/// Returns the wine as it was.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn delete_wine(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+) -> anyhow::Result<Wine> {
+    let mut trans = db.begin().await?;
+    let wine = get_wine(&mut *trans, cellar_id, wine_id).await?;
+    let mut before = serde_json::to_value(&wine)?;
+    let grapes = grape_names(&mut *trans, wine_id).await?;
+    let pairings = pairings_of(&mut *trans, wine_id).await?;
+    let events = sqlx::query_as!(
+        WineInvEvent,
+        r#"SELECT event_id AS "event_id!", dt, kind AS "kind: EventKind", bottles,
+                  unit_price, currency, vendor
+           FROM wine_inventory_events
+           WHERE wine_id=$1 ORDER BY dt, event_id"#,
         wine_id
     )
+    .fetch_all(&mut *trans)
+    .await?;
+    if let Some(wine) = before.as_object_mut() {
+        wine.insert("grapes".to_owned(), serde_json::to_value(grapes)?);
+        wine.insert("pairings".to_owned(), serde_json::to_value(pairings)?);
+        wine.insert("events".to_owned(), serde_json::to_value(events)?);
+    }
+    let change = Change::new(AuditEntity::Wine, wine_id, wine_id).before(Some(before))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    let deleted_at = chrono::Local::now().naive_local();
+    sqlx::query!(
+        "UPDATE wines SET deleted_at=$2 WHERE wine_id=$1",
+        wine_id,
+        deleted_at
+    )
     .execute(&mut *trans)
     .await?;
-    sqlx::query!("DELETE FROM wines WHERE wine_id=$1", wine_id)
+    trans.commit().await?;
+    Ok(wine)
+}
+
+/// Takes the wine back out of the trash, as it was when it was deleted.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn restore_wine(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+) -> anyhow::Result<Wine> {
+    let mut trans = db.begin().await?;
+    find_wine(&mut *trans, cellar_id, wine_id, true).await?;
+    sqlx::query!("UPDATE wines SET deleted_at=NULL WHERE wine_id=$1", wine_id)
         .execute(&mut *trans)
         .await?;
+    let res = get_wine(&mut *trans, cellar_id, wine_id).await?;
+    let change = Change::new(AuditEntity::Wine, wine_id, wine_id)
+        .after(Some(&res))?
+        .action(AuditAction::Restore);
+    audit(&mut trans, cellar_id, &user.username, change).await?;
     trans.commit().await?;
-    Ok(())
+    Ok(res)
 }
 
-pub(crate) struct Grape {
-    pub rowid: i64,
+/// A wine in the cellar's trash.
+#[derive(Debug, Clone)]
+pub(crate) struct TrashedWine {
+    pub wine_id: i64,
     pub name: String,
-}
-#[tracing::instrument(skip(db))]
-pub(crate) async fn get_grapes(db: &sqlx::SqlitePool) -> anyhow::Result<Vec<Grape>> {
-    let res = sqlx::query!("SELECT rowid, name FROM grapes ORDER BY name")
-        .fetch_all(db)
-        .await?
-        .into_iter()
-        .map(|r| Grape {
-            // This table always has a rowid
-            rowid: r.rowid.unwrap(),
-            name: r.name,
-        })
-        .collect();
-    Ok(res)
+    pub year: i64,
+    pub producer: Option<String>,
+    pub deleted_at: chrono::NaiveDateTime,
+    /// Who moved it to the trash, as the audit log has it.
+    pub deleted_by: Option<String>,
 }
 
+/// The wines in the cellar's trash, most recently deleted first.
 #[tracing::instrument(skip(db))]
-pub(crate) async fn get_wine_grapes(
+pub(crate) async fn trash(
     db: &sqlx::SqlitePool,
-    wine_id: i64,
-) -> anyhow::Result<Vec<String>> {
-    let res = sqlx::query_scalar!(
-        "SELECT grape_name FROM wine_grapes WHERE wine_id=$1 ORDER BY grape_name",
-        wine_id
+    cellar_id: i64,
+) -> anyhow::Result<Vec<TrashedWine>> {
+    let res = sqlx::query_as!(
+        TrashedWine,
+        r#"SELECT w.wine_id AS "wine_id!", w.name, w.year, p.name AS "producer?",
+                  w.deleted_at AS "deleted_at!: chrono::NaiveDateTime",
+                  (SELECT a.username FROM audit_log a
+                   WHERE a.wine_id = w.wine_id AND a.entity = 'wine' AND a.action = 'delete'
+                   ORDER BY a.audit_id DESC LIMIT 1) AS "deleted_by?: String"
+           FROM wines w
+           LEFT JOIN producers p ON p.producer_id = w.producer_id
+           WHERE w.cellar_id = $1 AND w.deleted_at IS NOT NULL
+           ORDER BY w.deleted_at DESC, w.wine_id DESC"#,
+        cellar_id
     )
     .fetch_all(db)
     .await?;
-
     Ok(res)
 }
-#[tracing::instrument(skip(db))]
-pub(crate) async fn set_wine_grapes(
+
+/// Deletes a wine in the trash for good, with everything about it.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn purge_wine(
     db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
     wine_id: i64,
-    grapes: &[&str],
 ) -> anyhow::Result<()> {
-    tracing::info!("set_wine_grapes: {wine_id}: {grapes:?}");
     let mut trans = db.begin().await?;
-
-    sqlx::query!("DELETE FROM wine_grapes WHERE wine_id=$1", wine_id)
-        .execute(&mut *trans)
-        .await?;
-
-    for grape in grapes {
-        sqlx::query!("INSERT INTO wine_grapes VALUES($1, $2)", wine_id, grape)
-            .execute(&mut *trans)
-            .await?;
-    }
-
+    purge(&mut trans, cellar_id, &user.username, wine_id).await?;
     trans.commit().await?;
-
     Ok(())
 }
 
+/// Who the audit log says purged the wines left in the trash for too long.
+pub(crate) const AUTO_PURGE: &str = "automatic purge";
+
+/// Deletes the wines of every cellar that were moved to the trash before `deleted_before`
+/// for good. Returns how many there were.
 #[tracing::instrument(skip(db))]
-pub(crate) async fn set_wine_image(
+pub(crate) async fn purge_trash(
     db: &sqlx::SqlitePool,
+    deleted_before: chrono::NaiveDateTime,
+) -> anyhow::Result<usize> {
+    let mut trans = db.begin().await?;
+    let wines = sqlx::query!(
+        r#"SELECT wine_id AS "wine_id!", cellar_id FROM wines
+           WHERE deleted_at < $1"#,
+        deleted_before
+    )
+    .fetch_all(&mut *trans)
+    .await?;
+    for wine in &wines {
+        purge(&mut trans, wine.cellar_id, AUTO_PURGE, wine.wine_id).await?;
+    }
+    trans.commit().await?;
+    Ok(wines.len())
+}
+
+/// Deletes a trashed wine and everything about it.
+async fn purge(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    username: &str,
     wine_id: i64,
-    image: &[u8],
 ) -> anyhow::Result<()> {
-    sqlx::query!("UPDATE wines SET image=$2 WHERE wine_id=$1", wine_id, image,)
-        .execute(db)
+    let wine = find_wine(&mut *conn, cellar_id, wine_id, true).await?;
+    let change = Change::new(AuditEntity::Wine, wine_id, wine_id)
+        .before(Some(wine))?
+        .action(AuditAction::Purge);
+    audit(&mut *conn, cellar_id, username, change).await?;
+    sqlx::query!("DELETE FROM wine_food_pairings WHERE wine_id=$1", wine_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!("DELETE FROM wine_grapes WHERE wine_id=$1", wine_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!("DELETE FROM bottles WHERE wine_id=$1", wine_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!("DELETE FROM bottle_moves WHERE wine_id=$1", wine_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM tasting_aromas
+         WHERE tasting_id IN (SELECT tasting_id FROM tastings WHERE wine_id=$1)",
+        wine_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM tasting_sheets
+         WHERE tasting_id IN (SELECT tasting_id FROM tastings WHERE wine_id=$1)",
+        wine_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!("DELETE FROM tastings WHERE wine_id=$1", wine_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM wine_inventory_events WHERE wine_id=$1",
+        wine_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!("DELETE FROM wines WHERE wine_id=$1", wine_id)
+        .execute(&mut *conn)
         .await?;
     Ok(())
 }
 
-/// Sets (or clears) the single comment on a wine.
-/// Pass `None` for `text` to clear the note; `dt` should be `None` when clearing.
+/// The producers of the cellar's wines. Producers are shared between cellars, so that a name
+/// means the same everywhere, but each cellar only sees its own.
 #[tracing::instrument(skip(db))]
-pub(crate) async fn set_wine_comment(
+pub(crate) async fn producers(
     db: &sqlx::SqlitePool,
-    wine_id: i64,
-    text: Option<&str>,
-    dt: Option<chrono::NaiveDateTime>,
-) -> anyhow::Result<()> {
-    sqlx::query!(
-        "UPDATE wines SET comment=$2, comment_updated_at=$3 WHERE wine_id=$1",
-        wine_id,
-        text,
-        dt
+    cellar_id: i64,
+) -> anyhow::Result<Vec<Producer>> {
+    let res = sqlx::query_as!(
+        Producer,
+        r#"SELECT producer_id AS "producer_id!", name FROM producers
+           WHERE producer_id IN (SELECT producer_id FROM wines
+                                 WHERE cellar_id = $1 AND deleted_at IS NULL)
+           ORDER BY name"#,
+        cellar_id
     )
-    .execute(db)
+    .fetch_all(db)
     .await?;
-    Ok(())
+    Ok(res)
 }
 
+/// Returns the id of the producer called `name` (ignoring case), adding it if it's new.
 #[tracing::instrument(skip(db))]
-pub(crate) async fn add_wine_event(
+pub(crate) async fn producer_id(db: &sqlx::SqlitePool, name: &str) -> anyhow::Result<i64> {
+    let id = sqlx::query_scalar!(
+        "INSERT INTO producers (name) VALUES ($1)
+         ON CONFLICT (name) DO UPDATE SET name = producers.name
+         RETURNING producer_id",
+        name
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(id)
+}
+
+/// All regions in hierarchy order: each country followed by its regions, each region followed
+/// by its appellations, alphabetical within each level.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn regions(db: &sqlx::SqlitePool) -> anyhow::Result<Vec<Region>> {
+    let res = sqlx::query_as!(
+        Region,
+        r#"WITH RECURSIVE tree(region_id, parent_id, kind, name, path) AS (
+               SELECT region_id, parent_id, kind, name, name
+               FROM regions WHERE parent_id IS NULL
+               UNION ALL
+               SELECT r.region_id, r.parent_id, r.kind, r.name, t.path || char(31) || r.name
+               FROM regions r JOIN tree t ON r.parent_id = t.region_id
+           )
+           SELECT region_id AS "region_id!", parent_id AS "parent_id?",
+                  kind AS "kind!: RegionKind", name AS "name!"
+           FROM tree ORDER BY path"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+#[tracing::instrument(skip(db))]
+pub(crate) async fn get_region(db: &sqlx::SqlitePool, region_id: i64) -> anyhow::Result<Region> {
+    let res = sqlx::query_as!(
+        Region,
+        r#"SELECT region_id, parent_id, kind AS "kind: RegionKind", name
+           FROM regions WHERE region_id = $1"#,
+        region_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(res)
+}
+
+/// Adds a region below `parent_id` (or a country when there is no parent). The caller picks
+/// `kind` to match the parent, see [`RegionKind::child`]. Returns a DB error (unique
+/// constraint) if the parent already has a region with that name.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn add_region(
     db: &sqlx::SqlitePool,
-    wine_id: i64,
-    bottles: i64,
-    dt: chrono::NaiveDateTime,
-) -> anyhow::Result<()> {
-    tracing::info!("wine event");
-    sqlx::query!(
-        "INSERT INTO wine_inventory_events (wine_id, bottles, dt) VALUES ($1, $2, $3)",
-        wine_id,
-        bottles,
-        dt
+    parent_id: Option<i64>,
+    kind: RegionKind,
+    name: &str,
+) -> anyhow::Result<Region> {
+    let region_id = sqlx::query_scalar!(
+        "INSERT INTO regions (parent_id, kind, name) VALUES ($1, $2, $3) RETURNING region_id",
+        parent_id,
+        kind,
+        name
     )
-    .execute(db)
+    .fetch_one(db)
     .await?;
-    Ok(())
+    get_region(db, region_id).await
 }
 
 #[tracing::instrument(skip(db))]
-pub(crate) async fn wine_image(
+pub(crate) async fn locations(
     db: &sqlx::SqlitePool,
-    wine_id: i64,
-) -> anyhow::Result<Option<Vec<u8>>> {
-    let res = sqlx::query_scalar!("SELECT image FROM wines WHERE wine_id=$1", wine_id)
-        .fetch_optional(db)
-        .await?
-        .flatten();
+    cellar_id: i64,
+) -> anyhow::Result<Vec<Location>> {
+    let res = sqlx::query_as!(
+        Location,
+        r#"SELECT location_id AS "location_id!", name FROM locations
+           WHERE cellar_id = $1 ORDER BY name"#,
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?;
     Ok(res)
 }
 
-/// Returns all food pairings for the given wine, ordered by insertion time.
 #[tracing::instrument(skip(db))]
-pub(crate) async fn get_wine_food_pairings(
+pub(crate) async fn get_location(
     db: &sqlx::SqlitePool,
-    wine_id: i64,
-) -> anyhow::Result<Vec<FoodPairing>> {
-    let res = sqlx::query!(
-        "SELECT id, food FROM wine_food_pairings WHERE wine_id = $1 ORDER BY id",
-        wine_id
+    cellar_id: i64,
+    location_id: i64,
+) -> anyhow::Result<Location> {
+    let res = sqlx::query_as!(
+        Location,
+        r#"SELECT location_id AS "location_id!", name FROM locations
+           WHERE location_id=$1 AND cellar_id=$2"#,
+        location_id,
+        cellar_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(res)
+}
+
+/// Adds a location. Fails with a unique violation if the cellar has one with the same name.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn add_location(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    name: &str,
+) -> anyhow::Result<Location> {
+    let mut trans = db.begin().await?;
+    let res = sqlx::query_as!(
+        Location,
+        r#"INSERT INTO locations (cellar_id, name) VALUES ($1, $2)
+           RETURNING location_id AS "location_id!", name"#,
+        cellar_id,
+        name
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let change = Change::storage(AuditEntity::Location, res.location_id).after(Some(&res))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(res)
+}
+
+/// The racks in every location of the cellar, or just in `location_id`, by location and name.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn racks(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    location_id: Option<i64>,
+) -> anyhow::Result<Vec<Rack>> {
+    let res = sqlx::query_as!(
+        Rack,
+        r#"SELECT r.rack_id AS "rack_id!", r.location_id, l.name AS location, r.name,
+                  r.row_count, r.column_count
+           FROM racks r JOIN locations l ON l.location_id = r.location_id
+           WHERE l.cellar_id = $2 AND ($1 IS NULL OR r.location_id = $1)
+           ORDER BY l.name, r.name"#,
+        location_id,
+        cellar_id
     )
     .fetch_all(db)
-    .await?
-    .into_iter()
-    .map(|r| FoodPairing {
-        id: r.id.expect("id is NOT NULL"),
-        food: r.food,
-    })
-    .collect();
+    .await?;
     Ok(res)
 }
 
-/// Inserts a new food pairing and returns the created record.
-/// Returns a DB error (unique constraint) if the pairing already exists
-/// case-insensitively on this wine.
 #[tracing::instrument(skip(db))]
-pub(crate) async fn add_food_pairing(
+pub(crate) async fn get_rack(
+    db: impl sqlx::SqliteExecutor<'_>,
+    cellar_id: i64,
+    rack_id: i64,
+) -> anyhow::Result<Rack> {
+    let res = sqlx::query_as!(
+        Rack,
+        r#"SELECT r.rack_id AS "rack_id!", r.location_id, l.name AS location, r.name,
+                  r.row_count, r.column_count
+           FROM racks r JOIN locations l ON l.location_id = r.location_id
+           WHERE r.rack_id = $1 AND l.cellar_id = $2"#,
+        rack_id,
+        cellar_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(res)
+}
+
+/// Adds a rack of `row_count` × `column_count` slots. Fails with a unique violation if the
+/// location already has a rack with the same name.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn add_rack(
     db: &sqlx::SqlitePool,
-    wine_id: i64,
-    food: &str,
-) -> anyhow::Result<FoodPairing> {
-    let r = sqlx::query!(
-        "INSERT INTO wine_food_pairings (wine_id, food) VALUES ($1, $2) RETURNING id, food",
+    cellar_id: i64,
+    user: &User,
+    location_id: i64,
+    name: &str,
+    row_count: i64,
+    column_count: i64,
+) -> anyhow::Result<Rack> {
+    get_location(db, cellar_id, location_id).await?;
+    let mut trans = db.begin().await?;
+    let rack_id = sqlx::query_scalar!(
+        r#"INSERT INTO racks (location_id, name, row_count, column_count)
+           VALUES ($1, $2, $3, $4) RETURNING rack_id AS "rack_id!""#,
+        location_id,
+        name,
+        row_count,
+        column_count
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let res = get_rack(&mut *trans, cellar_id, rack_id).await?;
+    let change = Change::storage(AuditEntity::Rack, rack_id).after(Some(&res))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(res)
+}
+
+/// The bottles in the cellar's racks, optionally only those of `wine_id` or in `location_id`,
+/// ordered by location, rack and slot.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn placed_bottles(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: Option<i64>,
+    location_id: Option<i64>,
+) -> anyhow::Result<Vec<PlacedBottle>> {
+    let res = sqlx::query_as!(
+        PlacedBottle,
+        r#"SELECT b.rack_id, b.row_num AS row, b.col_num AS col,
+                  r.location_id, l.name AS location, r.name AS rack,
+                  w.wine_id AS "wine_id!", w.name, w.year, w.style AS "style: WineStyle"
+           FROM bottles b
+           JOIN racks r ON r.rack_id = b.rack_id
+           JOIN locations l ON l.location_id = r.location_id
+           JOIN wines w ON w.wine_id = b.wine_id
+           WHERE l.cellar_id = $3 AND w.deleted_at IS NULL
+             AND ($1 IS NULL OR b.wine_id = $1) AND ($2 IS NULL OR r.location_id = $2)
+           ORDER BY l.name, r.name, b.row_num, b.col_num"#,
         wine_id,
-        food
+        location_id,
+        cellar_id
     )
-    .fetch_one(db)
+    .fetch_all(db)
     .await?;
-    Ok(FoodPairing {
-        id: r.id.expect("id is NOT NULL"),
-        food: r.food,
-    })
+    Ok(res)
+}
+
+/// Puts bottles of the wine that are in stock but not in a rack into `slots`. Fails with
+/// [`InventoryError`] if a slot doesn't exist or is taken, or if the wine doesn't have that
+/// many unplaced bottles.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn place_bottles(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    slots: &[Slot],
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let unplaced =
+        bottles_in_stock(&mut trans, wine_id).await? - bottles_placed(&mut trans, wine_id).await?;
+    if slots.len() as i64 > unplaced {
+        return Err(InventoryError::NotEnoughUnplaced(unplaced.max(0)).into());
+    }
+    place(&mut trans, cellar_id, user, wine_id, slots).await?;
+    trans.commit().await?;
+    Ok(())
 }
 
-/// Deletes a food pairing by its id, scoped to wine_id to prevent cross-wine deletions.
-#[tracing::instrument(skip(db))]
-pub(crate) async fn remove_food_pairing(
-    db: &sqlx::SqlitePool,
-    pairing_id: i64,
-    wine_id: i64,
-) -> anyhow::Result<()> {
-    sqlx::query!(
-        "DELETE FROM wine_food_pairings WHERE id = $1 AND wine_id = $2",
-        pairing_id,
-        wine_id
-    )
-    .execute(db)
-    .await?;
-    Ok(())
-}
+/// Takes one of the wine's bottles out of its slot without removing it from stock, e.g. to
+/// move it. Scoped to wine_id like [`remove_food_pairing`].
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn unplace_bottle(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    slot: Slot,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    pick(&mut trans, cellar_id, user, wine_id, &[slot]).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Moves one of the wine's bottles from `from` to the empty slot `to` and records the move.
+/// The number of bottles in stock doesn't change. Fails with [`InventoryError`] if `from`
+/// doesn't hold a bottle of the wine or `to` isn't an empty slot.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn move_bottle(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    from: Slot,
+    to: Slot,
+    dt: chrono::NaiveDateTime,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    check_empty(&mut trans, cellar_id, to).await?;
+    sqlx::query_scalar!(
+        "UPDATE bottles SET rack_id=$5, row_num=$6, col_num=$7
+         WHERE rack_id=$1 AND row_num=$2 AND col_num=$3 AND wine_id=$4
+         RETURNING rack_id",
+        from.rack_id,
+        from.row,
+        from.col,
+        wine_id,
+        to.rack_id,
+        to.row,
+        to.col
+    )
+    .fetch_optional(&mut *trans)
+    .await?
+    .ok_or(InventoryError::NotInSlot(from))?;
+    sqlx::query!(
+        "INSERT INTO bottle_moves
+         (wine_id, dt, from_rack_id, from_row, from_col, to_rack_id, to_row, to_col)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        wine_id,
+        dt,
+        from.rack_id,
+        from.row,
+        from.col,
+        to.rack_id,
+        to.row,
+        to.col
+    )
+    .execute(&mut *trans)
+    .await?;
+    audit_bottle(&mut trans, cellar_id, user, wine_id, Some(from), Some(to)).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// The wine's bottle moves, newest first.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn bottle_moves(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: i64,
+) -> anyhow::Result<Vec<BottleMove>> {
+    check_wine(db, cellar_id, wine_id).await?;
+    let res = sqlx::query!(
+        r#"SELECT m.move_id AS "move_id!", m.dt,
+                  m.from_rack_id, m.from_row, m.from_col,
+                  fl.name || ' › ' || fr.name AS "from_rack!: String",
+                  m.to_rack_id, m.to_row, m.to_col,
+                  tl.name || ' › ' || tr.name AS "to_rack!: String"
+           FROM bottle_moves m
+           JOIN racks fr ON fr.rack_id = m.from_rack_id
+           JOIN locations fl ON fl.location_id = fr.location_id
+           JOIN racks tr ON tr.rack_id = m.to_rack_id
+           JOIN locations tl ON tl.location_id = tr.location_id
+           WHERE m.wine_id = $1
+           ORDER BY m.dt DESC, m.move_id DESC"#,
+        wine_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|r| BottleMove {
+        move_id: r.move_id,
+        dt: r.dt,
+        from: Slot {
+            rack_id: r.from_rack_id,
+            row: r.from_row,
+            col: r.from_col,
+        },
+        from_rack: r.from_rack,
+        to: Slot {
+            rack_id: r.to_rack_id,
+            row: r.to_row,
+            col: r.to_col,
+        },
+        to_rack: r.to_rack,
+    })
+    .collect();
+    Ok(res)
+}
+
+/// The wine's tastings, newest first.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn wine_tastings(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: i64,
+) -> anyhow::Result<Vec<Tasting>> {
+    check_wine(db, cellar_id, wine_id).await?;
+    query_tastings(db, wine_id, None).await
+}
+
+/// Fetches tastings with their sheets, the aromas aggregated like the grapes in
+/// [`query_wine_rows`].
+async fn query_tastings(
+    db: impl sqlx::SqliteExecutor<'_>,
+    wine_id: i64,
+    tasting_id: Option<i64>,
+) -> anyhow::Result<Vec<Tasting>> {
+    let res = sqlx::query!(
+        r#"SELECT t.tasting_id AS "tasting_id!", t.wine_id,
+                  t.tasted_on AS "tasted_on: chrono::NaiveDate", t.score, t.scale, t.note,
+                  t.occasion, s.tasting_id IS NOT NULL AS "has_sheet!: bool",
+                  s.sweetness, s.acidity, s.tannin, s.body, s.finish,
+                  ta.aromas AS "aromas: String"
+           FROM tastings t
+           LEFT JOIN tasting_sheets s ON s.tasting_id = t.tasting_id
+           LEFT JOIN (SELECT ta.tasting_id, GROUP_CONCAT(a.name, char(31)) AS aromas
+                      FROM tasting_aromas ta
+                      JOIN aromas a ON a.aroma_id = ta.aroma_id
+                      GROUP BY ta.tasting_id) ta ON ta.tasting_id = t.tasting_id
+           WHERE t.wine_id = $1 AND ($2 IS NULL OR t.tasting_id = $2)
+           ORDER BY t.tasted_on DESC, t.tasting_id DESC"#,
+        wine_id,
+        tasting_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|r| Tasting {
+        tasting_id: r.tasting_id,
+        wine_id: r.wine_id,
+        tasted_on: r.tasted_on,
+        score: r.score,
+        scale: r.scale,
+        note: r.note,
+        occasion: r.occasion,
+        sheet: r.has_sheet.then(|| TastingSheet {
+            sweetness: r.sweetness,
+            acidity: r.acidity,
+            tannin: r.tannin,
+            body: r.body,
+            finish: r.finish,
+            aromas: split_list(r.aromas),
+        }),
+    })
+    .collect();
+    Ok(res)
+}
+
+/// Records a tasting, and its sheet unless that is empty. Fails if an aroma isn't in the
+/// vocabulary; check with [`TastingSheet::validate`] first for a friendlier error.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn add_tasting(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    tasting: &NewTasting<'_>,
+) -> anyhow::Result<Tasting> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let tasting_id = sqlx::query_scalar!(
+        r#"INSERT INTO tastings (wine_id, tasted_on, score, scale, note, occasion)
+           VALUES ($1, $2, $3, $4, $5, $6)
+           RETURNING tasting_id AS "tasting_id!""#,
+        wine_id,
+        tasting.tasted_on,
+        tasting.score,
+        tasting.scale,
+        tasting.note,
+        tasting.occasion
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    if let Some(sheet) = tasting.sheet.filter(|s| !s.is_empty()) {
+        sqlx::query!(
+            "INSERT INTO tasting_sheets (tasting_id, sweetness, acidity, tannin, body, finish)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            tasting_id,
+            sheet.sweetness,
+            sheet.acidity,
+            sheet.tannin,
+            sheet.body,
+            sheet.finish
+        )
+        .execute(&mut *trans)
+        .await?;
+        for aroma in &sheet.aromas {
+            let aroma_id =
+                sqlx::query_scalar!("SELECT aroma_id FROM aromas WHERE name = $1", aroma)
+                    .fetch_optional(&mut *trans)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Unknown aroma: {aroma}"))?;
+            sqlx::query!(
+                "INSERT OR IGNORE INTO tasting_aromas (tasting_id, aroma_id) VALUES ($1, $2)",
+                tasting_id,
+                aroma_id
+            )
+            .execute(&mut *trans)
+            .await?;
+        }
+    }
+    let res = query_tastings(&mut *trans, wine_id, Some(tasting_id))
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+    let change = Change::new(AuditEntity::Tasting, tasting_id, wine_id).after(Some(&res))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(res)
+}
+
+/// The aroma vocabulary, grouped by family.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn aromas(db: &sqlx::SqlitePool) -> anyhow::Result<Vec<Aroma>> {
+    let res = sqlx::query_as!(
+        Aroma,
+        r#"SELECT aroma_id AS "aroma_id!", family, name FROM aromas ORDER BY aroma_id"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+/// Deletes a tasting, scoped to wine_id to prevent cross-wine deletions. Fails with
+/// [`sqlx::Error::RowNotFound`] if the wine has no such tasting.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn delete_tasting(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    tasting_id: i64,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let before = query_tastings(&mut *trans, wine_id, Some(tasting_id))
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+    sqlx::query!(
+        "DELETE FROM tasting_aromas WHERE tasting_id IN
+           (SELECT tasting_id FROM tastings WHERE tasting_id = $1 AND wine_id = $2)",
+        tasting_id,
+        wine_id
+    )
+    .execute(&mut *trans)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM tasting_sheets WHERE tasting_id IN
+           (SELECT tasting_id FROM tastings WHERE tasting_id = $1 AND wine_id = $2)",
+        tasting_id,
+        wine_id
+    )
+    .execute(&mut *trans)
+    .await?;
+    sqlx::query_scalar!(
+        "DELETE FROM tastings WHERE tasting_id = $1 AND wine_id = $2 RETURNING tasting_id",
+        tasting_id,
+        wine_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let change = Change::new(AuditEntity::Tasting, tasting_id, wine_id).before(Some(before))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// A grape used by at least one of the cellar's wines, for the grape filter.
+#[derive(Debug)]
+pub(crate) struct GrapeFacet {
+    pub name: String,
+    /// How many wines are made from it.
+    pub wines: i64,
+}
+
+#[tracing::instrument(skip(db))]
+pub(crate) async fn grape_facets(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+) -> anyhow::Result<Vec<GrapeFacet>> {
+    let res = sqlx::query_as!(
+        GrapeFacet,
+        r#"SELECT wg.grape_name AS name, COUNT(*) AS "wines!: i64"
+           FROM wine_grapes wg JOIN wines w ON w.wine_id = wg.wine_id
+           WHERE w.cellar_id = $1 AND w.deleted_at IS NULL
+           GROUP BY wg.grape_name
+           ORDER BY wg.grape_name"#,
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct Grape {
+    pub rowid: i64,
+    pub name: String,
+}
+#[tracing::instrument(skip(db))]
+pub(crate) async fn get_grapes(db: &sqlx::SqlitePool) -> anyhow::Result<Vec<Grape>> {
+    let res = sqlx::query!("SELECT rowid, name FROM grapes ORDER BY name")
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|r| Grape {
+            // This table always has a rowid
+            rowid: r.rowid.unwrap(),
+            name: r.name,
+        })
+        .collect();
+    Ok(res)
+}
+
+#[tracing::instrument(skip(db))]
+pub(crate) async fn get_wine_grapes(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: i64,
+) -> anyhow::Result<Vec<String>> {
+    check_wine(db, cellar_id, wine_id).await?;
+    grape_names(db, wine_id).await
+}
+
+async fn grape_names(
+    db: impl sqlx::SqliteExecutor<'_>,
+    wine_id: i64,
+) -> anyhow::Result<Vec<String>> {
+    let res = sqlx::query_scalar!(
+        "SELECT grape_name FROM wine_grapes WHERE wine_id=$1 ORDER BY grape_name",
+        wine_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn set_wine_grapes(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    grapes: &[&str],
+) -> anyhow::Result<()> {
+    tracing::info!("set_wine_grapes: {wine_id}: {grapes:?}");
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let before = grape_names(&mut *trans, wine_id).await?;
+
+    sqlx::query!("DELETE FROM wine_grapes WHERE wine_id=$1", wine_id)
+        .execute(&mut *trans)
+        .await?;
+
+    for grape in grapes {
+        sqlx::query!("INSERT INTO wine_grapes VALUES($1, $2)", wine_id, grape)
+            .execute(&mut *trans)
+            .await?;
+    }
+    let after = grape_names(&mut *trans, wine_id).await?;
+    audit_grapes(&mut trans, cellar_id, user, wine_id, before, after).await?;
+
+    trans.commit().await?;
+
+    Ok(())
+}
+
+/// A grape list is recorded as one change, with no grapes standing for none.
+async fn audit_grapes(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    before: Vec<String>,
+    after: Vec<String>,
+) -> anyhow::Result<()> {
+    let change = Change::new(AuditEntity::Grapes, wine_id, wine_id)
+        .before((!before.is_empty()).then_some(before))?
+        .after((!after.is_empty()).then_some(after))?;
+    audit(conn, cellar_id, &user.username, change).await
+}
+
+/// Replaces the wine's label image. The audit log only has the images' sizes.
+#[tracing::instrument(skip(db, user, image))]
+pub(crate) async fn set_wine_image(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    image: &[u8],
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    let before = sqlx::query_scalar!(
+        r#"SELECT length(image) AS "bytes: i64" FROM wines
+           WHERE wine_id=$1 AND cellar_id=$2 AND deleted_at IS NULL"#,
+        wine_id,
+        cellar_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    sqlx::query!("UPDATE wines SET image=$2 WHERE wine_id=$1", wine_id, image)
+        .execute(&mut *trans)
+        .await?;
+    let size = |bytes: i64| serde_json::json!({ "bytes": bytes });
+    let change = Change::new(AuditEntity::Image, wine_id, wine_id)
+        .before(before.map(size))?
+        .after(Some(size(image.len() as i64)))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// A wine's note as the audit log records it.
+#[derive(serde::Serialize)]
+struct NoteSnapshot<'a> {
+    comment: &'a str,
+    updated_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Sets (or clears) the single comment on a wine.
+/// Pass `None` for `text` to clear the note; `dt` should be `None` when clearing.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn set_wine_comment(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    text: Option<&str>,
+    dt: Option<chrono::NaiveDateTime>,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    let before = get_wine(&mut *trans, cellar_id, wine_id).await?;
+    sqlx::query!(
+        "UPDATE wines SET comment=$2, comment_updated_at=$3 WHERE wine_id=$1",
+        wine_id,
+        text,
+        dt
+    )
+    .execute(&mut *trans)
+    .await?;
+    audit_comment(&mut trans, cellar_id, user, &before, text, dt).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+async fn audit_comment(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    user: &User,
+    before: &Wine,
+    text: Option<&str>,
+    dt: Option<chrono::NaiveDateTime>,
+) -> anyhow::Result<()> {
+    let after = text.map(|comment| NoteSnapshot {
+        comment,
+        updated_at: dt,
+    });
+    let wine_id = before.wine_id;
+    let before = before.comment.as_deref().map(|comment| NoteSnapshot {
+        comment,
+        updated_at: before.comment_updated_at,
+    });
+    let change = Change::new(AuditEntity::Comment, wine_id, wine_id)
+        .before(before)?
+        .after(after)?;
+    audit(conn, cellar_id, &user.username, change).await
+}
+
+impl NewWineEvent<'_> {
+    /// Checks the event on its own, before it touches the stock.
+    fn validate(&self) -> Result<(), InventoryError> {
+        if self.price.is_some() && self.kind != EventKind::Purchase {
+            return Err(InventoryError::PriceNotAllowed(self.kind));
+        }
+        if self.bottles == 0 {
+            return Err(InventoryError::NoBottles);
+        }
+        if self
+            .kind
+            .adds_bottles()
+            .is_some_and(|adds| adds != (self.bottles > 0))
+        {
+            return Err(InventoryError::WrongDirection(self.kind));
+        }
+        if self.slots.len() as u64 > self.bottles.unsigned_abs() {
+            return Err(InventoryError::TooManySlots);
+        }
+        Ok(())
+    }
+}
+
+/// The wine's bottle count, summed over its events.
+async fn bottles_in_stock(conn: &mut sqlx::SqliteConnection, wine_id: i64) -> anyhow::Result<i64> {
+    let res = sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(bottles), 0) AS "bottles!: i64"
+           FROM wine_inventory_events WHERE wine_id=$1"#,
+        wine_id
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
+}
+
+/// Fails with [`InventoryError::StockBelowZeroOn`] if the wine's events would leave fewer than
+/// zero bottles in stock at the end of any day from `from` on, such as when a consumption is
+/// dated before the purchase it comes from. Days before `from` weren't changed, so mistakes
+/// made there earlier don't block new events.
+async fn check_running_stock(
+    conn: &mut sqlx::SqliteConnection,
+    wine_id: i64,
+    from: chrono::NaiveDate,
+) -> anyhow::Result<()> {
+    let below = sqlx::query!(
+        r#"SELECT day AS "day!: chrono::NaiveDate", in_stock AS "in_stock!: i64"
+           FROM (SELECT date(dt) AS day, SUM(SUM(bottles)) OVER (ORDER BY date(dt)) AS in_stock
+                 FROM wine_inventory_events WHERE wine_id=$1 GROUP BY date(dt))
+           WHERE day >= $2 AND in_stock < 0
+           ORDER BY day LIMIT 1"#,
+        wine_id,
+        from
+    )
+    .fetch_optional(conn)
+    .await?;
+    match below {
+        Some(below) => Err(InventoryError::StockBelowZeroOn {
+            date: below.day,
+            in_stock: below.in_stock,
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// How many of the wine's bottles are in racks.
+async fn bottles_placed(conn: &mut sqlx::SqliteConnection, wine_id: i64) -> anyhow::Result<i64> {
+    let res = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "placed!: i64" FROM bottles WHERE wine_id=$1"#,
+        wine_id
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
+}
+
+/// Fails with [`InventoryError::BottlesInRacks`] if the wine has more bottles in racks than
+/// `in_stock`.
+async fn check_placed(
+    conn: &mut sqlx::SqliteConnection,
+    wine_id: i64,
+    in_stock: i64,
+) -> anyhow::Result<()> {
+    let placed = bottles_placed(conn, wine_id).await?;
+    if placed > in_stock {
+        return Err(InventoryError::BottlesInRacks { in_stock, placed }.into());
+    }
+    Ok(())
+}
+
+/// Fails with [`InventoryError`] unless `slot` exists in the cellar and is empty. A bottle of
+/// a trashed wine keeps its slot until the wine is purged, so restoring it puts everything
+/// back where it was.
+async fn check_empty(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    slot: Slot,
+) -> anyhow::Result<()> {
+    let rack = sqlx::query!(
+        "SELECT r.row_count, r.column_count
+         FROM racks r JOIN locations l ON l.location_id = r.location_id
+         WHERE r.rack_id=$1 AND l.cellar_id=$2",
+        slot.rack_id,
+        cellar_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let in_rack = rack.is_some_and(|r| {
+        (1..=r.row_count).contains(&slot.row) && (1..=r.column_count).contains(&slot.col)
+    });
+    if !in_rack {
+        return Err(InventoryError::NoSuchSlot(slot).into());
+    }
+    let taken = sqlx::query!(
+        r#"SELECT w.name || ' ' || w.year AS "wine!: String",
+                  w.deleted_at IS NOT NULL AS "trashed!: bool"
+           FROM bottles b JOIN wines w ON w.wine_id = b.wine_id
+           WHERE b.rack_id=$1 AND b.row_num=$2 AND b.col_num=$3"#,
+        slot.rack_id,
+        slot.row,
+        slot.col
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    match taken {
+        Some(taken) if taken.trashed => Err(InventoryError::SlotInTrash {
+            slot,
+            wine: taken.wine,
+        }
+        .into()),
+        Some(_) => Err(InventoryError::SlotTaken(slot).into()),
+        None => Ok(()),
+    }
+}
+
+/// Puts a bottle of the wine in each of `slots`, which must exist in the cellar and be empty.
+async fn place(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    slots: &[Slot],
+) -> anyhow::Result<()> {
+    for &slot in slots {
+        check_empty(&mut *conn, cellar_id, slot).await?;
+        sqlx::query!(
+            "INSERT INTO bottles (rack_id, row_num, col_num, wine_id) VALUES ($1, $2, $3, $4)",
+            slot.rack_id,
+            slot.row,
+            slot.col,
+            wine_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        audit_bottle(conn, cellar_id, user, wine_id, None, Some(slot)).await?;
+    }
+    Ok(())
+}
+
+/// Takes the wine's bottles out of `slots`.
+async fn pick(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    slots: &[Slot],
+) -> anyhow::Result<()> {
+    for &slot in slots {
+        sqlx::query_scalar!(
+            "DELETE FROM bottles
+             WHERE rack_id=$1 AND row_num=$2 AND col_num=$3 AND wine_id=$4
+             RETURNING rack_id",
+            slot.rack_id,
+            slot.row,
+            slot.col,
+            wine_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(InventoryError::NotInSlot(slot))?;
+        audit_bottle(conn, cellar_id, user, wine_id, Some(slot), None).await?;
+    }
+    Ok(())
+}
+
+/// A slot as the audit log records it, with its rack's name rather than its id.
+#[derive(serde::Serialize)]
+struct BottleSlot {
+    rack: String,
+    row: i64,
+    col: i64,
+}
+
+async fn bottle_slot(
+    conn: &mut sqlx::SqliteConnection,
+    slot: Option<Slot>,
+) -> anyhow::Result<Option<BottleSlot>> {
+    let Some(slot) = slot else {
+        return Ok(None);
+    };
+    let rack = sqlx::query_scalar!(
+        r#"SELECT l.name || ' › ' || r.name AS "rack!: String"
+           FROM racks r JOIN locations l ON l.location_id = r.location_id
+           WHERE r.rack_id = $1"#,
+        slot.rack_id
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(Some(BottleSlot {
+        rack,
+        row: slot.row,
+        col: slot.col,
+    }))
+}
+
+/// Records a bottle of the wine put in `to`, taken out of `from`, or moved between them.
+async fn audit_bottle(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    from: Option<Slot>,
+    to: Option<Slot>,
+) -> anyhow::Result<()> {
+    let change = Change::new(AuditEntity::Bottle, wine_id, wine_id)
+        .before(bottle_slot(&mut *conn, from).await?)?
+        .after(bottle_slot(&mut *conn, to).await?)?;
+    audit(conn, cellar_id, &user.username, change).await
+}
+
+/// Records a change to a wine's bottle count. Fails with [`InventoryError`] if the number of
+/// bottles is zero or has the wrong sign for the kind of event, if a price is given for
+/// anything but a purchase, or if the wine doesn't have enough bottles in stock, in total or
+/// at the end of any day from the event's on. Bottles coming in are put in the event's slots
+/// and bottles going out are taken from them; the change is rejected if a slot is taken or
+/// empty, or if more bottles would be left in racks than in stock.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn add_wine_event(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    event: &NewWineEvent<'_>,
+) -> anyhow::Result<()> {
+    tracing::info!("wine event");
+    event.validate()?;
+    let NewWineEvent {
+        kind,
+        bottles,
+        dt,
+        ref price,
+        vendor,
+        slots,
+    } = *event;
+    // Insert first and check afterwards: the insert takes the write lock, so no other event
+    // for the wine can sneak in between the check and the commit.
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let unit_price = price.as_ref().map(|p| p.amount);
+    let currency = price.as_ref().map(|p| p.currency.as_str());
+    let event_id = sqlx::query_scalar!(
+        r#"INSERT INTO wine_inventory_events
+             (wine_id, kind, bottles, dt, unit_price, currency, vendor)
+           VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING event_id AS "event_id!""#,
+        wine_id,
+        kind,
+        bottles,
+        dt,
+        unit_price,
+        currency,
+        vendor
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let in_stock = bottles_in_stock(&mut trans, wine_id).await?;
+    if in_stock < 0 {
+        trans.rollback().await?;
+        return Err(InventoryError::NotEnoughBottles {
+            in_stock: in_stock - bottles,
+            requested: -bottles,
+        }
+        .into());
+    }
+    // Any error drops the transaction, which rolls it back
+    check_running_stock(&mut trans, wine_id, dt.date()).await?;
+    if bottles > 0 {
+        place(&mut trans, cellar_id, user, wine_id, slots).await?;
+    } else {
+        pick(&mut trans, cellar_id, user, wine_id, slots).await?;
+    }
+    check_placed(&mut trans, wine_id, in_stock).await?;
+    audit_event(&mut trans, cellar_id, user, wine_id, event_id, None).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Records an event as it is now, compared to `before`; an event that is gone is deleted.
+async fn audit_event(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    event_id: i64,
+    before: Option<WineInvEvent>,
+) -> anyhow::Result<()> {
+    let after = match find_event(&mut *conn, wine_id, event_id).await {
+        Ok(event) => Some(event),
+        Err(e) if matches!(e.downcast_ref(), Some(sqlx::Error::RowNotFound)) => None,
+        Err(e) => return Err(e),
+    };
+    let change = Change::new(AuditEntity::Event, event_id, wine_id)
+        .before(before)?
+        .after(after)?;
+    audit(conn, cellar_id, &user.username, change).await
+}
+
+/// Replaces an inventory event, scoped to wine_id to prevent cross-wine edits. The event is
+/// checked like a new one, and the change is rolled back with
+/// [`InventoryError::StockBelowZero`] if it would leave fewer than zero bottles in stock, or
+/// fewer than are in racks, and with [`InventoryError::StockBelowZeroOn`] if the stock would
+/// drop below zero on some day, like when a purchase is moved after its consumptions. The
+/// event's `slots` aren't used. Fails with [`sqlx::Error::RowNotFound`] if the wine has no
+/// such event.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn update_wine_event(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    event_id: i64,
+    event: &NewWineEvent<'_>,
+) -> anyhow::Result<()> {
+    event.validate()?;
+    let NewWineEvent {
+        kind,
+        bottles,
+        dt,
+        ref price,
+        vendor,
+        slots: _,
+    } = *event;
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let before = find_event(&mut *trans, wine_id, event_id).await?;
+    let unit_price = price.as_ref().map(|p| p.amount);
+    let currency = price.as_ref().map(|p| p.currency.as_str());
+    sqlx::query_scalar!(
+        "UPDATE wine_inventory_events
+         SET kind=$3, bottles=$4, dt=$5, unit_price=$6, currency=$7, vendor=$8
+         WHERE event_id=$1 AND wine_id=$2
+         RETURNING event_id",
+        event_id,
+        wine_id,
+        kind,
+        bottles,
+        dt,
+        unit_price,
+        currency,
+        vendor
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let in_stock = bottles_in_stock(&mut trans, wine_id).await?;
+    if in_stock < 0 {
+        trans.rollback().await?;
+        return Err(InventoryError::StockBelowZero(in_stock).into());
+    }
+    check_running_stock(&mut trans, wine_id, before.dt.date().min(dt.date())).await?;
+    check_placed(&mut trans, wine_id, in_stock).await?;
+    audit_event(&mut trans, cellar_id, user, wine_id, event_id, Some(before)).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Deletes an inventory event, scoped to wine_id to prevent cross-wine deletions. Rolled back
+/// with [`InventoryError::StockBelowZero`] if the wine's later events removed the bottles it
+/// added, in total or by some day (see [`InventoryError::StockBelowZeroOn`]), or
+/// [`InventoryError::BottlesInRacks`] if they are still in racks. Fails with
+/// [`sqlx::Error::RowNotFound`] if the wine has no such event.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn delete_wine_event(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    event_id: i64,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let before = find_event(&mut *trans, wine_id, event_id).await?;
+    sqlx::query_scalar!(
+        "DELETE FROM wine_inventory_events WHERE event_id=$1 AND wine_id=$2 RETURNING event_id",
+        event_id,
+        wine_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let in_stock = bottles_in_stock(&mut trans, wine_id).await?;
+    if in_stock < 0 {
+        trans.rollback().await?;
+        return Err(InventoryError::StockBelowZero(in_stock).into());
+    }
+    check_running_stock(&mut trans, wine_id, before.dt.date()).await?;
+    check_placed(&mut trans, wine_id, in_stock).await?;
+    audit_event(&mut trans, cellar_id, user, wine_id, event_id, Some(before)).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Purchase costs of the cellar's wines in stock (or just `wine_id`), one row per wine and currency
+/// it was bought in. Wines in stock without any priced purchase are left out.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn wine_costs(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: Option<i64>,
+) -> anyhow::Result<Vec<WineCost>> {
+    let res = sqlx::query_as!(
+        WineCost,
+        r#"SELECT w.wine_id AS "wine_id!", w.name, w.year, inv.bottles AS "bottles!: i64",
+                  c.currency AS "currency!", c.priced_bottles AS "priced_bottles!: i64",
+                  c.total_cost AS "total_cost!: i64",
+                  SUM(c.priced_bottles) OVER (PARTITION BY w.wine_id) AS "all_priced_bottles!: i64"
+           FROM wines w
+           JOIN (SELECT wine_id, SUM(bottles) AS bottles
+                 FROM wine_inventory_events
+                 GROUP BY wine_id) inv ON inv.wine_id = w.wine_id
+           JOIN (SELECT wine_id, currency, SUM(bottles) AS priced_bottles,
+                        SUM(unit_price * bottles) AS total_cost
+                 FROM wine_inventory_events
+                 WHERE kind = 'purchase' AND unit_price IS NOT NULL
+                 GROUP BY wine_id, currency) c ON c.wine_id = w.wine_id
+           WHERE w.cellar_id = $2 AND w.deleted_at IS NULL AND inv.bottles > 0
+             AND ($1 IS NULL OR w.wine_id = $1)
+           ORDER BY w.name, w.year, c.currency"#,
+        wine_id,
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+/// The currency of the cellar's most recent priced purchase, used as the default in the buy
+/// form.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn last_currency(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+) -> anyhow::Result<Option<String>> {
+    let res = sqlx::query_scalar!(
+        r#"SELECT e.currency AS "currency!" FROM wine_inventory_events e
+           JOIN wines w ON w.wine_id = e.wine_id
+           WHERE w.cellar_id = $1 AND e.currency IS NOT NULL
+           ORDER BY e.dt DESC LIMIT 1"#,
+        cellar_id
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(res)
+}
+
+/// One wine's worth of changes from a CSV import. `wine_id` is `None` for wines that don't
+/// exist yet; grapes and pairings are only ever added, never removed.
+#[derive(Debug)]
+pub(crate) struct WineImport {
+    pub wine_id: Option<i64>,
+    pub name: String,
+    pub year: i64,
+    pub bottles_delta: i64,
+    pub grapes: Vec<String>,
+    pub pairings: Vec<String>,
+    pub note: Option<String>,
+}
+
+/// Applies a planned CSV import to the cellar in a single transaction, so a failure part-way
+/// leaves it untouched. Fails with [`InventoryError::BottlesInRacks`] if a wine would end up
+/// with fewer bottles than it has in racks. Inventory changes are recorded as correction
+/// events dated `dt`.
+#[tracing::instrument(skip(db, user, imports))]
+pub(crate) async fn import_wines(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    imports: &[WineImport],
+    dt: chrono::NaiveDateTime,
+) -> anyhow::Result<()> {
+    tracing::info!("Importing {} wines", imports.len());
+    let mut trans = db.begin().await?;
+    for import in imports {
+        let wine_id = match import.wine_id {
+            Some(wine_id) => {
+                check_wine(&mut *trans, cellar_id, wine_id).await?;
+                wine_id
+            }
+            None => {
+                let wine_id = sqlx::query_scalar!(
+                    r#"INSERT INTO wines (cellar_id, name, year) VALUES ($1, $2, $3)
+                       RETURNING wine_id AS "wine_id!""#,
+                    cellar_id,
+                    import.name,
+                    import.year
+                )
+                .fetch_one(&mut *trans)
+                .await?;
+                let wine = get_wine(&mut *trans, cellar_id, wine_id).await?;
+                let change = Change::new(AuditEntity::Wine, wine_id, wine_id).after(Some(wine))?;
+                audit(&mut trans, cellar_id, &user.username, change).await?;
+                wine_id
+            }
+        };
+        let grapes = grape_names(&mut *trans, wine_id).await?;
+        for grape in &import.grapes {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO wine_grapes VALUES($1, $2)",
+                wine_id,
+                grape
+            )
+            .execute(&mut *trans)
+            .await?;
+        }
+        let after = grape_names(&mut *trans, wine_id).await?;
+        audit_grapes(&mut trans, cellar_id, user, wine_id, grapes, after).await?;
+        for food in &import.pairings {
+            let pairing = sqlx::query_as!(
+                FoodPairing,
+                r#"INSERT OR IGNORE INTO wine_food_pairings (wine_id, food) VALUES ($1, $2)
+                   RETURNING id AS "id!", food"#,
+                wine_id,
+                food
+            )
+            .fetch_optional(&mut *trans)
+            .await?;
+            if let Some(pairing) = pairing {
+                let change =
+                    Change::new(AuditEntity::Pairing, pairing.id, wine_id).after(Some(pairing))?;
+                audit(&mut trans, cellar_id, &user.username, change).await?;
+            }
+        }
+        if let Some(note) = &import.note {
+            let before = get_wine(&mut *trans, cellar_id, wine_id).await?;
+            sqlx::query!(
+                "UPDATE wines SET comment=$2, comment_updated_at=$3 WHERE wine_id=$1",
+                wine_id,
+                note,
+                dt
+            )
+            .execute(&mut *trans)
+            .await?;
+            audit_comment(&mut trans, cellar_id, user, &before, Some(note), Some(dt)).await?;
+        }
+        if import.bottles_delta != 0 {
+            let event_id = sqlx::query_scalar!(
+                r#"INSERT INTO wine_inventory_events (wine_id, kind, bottles, dt)
+                   VALUES ($1, 'correction', $2, $3) RETURNING event_id AS "event_id!""#,
+                wine_id,
+                import.bottles_delta,
+                dt
+            )
+            .fetch_one(&mut *trans)
+            .await?;
+            let in_stock = bottles_in_stock(&mut trans, wine_id).await?;
+            check_placed(&mut trans, wine_id, in_stock).await?;
+            audit_event(&mut trans, cellar_id, user, wine_id, event_id, None).await?;
+        }
+    }
+    trans.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(db))]
+pub(crate) async fn wine_image(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: i64,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let res = sqlx::query_scalar!(
+        "SELECT image FROM wines WHERE wine_id=$1 AND cellar_id=$2 AND deleted_at IS NULL",
+        wine_id,
+        cellar_id
+    )
+    .fetch_optional(db)
+    .await?
+    .flatten();
+    Ok(res)
+}
+
+/// Returns all food pairings for the given wine, ordered by insertion time.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn get_wine_food_pairings(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: i64,
+) -> anyhow::Result<Vec<FoodPairing>> {
+    check_wine(db, cellar_id, wine_id).await?;
+    pairings_of(db, wine_id).await
+}
+
+async fn pairings_of(
+    db: impl sqlx::SqliteExecutor<'_>,
+    wine_id: i64,
+) -> anyhow::Result<Vec<FoodPairing>> {
+    let res = sqlx::query_as!(
+        FoodPairing,
+        r#"SELECT id AS "id!", food FROM wine_food_pairings WHERE wine_id = $1 ORDER BY id"#,
+        wine_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+/// Inserts a new food pairing and returns the created record.
+/// Returns a DB error (unique constraint) if the pairing already exists
+/// case-insensitively on this wine.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn add_food_pairing(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    food: &str,
+) -> anyhow::Result<FoodPairing> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let res = sqlx::query_as!(
+        FoodPairing,
+        r#"INSERT INTO wine_food_pairings (wine_id, food) VALUES ($1, $2)
+           RETURNING id AS "id!", food"#,
+        wine_id,
+        food
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let change = Change::new(AuditEntity::Pairing, res.id, wine_id).after(Some(&res))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(res)
+}
+
+/// Returns one of the wine's food pairings. Fails with [`sqlx::Error::RowNotFound`] if the
+/// wine has no such pairing.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn get_food_pairing(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    wine_id: i64,
+    pairing_id: i64,
+) -> anyhow::Result<FoodPairing> {
+    check_wine(db, cellar_id, wine_id).await?;
+    let res = sqlx::query_as!(
+        FoodPairing,
+        r#"SELECT id AS "id!", food FROM wine_food_pairings WHERE id = $1 AND wine_id = $2"#,
+        pairing_id,
+        wine_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(res)
+}
+
+/// Renames a food pairing, scoped to wine_id like [`remove_food_pairing`]. Fails with
+/// [`sqlx::Error::RowNotFound`] if the wine has no such pairing, and with a DB error (unique
+/// constraint) if the wine already has the new one.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn update_food_pairing(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    wine_id: i64,
+    pairing_id: i64,
+    food: &str,
+) -> anyhow::Result<FoodPairing> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let before = sqlx::query_as!(
+        FoodPairing,
+        r#"SELECT id AS "id!", food FROM wine_food_pairings WHERE id = $1 AND wine_id = $2"#,
+        pairing_id,
+        wine_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let res = sqlx::query_as!(
+        FoodPairing,
+        r#"UPDATE wine_food_pairings SET food = $1 WHERE id = $2
+           RETURNING id AS "id!", food"#,
+        food,
+        pairing_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let change = Change::new(AuditEntity::Pairing, pairing_id, wine_id)
+        .before(Some(before))?
+        .after(Some(&res))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(res)
+}
+
+/// Deletes a food pairing by its id, scoped to wine_id to prevent cross-wine deletions. Fails
+/// with [`sqlx::Error::RowNotFound`] if the wine has no such pairing.
+#[tracing::instrument(skip(db, user))]
+pub(crate) async fn remove_food_pairing(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user: &User,
+    pairing_id: i64,
+    wine_id: i64,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    check_wine(&mut *trans, cellar_id, wine_id).await?;
+    let before = sqlx::query_as!(
+        FoodPairing,
+        r#"DELETE FROM wine_food_pairings WHERE id = $1 AND wine_id = $2
+           RETURNING id AS "id!", food"#,
+        pairing_id,
+        wine_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    let change = Change::new(AuditEntity::Pairing, pairing_id, wine_id).before(Some(before))?;
+    audit(&mut trans, cellar_id, &user.username, change).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Marks the start of a match in [`SearchHit`] texts. Control characters can't be typed into
+/// the forms, so they can't be confused with the wine's own text.
+pub(crate) const MATCH_START: char = '\u{2}';
+/// Marks the end of a match in [`SearchHit`] texts.
+pub(crate) const MATCH_END: char = '\u{3}';
+
+/// A wine found by [`search_wines`]. Matches in `name` and `snippet` are enclosed in
+/// [`MATCH_START`] and [`MATCH_END`].
+#[derive(Debug)]
+pub(crate) struct SearchHit {
+    pub wine_id: i64,
+    pub year: i64,
+    pub name: String,
+    /// The part of the wine's text that matched best, which may be its name.
+    pub snippet: String,
+}
+
+/// Turns what the user typed into an FTS5 query: every word must match, as a prefix so that
+/// results show up while typing. Words are quoted, so FTS5 operators and punctuation are
+/// searched for rather than interpreted. `None` if there is nothing to search for.
+fn fts_query(q: &str) -> Option<String> {
+    let words: Vec<_> = q
+        .split_whitespace()
+        .filter(|w| w.chars().any(char::is_alphanumeric))
+        .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Searches the names, producers, notes, tasting notes, grapes and pairings of the cellar's
+/// wines. The best matches come first, with matches in the name and producer weighing most.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn search_wines(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    q: &str,
+    limit: i64,
+) -> anyhow::Result<Vec<SearchHit>> {
+    let Some(query) = fts_query(q) else {
+        return Ok(Vec::new());
+    };
+    let res = sqlx::query_as!(
+        SearchHit,
+        r#"SELECT w.wine_id AS "wine_id!", w.year,
+                  highlight(wine_search, 0, char(2), char(3)) AS "name!: String",
+                  snippet(wine_search, -1, char(2), char(3), '…', 12) AS "snippet!: String"
+           FROM wine_search
+           JOIN wines w ON w.wine_id = wine_search.rowid
+           WHERE wine_search MATCH $1 AND w.cellar_id = $3 AND w.deleted_at IS NULL
+           ORDER BY bm25(wine_search, 10.0, 5.0, 1.0, 1.0, 3.0, 3.0), w.name
+           LIMIT $2"#,
+        query,
+        limit,
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+/// Searches the cellar's wines by food pairing using a case-insensitive substring match.
+/// Special LIKE characters (`%`, `_`, `\`) in `q` are escaped so they are
+/// treated as literals.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn search_wines_by_food(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    q: &str,
+) -> anyhow::Result<Vec<WineWithPairings>> {
+    let pattern = format!("%{}%", like_escape(q));
+
+    let wines = sqlx::query!(
+        r#"SELECT DISTINCT w.wine_id, w.name, w.year
+           FROM wines w
+           JOIN wine_food_pairings fp ON fp.wine_id = w.wine_id
+           WHERE w.cellar_id = $2 AND w.deleted_at IS NULL AND fp.food LIKE $1 ESCAPE '\'
+           ORDER BY w.name, w.year"#,
+        pattern,
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut result = Vec::new();
+    for wine in wines {
+        let pairings = sqlx::query_scalar!(
+            "SELECT food FROM wine_food_pairings WHERE wine_id = $1 ORDER BY id",
+            wine.wine_id
+        )
+        .fetch_all(db)
+        .await?;
+        result.push(WineWithPairings {
+            wine_id: wine.wine_id.expect("wine_id is NOT NULL"),
+            name: wine.name,
+            year: wine.year,
+            matched_pairings: pairings,
+        });
+    }
+    Ok(result)
+}
+
+/// A local account. The password hash never leaves [`user_login`]. Admins manage the
+/// accounts, add cellars and back up and restore the whole database.
+#[derive(serde::Serialize, Debug, Clone)]
+pub(crate) struct User {
+    pub user_id: i64,
+    pub username: String,
+    pub is_admin: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Why a change to an account or to who has access to a cellar was refused.
+#[derive(Debug)]
+pub(crate) enum AccountError {
+    /// The change would leave the cellar with this name without an owner.
+    LastOwner(String),
+    NoSuchUser(String),
+}
+
+impl std::fmt::Display for AccountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LastOwner(cellar) => write!(f, "{cellar} would be left without an owner"),
+            Self::NoSuchUser(username) => write!(f, "There is no user called {username}"),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+#[tracing::instrument(skip(db))]
+pub(crate) async fn users(db: &sqlx::SqlitePool) -> anyhow::Result<Vec<User>> {
+    let res = sqlx::query_as!(
+        User,
+        r#"SELECT user_id AS "user_id!", username, is_admin, created_at FROM users
+           ORDER BY username COLLATE NOCASE"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+#[tracing::instrument(skip(db))]
+pub(crate) async fn has_users(db: &sqlx::SqlitePool) -> anyhow::Result<bool> {
+    let res = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM users) AS "exists!: bool""#)
+        .fetch_one(db)
+        .await?;
+    Ok(res)
+}
+
+/// Adds an account, without access to any cellar. Fails with a unique violation if the
+/// username is taken, ignoring case.
+#[tracing::instrument(skip(db, password_hash))]
+pub(crate) async fn add_user(
+    db: &sqlx::SqlitePool,
+    username: &str,
+    password_hash: &str,
+    is_admin: bool,
+) -> anyhow::Result<User> {
+    let res = sqlx::query_as!(
+        User,
+        r#"INSERT INTO users (username, password_hash, is_admin) VALUES ($1, $2, $3)
+           RETURNING user_id AS "user_id!", username, is_admin, created_at"#,
+        username,
+        password_hash,
+        is_admin
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(res)
+}
+
+/// Adds the first account, an admin owning every cellar, or returns `None` if there already
+/// is one. Checked in the same statement, so two browsers racing through the first run can't
+/// both get in.
+#[tracing::instrument(skip(db, password_hash))]
+pub(crate) async fn add_first_user(
+    db: &sqlx::SqlitePool,
+    username: &str,
+    password_hash: &str,
+) -> anyhow::Result<Option<User>> {
+    let mut trans = db.begin().await?;
+    let Some(user) = sqlx::query_as!(
+        User,
+        r#"INSERT INTO users (username, password_hash, is_admin)
+           SELECT $1, $2, TRUE WHERE NOT EXISTS (SELECT 1 FROM users)
+           RETURNING user_id AS "user_id!", username, is_admin, created_at"#,
+        username,
+        password_hash
+    )
+    .fetch_optional(&mut *trans)
+    .await?
+    else {
+        return Ok(None);
+    };
+    sqlx::query!(
+        "INSERT INTO cellar_members (cellar_id, user_id, role)
+         SELECT cellar_id, $1, 'owner' FROM cellars",
+        user.user_id
+    )
+    .execute(&mut *trans)
+    .await?;
+    trans.commit().await?;
+    Ok(Some(user))
+}
+
+/// The account with `username`, ignoring case, and its password hash.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn user_login(
+    db: &sqlx::SqlitePool,
+    username: &str,
+) -> anyhow::Result<Option<(User, String)>> {
+    let res = sqlx::query!(
+        r#"SELECT user_id AS "user_id!", username, is_admin, created_at, password_hash
+           FROM users WHERE username = $1"#,
+        username
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|r| {
+        let user = User {
+            user_id: r.user_id,
+            username: r.username,
+            is_admin: r.is_admin,
+            created_at: r.created_at,
+        };
+        (user, r.password_hash)
+    });
+    Ok(res)
+}
+
+#[tracing::instrument(skip(db))]
+pub(crate) async fn user_password_hash(
+    db: &sqlx::SqlitePool,
+    user_id: i64,
+) -> anyhow::Result<String> {
+    let res = sqlx::query_scalar!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        user_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(res)
+}
+
+/// Changes a password and logs the account out everywhere but the session `keep`.
+#[tracing::instrument(skip(db, password_hash, keep))]
+pub(crate) async fn set_password(
+    db: &sqlx::SqlitePool,
+    user_id: i64,
+    password_hash: &str,
+    keep: &str,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    sqlx::query!(
+        "UPDATE users SET password_hash = $2 WHERE user_id = $1 RETURNING user_id",
+        user_id,
+        password_hash
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM sessions WHERE user_id = $1 AND token_hash != $2",
+        user_id,
+        keep
+    )
+    .execute(&mut *trans)
+    .await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Deletes an account along with its sessions and memberships. Fails with
+/// [`AccountError::LastOwner`] if it is the only owner of a cellar.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn delete_user(db: &sqlx::SqlitePool, user_id: i64) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    let sole_owner = sqlx::query_scalar!(
+        "SELECT c.name FROM cellar_members m JOIN cellars c ON c.cellar_id = m.cellar_id
+         WHERE m.user_id = $1 AND m.role = 'owner'
+           AND NOT EXISTS (SELECT 1 FROM cellar_members o
+                           WHERE o.cellar_id = m.cellar_id AND o.role = 'owner'
+                             AND o.user_id != $1)
+         LIMIT 1",
+        user_id
+    )
+    .fetch_optional(&mut *trans)
+    .await?;
+    if let Some(cellar) = sole_owner {
+        return Err(AccountError::LastOwner(cellar).into());
+    }
+    sqlx::query!("DELETE FROM sessions WHERE user_id = $1", user_id)
+        .execute(&mut *trans)
+        .await?;
+    sqlx::query!("DELETE FROM cellar_members WHERE user_id = $1", user_id)
+        .execute(&mut *trans)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM users WHERE user_id = $1 RETURNING user_id",
+        user_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Starts a session that lasts until `expires_at`, and clears out the ones that have ended.
+#[tracing::instrument(skip(db, token_hash))]
+pub(crate) async fn add_session(
+    db: &sqlx::SqlitePool,
+    token_hash: &str,
+    user_id: i64,
+    now: chrono::NaiveDateTime,
+    expires_at: chrono::NaiveDateTime,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    sqlx::query!("DELETE FROM sessions WHERE expires_at <= $1", now)
+        .execute(&mut *trans)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO sessions (token_hash, user_id, expires_at) VALUES ($1, $2, $3)",
+        token_hash,
+        user_id,
+        expires_at
+    )
+    .execute(&mut *trans)
+    .await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// The account logged in with the session, unless it has expired, and the cellar last
+/// switched to in it, if any.
+#[tracing::instrument(skip(db, token_hash))]
+pub(crate) async fn session_user(
+    db: &sqlx::SqlitePool,
+    token_hash: &str,
+    now: chrono::NaiveDateTime,
+) -> anyhow::Result<Option<(User, Option<i64>)>> {
+    let res = sqlx::query!(
+        r#"SELECT u.user_id AS "user_id!", u.username, u.is_admin, u.created_at, s.cellar_id
+           FROM sessions s JOIN users u ON u.user_id = s.user_id
+           WHERE s.token_hash = $1 AND s.expires_at > $2"#,
+        token_hash,
+        now
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|r| {
+        let user = User {
+            user_id: r.user_id,
+            username: r.username,
+            is_admin: r.is_admin,
+            created_at: r.created_at,
+        };
+        (user, r.cellar_id)
+    });
+    Ok(res)
+}
+
+/// Makes the session open `cellar_id` from now on.
+#[tracing::instrument(skip(db, token_hash))]
+pub(crate) async fn set_session_cellar(
+    db: &sqlx::SqlitePool,
+    token_hash: &str,
+    cellar_id: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE sessions SET cellar_id = $2 WHERE token_hash = $1",
+        token_hash,
+        cellar_id
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(db, token_hash))]
+pub(crate) async fn delete_session(db: &sqlx::SqlitePool, token_hash: &str) -> anyhow::Result<()> {
+    sqlx::query!("DELETE FROM sessions WHERE token_hash = $1", token_hash)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// What a member may do in a cellar. Editors change the wines and the racks; owners also
+/// decide who else has access.
+#[derive(sqlx::Type, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CellarRole {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+impl CellarRole {
+    pub(crate) const ALL: [Self; 3] = [Self::Owner, Self::Editor, Self::Viewer];
+
+    /// The value stored in `cellar_members.role` and used in forms.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Editor => "editor",
+            Self::Viewer => "viewer",
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Owner => "Owner",
+            Self::Editor => "Editor",
+            Self::Viewer => "Viewer",
+        }
+    }
+
+    pub(crate) fn can_edit(self) -> bool {
+        self != Self::Viewer
+    }
+}
+
+impl FromStr for CellarRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown role: {s}"))
+    }
+}
+
+/// A cellar as seen by one of its members.
+#[derive(serde::Serialize, Debug, Clone)]
+pub(crate) struct Cellar {
+    pub cellar_id: i64,
+    pub name: String,
+    pub role: CellarRole,
+}
+
+/// The cellars the user has access to, by name.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn user_cellars(
+    db: &sqlx::SqlitePool,
+    user_id: i64,
+) -> anyhow::Result<Vec<Cellar>> {
+    let res = sqlx::query_as!(
+        Cellar,
+        r#"SELECT c.cellar_id AS "cellar_id!", c.name, m.role AS "role: CellarRole"
+           FROM cellars c JOIN cellar_members m ON m.cellar_id = c.cellar_id
+           WHERE m.user_id = $1
+           ORDER BY c.name COLLATE NOCASE, c.cellar_id"#,
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+/// Adds an empty cellar owned by `owner_id`.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn add_cellar(
+    db: &sqlx::SqlitePool,
+    name: &str,
+    owner_id: i64,
+) -> anyhow::Result<Cellar> {
+    let mut trans = db.begin().await?;
+    let cellar_id = sqlx::query_scalar!(
+        "INSERT INTO cellars (name) VALUES ($1) RETURNING cellar_id",
+        name
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO cellar_members (cellar_id, user_id, role) VALUES ($1, $2, 'owner')",
+        cellar_id,
+        owner_id
+    )
+    .execute(&mut *trans)
+    .await?;
+    trans.commit().await?;
+    Ok(Cellar {
+        cellar_id,
+        name: name.to_owned(),
+        role: CellarRole::Owner,
+    })
+}
+
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct CellarMember {
+    pub user_id: i64,
+    pub username: String,
+    pub role: CellarRole,
+}
+
+#[tracing::instrument(skip(db))]
+pub(crate) async fn cellar_members(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+) -> anyhow::Result<Vec<CellarMember>> {
+    let res = sqlx::query_as!(
+        CellarMember,
+        r#"SELECT u.user_id AS "user_id!", u.username, m.role AS "role: CellarRole"
+           FROM cellar_members m JOIN users u ON u.user_id = m.user_id
+           WHERE m.cellar_id = $1
+           ORDER BY u.username COLLATE NOCASE"#,
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+/// Fails with [`AccountError::LastOwner`] unless the cellar has an owner.
+async fn check_owner(conn: &mut sqlx::SqliteConnection, cellar_id: i64) -> anyhow::Result<()> {
+    let owner = sqlx::query_scalar!(
+        "SELECT user_id FROM cellar_members WHERE cellar_id = $1 AND role = 'owner' LIMIT 1",
+        cellar_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    if owner.is_none() {
+        let name = sqlx::query_scalar!("SELECT name FROM cellars WHERE cellar_id = $1", cellar_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        return Err(AccountError::LastOwner(name).into());
+    }
+    Ok(())
+}
+
+/// Gives the user called `username` (ignoring case) `role` in the cellar, adding them if they
+/// aren't a member yet. Fails with [`AccountError`] if there is no such user, or if the
+/// cellar would be left without an owner.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn set_member(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    username: &str,
+    role: CellarRole,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    let user_id = sqlx::query_scalar!(
+        r#"SELECT user_id AS "user_id!" FROM users WHERE username = $1"#,
+        username
+    )
+    .fetch_optional(&mut *trans)
+    .await?
+    .ok_or_else(|| AccountError::NoSuchUser(username.to_owned()))?;
+    sqlx::query!(
+        "INSERT INTO cellar_members (cellar_id, user_id, role) VALUES ($1, $2, $3)
+         ON CONFLICT (cellar_id, user_id) DO UPDATE SET role = excluded.role",
+        cellar_id,
+        user_id,
+        role
+    )
+    .execute(&mut *trans)
+    .await?;
+    check_owner(&mut trans, cellar_id).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Takes away the user's access to the cellar. Fails with [`AccountError::LastOwner`] if they
+/// are its only owner.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn remove_member(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    user_id: i64,
+) -> anyhow::Result<()> {
+    let mut trans = db.begin().await?;
+    sqlx::query!(
+        "DELETE FROM cellar_members WHERE cellar_id = $1 AND user_id = $2 RETURNING user_id",
+        cellar_id,
+        user_id
+    )
+    .fetch_one(&mut *trans)
+    .await?;
+    check_owner(&mut trans, cellar_id).await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+/// A read-only link to a cellar. The link itself is only shown when it is made; the
+/// database keeps the SHA-256 of its token.
+#[derive(serde::Serialize, Debug, Clone)]
+pub(crate) struct ShareLink {
+    pub share_id: i64,
+    pub label: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+/// The cellar's share links, newest first, expired ones included.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn share_links(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+) -> anyhow::Result<Vec<ShareLink>> {
+    let res = sqlx::query_as!(
+        ShareLink,
+        r#"SELECT share_id AS "share_id!", label, created_at, expires_at
+           FROM share_links WHERE cellar_id = $1
+           ORDER BY created_at DESC, share_id DESC"#,
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+#[tracing::instrument(skip(db, token_hash))]
+pub(crate) async fn add_share_link(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    token_hash: &str,
+    label: &str,
+    expires_at: Option<chrono::NaiveDateTime>,
+) -> anyhow::Result<ShareLink> {
+    let res = sqlx::query_as!(
+        ShareLink,
+        r#"INSERT INTO share_links (cellar_id, token_hash, label, expires_at)
+           VALUES ($1, $2, $3, $4)
+           RETURNING share_id AS "share_id!", label, created_at, expires_at"#,
+        cellar_id,
+        token_hash,
+        label,
+        expires_at
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(res)
+}
+
+/// Revokes a share link. Fails with [`sqlx::Error::RowNotFound`] if the cellar has no such
+/// link.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn delete_share_link(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    share_id: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "DELETE FROM share_links WHERE cellar_id = $1 AND share_id = $2 RETURNING share_id",
+        cellar_id,
+        share_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(())
+}
+
+/// The cellar a share link opens, as long as it hasn't been revoked or expired.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedCellar {
+    pub cellar_id: i64,
+    pub name: String,
+}
+
+#[tracing::instrument(skip(db, token_hash))]
+pub(crate) async fn shared_cellar(
+    db: &sqlx::SqlitePool,
+    token_hash: &str,
+    now: chrono::NaiveDateTime,
+) -> anyhow::Result<Option<SharedCellar>> {
+    let res = sqlx::query_as!(
+        SharedCellar,
+        r#"SELECT c.cellar_id AS "cellar_id!", c.name
+           FROM share_links s JOIN cellars c ON c.cellar_id = s.cellar_id
+           WHERE s.token_hash = $1 AND (s.expires_at IS NULL OR s.expires_at > $2)"#,
+        token_hash,
+        now
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(res)
+}
+
+/// What an [`AuditEntry`] is about. Grapes, images, comments and bottles are a wine's, and
+/// have the wine's id as their own. Locations and racks aren't any wine's.
+#[derive(sqlx::Type, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AuditEntity {
+    Wine,
+    Grapes,
+    Pairing,
+    Event,
+    Image,
+    Comment,
+    Tasting,
+    /// A bottle put in a rack (added), taken out (deleted) or moved (changed).
+    Bottle,
+    Location,
+    Rack,
+}
+
+impl AuditEntity {
+    pub(crate) const ALL: [Self; 10] = [
+        Self::Wine,
+        Self::Grapes,
+        Self::Pairing,
+        Self::Event,
+        Self::Image,
+        Self::Comment,
+        Self::Tasting,
+        Self::Bottle,
+        Self::Location,
+        Self::Rack,
+    ];
+
+    /// The value stored in `audit_log.entity` and used in query strings.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Wine => "wine",
+            Self::Grapes => "grapes",
+            Self::Pairing => "pairing",
+            Self::Event => "event",
+            Self::Image => "image",
+            Self::Comment => "comment",
+            Self::Tasting => "tasting",
+            Self::Bottle => "bottle",
+            Self::Location => "location",
+            Self::Rack => "rack",
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Wine => "Wine",
+            Self::Grapes => "Grapes",
+            Self::Pairing => "Pairing",
+            Self::Event => "Event",
+            Self::Image => "Image",
+            Self::Comment => "Note",
+            Self::Tasting => "Tasting",
+            Self::Bottle => "Bottle",
+            Self::Location => "Location",
+            Self::Rack => "Rack",
+        }
+    }
+}
+
+impl FromStr for AuditEntity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|entity| entity.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown kind of change: {s}"))
+    }
+}
+
+/// Whether an [`AuditEntry`] added, changed or deleted its entity. It follows from which of
+/// the snapshots are there.
+#[derive(sqlx::Type, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AuditAction {
+    Add,
+    Update,
+    Delete,
+    /// A wine taken back out of the trash.
+    Restore,
+    /// A wine in the trash deleted for good.
+    Purge,
+}
+
+impl AuditAction {
+    pub(crate) const ALL: [Self; 5] = [
+        Self::Add,
+        Self::Update,
+        Self::Delete,
+        Self::Restore,
+        Self::Purge,
+    ];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Restore => "restore",
+            Self::Purge => "purge",
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Add => "Added",
+            Self::Update => "Changed",
+            Self::Delete => "Deleted",
+            Self::Restore => "Restored",
+            Self::Purge => "Purged",
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown action: {s}"))
+    }
+}
+
+/// A change to record with [`audit`]. The snapshots are serialized as they are taken, inside
+/// the change's transaction.
+struct Change {
+    entity: AuditEntity,
+    entity_id: i64,
+    /// `None` for locations and racks.
+    wine_id: Option<i64>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+    /// Set for the actions that don't follow from the snapshots.
+    action: Option<AuditAction>,
+}
+
+impl Change {
+    fn new(entity: AuditEntity, entity_id: i64, wine_id: i64) -> Self {
+        Self {
+            wine_id: Some(wine_id),
+            ..Self::storage(entity, entity_id)
+        }
+    }
+
+    /// A change to a location or rack, which isn't any wine's.
+    fn storage(entity: AuditEntity, entity_id: i64) -> Self {
+        Self {
+            entity,
+            entity_id,
+            wine_id: None,
+            before: None,
+            after: None,
+            action: None,
+        }
+    }
+
+    fn action(self, action: AuditAction) -> Self {
+        Self {
+            action: Some(action),
+            ..self
+        }
+    }
+
+    fn before(self, before: Option<impl serde::Serialize>) -> anyhow::Result<Self> {
+        Ok(Self {
+            before: before.map(serde_json::to_value).transpose()?,
+            ..self
+        })
+    }
+
+    fn after(self, after: Option<impl serde::Serialize>) -> anyhow::Result<Self> {
+        Ok(Self {
+            after: after.map(serde_json::to_value).transpose()?,
+            ..self
+        })
+    }
+}
+
+/// Adds `change`, made by `username`, to the cellar's audit log. Changes that leave everything as
+/// it was aren't recorded. Runs before a wine is deleted, while its name can still be read.
+async fn audit(
+    conn: &mut sqlx::SqliteConnection,
+    cellar_id: i64,
+    username: &str,
+    change: Change,
+) -> anyhow::Result<()> {
+    let action = match (change.action, &change.before, &change.after) {
+        (Some(action), _, _) => action,
+        (None, None, None) => return Ok(()),
+        (None, Some(before), Some(after)) if before == after => return Ok(()),
+        (None, None, Some(_)) => AuditAction::Add,
+        (None, Some(_), Some(_)) => AuditAction::Update,
+        (None, Some(_), None) => AuditAction::Delete,
+    };
+    let wine = match change.wine_id {
+        Some(wine_id) => Some(
+            sqlx::query_scalar!(
+                r#"SELECT name || ' ' || year AS "wine!: String" FROM wines WHERE wine_id = $1"#,
+                wine_id
+            )
+            .fetch_one(&mut *conn)
+            .await?,
+        ),
+        None => None,
+    };
+    let dt = chrono::Local::now().naive_local();
+    let before = change.before.map(|v| v.to_string());
+    let after = change.after.map(|v| v.to_string());
+    sqlx::query!(
+        "INSERT INTO audit_log
+           (cellar_id, dt, username, entity, entity_id, wine_id, wine, action, before, after)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        cellar_id,
+        dt,
+        username,
+        change.entity,
+        change.entity_id,
+        change.wine_id,
+        wine,
+        action,
+        before,
+        after
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// A recorded change, see the `audit_log` migration.
+#[derive(serde::Serialize, Debug, Clone)]
+pub(crate) struct AuditEntry {
+    pub audit_id: i64,
+    pub dt: chrono::NaiveDateTime,
+    pub username: String,
+    pub entity: AuditEntity,
+    pub entity_id: i64,
+    /// `None` for changes to locations and racks.
+    pub wine_id: Option<i64>,
+    /// The wine's name and vintage when the change was made.
+    pub wine: Option<String>,
+    pub action: AuditAction,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Which audit entries to list. Dates are inclusive.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct AuditFilter {
+    pub entity: Option<AuditEntity>,
+    pub action: Option<AuditAction>,
+    pub username: Option<String>,
+    pub wine_id: Option<i64>,
+    pub from: Option<chrono::NaiveDate>,
+    pub until: Option<chrono::NaiveDate>,
+}
+
+/// The cellar's audit entries matching `filter`, newest first: up to `limit` of them older
+/// than the entry `older_than`, if given.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn audit_log(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+    filter: &AuditFilter,
+    older_than: Option<i64>,
+    limit: i64,
+) -> anyhow::Result<Vec<AuditEntry>> {
+    let from = filter.from.map(|d| d.and_time(chrono::NaiveTime::MIN));
+    let until = filter
+        .until
+        .and_then(|d| d.succ_opt())
+        .map(|d| d.and_time(chrono::NaiveTime::MIN));
+    let rows = sqlx::query!(
+        r#"SELECT audit_id AS "audit_id!", dt, username, entity AS "entity: AuditEntity",
+                  entity_id, wine_id, wine, action AS "action: AuditAction", before, after
+           FROM audit_log
+           WHERE cellar_id = $1
+             AND ($2 IS NULL OR entity = $2)
+             AND ($3 IS NULL OR action = $3)
+             AND ($4 IS NULL OR username = $4 COLLATE NOCASE)
+             AND ($5 IS NULL OR wine_id = $5)
+             AND ($6 IS NULL OR dt >= $6)
+             AND ($7 IS NULL OR dt < $7)
+             AND ($8 IS NULL OR audit_id < $8)
+           ORDER BY audit_id DESC
+           LIMIT $9"#,
+        cellar_id,
+        filter.entity,
+        filter.action,
+        filter.username,
+        filter.wine_id,
+        from,
+        until,
+        older_than,
+        limit
+    )
+    .fetch_all(db)
+    .await?;
+    let json = |s: Option<String>| s.map(|s| serde_json::from_str(&s)).transpose();
+    rows.into_iter()
+        .map(|r| {
+            Ok(AuditEntry {
+                audit_id: r.audit_id,
+                dt: r.dt,
+                username: r.username,
+                entity: r.entity,
+                entity_id: r.entity_id,
+                wine_id: r.wine_id,
+                wine: r.wine,
+                action: r.action,
+                before: json(r.before)?,
+                after: json(r.after)?,
+            })
+        })
+        .collect()
+}
+
+/// Everyone who has changed something in the cellar, for filtering its audit log.
+#[tracing::instrument(skip(db))]
+pub(crate) async fn audit_usernames(
+    db: &sqlx::SqlitePool,
+    cellar_id: i64,
+) -> anyhow::Result<Vec<String>> {
+    let res = sqlx::query_scalar!(
+        "SELECT DISTINCT username FROM audit_log WHERE cellar_id = $1
+         ORDER BY username COLLATE NOCASE",
+        cellar_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cellar the migrations create.
+    const CELLAR: i64 = 1;
+
+    /// Who the tests make their changes as; the audit log only keeps the name.
+    fn tester() -> User {
+        User {
+            user_id: 1,
+            username: "tester".to_owned(),
+            is_admin: true,
+            created_at: chrono::NaiveDateTime::default(),
+        }
+    }
+
+    async fn setup_db() -> sqlx::SqlitePool {
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("connect to in-memory DB");
+        sqlx::migrate!().run(&pool).await.expect("run migrations");
+        pool
+    }
+
+    fn fields(name: &str, year: i64) -> WineFields<'_> {
+        WineFields {
+            name,
+            year,
+            style: None,
+            producer_id: None,
+            region_id: None,
+            drink_from: None,
+            drink_until: None,
+        }
+    }
+
+    /// Counts every statement SQLite starts executing on the connection it is installed on.
+    unsafe extern "C" fn count_statement(
+        _event: std::ffi::c_uint,
+        ctx: *mut std::ffi::c_void,
+        _stmt: *mut std::ffi::c_void,
+        _sql: *mut std::ffi::c_void,
+    ) -> std::ffi::c_int {
+        // SAFETY: ctx is the leaked &'static AtomicUsize handed to sqlite3_trace_v2 below
+        let counter = unsafe { &*(ctx as *const std::sync::atomic::AtomicUsize) };
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        0
+    }
+
+    /// Single-connection in-memory DB with a statement counter attached, so every query the
+    /// code under test issues is observed.
+    async fn setup_counting_db() -> (sqlx::SqlitePool, &'static std::sync::atomic::AtomicUsize) {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("connect to in-memory DB");
+        sqlx::migrate!().run(&pool).await.expect("run migrations");
+
+        let counter: &'static _ = Box::leak(Box::new(std::sync::atomic::AtomicUsize::new(0)));
+        let mut conn = pool.acquire().await.unwrap();
+        let mut handle = conn.lock_handle().await.unwrap();
+        // SAFETY: the connection outlives the pool used by the test and the counter is 'static
+        let rc = unsafe {
+            libsqlite3_sys::sqlite3_trace_v2(
+                handle.as_raw_handle().as_ptr(),
+                libsqlite3_sys::SQLITE_TRACE_STMT as std::ffi::c_uint,
+                Some(count_statement),
+                counter as *const _ as *mut std::ffi::c_void,
+            )
+        };
+        assert_eq!(rc, libsqlite3_sys::SQLITE_OK);
+        (pool, counter)
+    }
+
+    async fn seed_wines(db: &sqlx::SqlitePool, n: i64) {
+        let dt = chrono::NaiveDateTime::parse_from_str("2026-03-10 12:00:00", "%Y-%m-%d %H:%M:%S")
+            .unwrap();
+        for i in 0..n {
+            let wine = add_wine(
+                db,
+                CELLAR,
+                &tester(),
+                &fields(&format!("Wine {i}"), 2000 + i % 20),
+            )
+            .await
+            .unwrap();
+            set_wine_grapes(
+                db,
+                CELLAR,
+                &tester(),
+                wine.wine_id,
+                &["Merlot", "Cabernet franc"],
+            )
+            .await
+            .unwrap();
+            add_food_pairing(db, CELLAR, &tester(), wine.wine_id, "lamb")
+                .await
+                .unwrap();
+            add_food_pairing(db, CELLAR, &tester(), wine.wine_id, "beef")
+                .await
+                .unwrap();
+            add_wine_event(
+                db,
+                CELLAR,
+                &tester(),
+                wine.wine_id,
+                &NewWineEvent::new(EventKind::Purchase, 6, dt),
+            )
+            .await
+            .unwrap();
+            add_wine_event(
+                db,
+                CELLAR,
+                &tester(),
+                wine.wine_id,
+                &NewWineEvent::new(EventKind::Consumption, -1, dt),
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wine_rows_aggregates() {
+        let db = setup_db().await;
+        seed_wines(&db, 2).await;
+        let empty = add_wine(&db, CELLAR, &tester(), &fields("Empty", 2024))
+            .await
+            .unwrap();
+
+        let rows = wine_rows(&db, CELLAR, &WineFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        let row = rows.iter().find(|r| r.name == "Wine 0").unwrap();
+        assert_eq!(row.bottles, 5);
+        assert_eq!(row.grapes, ["Cabernet franc", "Merlot"]);
+        assert_eq!(row.pairings, ["lamb", "beef"]);
+
+        let row = wine_row(&db, CELLAR, empty.wine_id).await.unwrap();
+        assert_eq!(row.bottles, 0);
+        assert!(row.grapes.is_empty());
+        assert!(row.pairings.is_empty());
+
+        let in_stock = WineFilter {
+            in_stock: true,
+            ..Default::default()
+        };
+        let rows = wine_rows(&db, CELLAR, &in_stock).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|r| r.wine_id != empty.wine_id));
+    }
+
+    #[tokio::test]
+    async fn test_wine_rows_order_and_pages() {
+        let db = setup_db().await;
+        let dt = |day| {
+            chrono::NaiveDate::from_ymd_opt(2026, 1, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        let mut ids = Vec::new();
+        for (name, year, bottles, day) in [("b", 2018, 3, 5), ("A", 2020, 1, 9), ("c", 2015, 6, 1)]
+        {
+            let wine = add_wine(&db, CELLAR, &tester(), &fields(name, year))
+                .await
+                .unwrap();
+            add_wine_event(
+                &db,
+                CELLAR,
+                &tester(),
+                wine.wine_id,
+                &NewWineEvent::new(EventKind::Purchase, bottles, dt(day)),
+            )
+            .await
+            .unwrap();
+            ids.push(wine.wine_id);
+        }
+        let idle = add_wine(&db, CELLAR, &tester(), &fields("d", 2010))
+            .await
+            .unwrap();
+
+        let names = |rows: Vec<WineRow>| rows.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        let page = |sort, descending, limit, offset| {
+            let db = db.clone();
+            async move {
+                let order = WineOrder { sort, descending };
+                names(
+                    wine_rows_page(&db, CELLAR, &WineFilter::default(), order, limit, offset)
+                        .await
+                        .unwrap(),
+                )
+            }
+        };
+        // Names sort case-insensitively
+        assert_eq!(
+            page(WineSort::Name, false, -1, 0).await,
+            ["A", "b", "c", "d"]
+        );
+        assert_eq!(page(WineSort::Year, true, 2, 0).await, ["A", "b"]);
+        assert_eq!(page(WineSort::Year, true, 2, 2).await, ["c", "d"]);
+        assert_eq!(
+            page(WineSort::Bottles, true, -1, 0).await,
+            ["c", "b", "A", "d"]
+        );
+        // Wines without activity come last in both directions
+        assert_eq!(
+            page(WineSort::LastActivity, false, -1, 0).await,
+            ["c", "b", "A", "d"]
+        );
+        assert_eq!(
+            page(WineSort::LastActivity, true, -1, 0).await,
+            ["A", "b", "c", "d"]
+        );
+        let row = wine_row(&db, CELLAR, idle.wine_id).await.unwrap();
+        assert_eq!(row.last_activity, None);
+        assert_eq!(
+            wine_row(&db, CELLAR, ids[1]).await.unwrap().last_activity,
+            Some(dt(9))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wine_filters() {
+        let db = setup_db().await;
+        let dt = chrono::Local::now().naive_local();
+        let old = add_wine(&db, CELLAR, &tester(), &fields("Old", 2005))
+            .await
+            .unwrap();
+        let mid = add_wine(&db, CELLAR, &tester(), &fields("Mid", 2015))
+            .await
+            .unwrap();
+        let new = add_wine(&db, CELLAR, &tester(), &fields("New", 2022))
+            .await
+            .unwrap();
+        set_wine_grapes(&db, CELLAR, &tester(), old.wine_id, &["Merlot"])
+            .await
+            .unwrap();
+        set_wine_grapes(&db, CELLAR, &tester(), mid.wine_id, &["Malbec", "Merlot"])
+            .await
+            .unwrap();
+        set_wine_grapes(&db, CELLAR, &tester(), new.wine_id, &["Riesling"])
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), mid.wine_id, "Grilled 100% beef")
+            .await
+            .unwrap();
+        set_wine_comment(&db, CELLAR, &tester(), new.wine_id, Some("Zesty"), Some(dt))
+            .await
+            .unwrap();
+        add_wine_event(
+            &db,
+            CELLAR,
+            &tester(),
+            old.wine_id,
+            &NewWineEvent::new(EventKind::Purchase, 2, dt),
+        )
+        .await
+        .unwrap();
+
+        let names = |rows: Vec<WineRow>| rows.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        let matching = |filter: WineFilter| {
+            let db = db.clone();
+            async move { names(wine_rows(&db, CELLAR, &filter).await.unwrap()) }
+        };
+        let grapes = |gs: &[&str]| WineFilter {
+            grapes: gs.iter().map(|g| g.to_string()).collect(),
+            ..Default::default()
+        };
+
+        // Grapes match exactly, any of them
+        assert_eq!(matching(grapes(&["Merlot"])).await, ["Mid", "Old"]);
+        assert_eq!(
+            matching(grapes(&["Malbec", "Riesling"])).await,
+            ["Mid", "New"]
+        );
+        assert!(matching(grapes(&["Merl"])).await.is_empty());
+
+        let vintages = WineFilter {
+            year_from: Some(2010),
+            year_to: Some(2020),
+            ..Default::default()
+        };
+        assert_eq!(matching(vintages).await, ["Mid"]);
+        let in_stock = WineFilter {
+            in_stock: true,
+            ..Default::default()
+        };
+        assert_eq!(matching(in_stock).await, ["Old"]);
+        let has_note = WineFilter {
+            has_note: true,
+            ..Default::default()
+        };
+        assert_eq!(matching(has_note).await, ["New"]);
+        let has_image = WineFilter {
+            has_image: true,
+            ..Default::default()
+        };
+        assert!(matching(has_image).await.is_empty());
+        let pairing = |p: &str| WineFilter {
+            pairing: Some(p.to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(matching(pairing("BEEF")).await, ["Mid"]);
+        assert_eq!(matching(pairing("100%")).await, ["Mid"]);
+        assert!(matching(pairing("0%b")).await.is_empty());
+
+        // Filters combine
+        let combined = WineFilter {
+            year_to: Some(2010),
+            ..grapes(&["Merlot"])
+        };
+        assert_eq!(matching(combined).await, ["Old"]);
+
+        let facets = grape_facets(&db, CELLAR).await.unwrap();
+        let merlot = facets.iter().find(|f| f.name == "Merlot").unwrap();
+        assert_eq!(merlot.wines, 2);
+        assert_eq!(facets.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_wine_rows_query_count_is_constant() {
+        let mut counts = Vec::new();
+        for n in [3, 300] {
+            let (db, counter) = setup_counting_db().await;
+            seed_wines(&db, n).await;
+
+            counter.store(0, std::sync::atomic::Ordering::SeqCst);
+            let rows = wine_rows(&db, CELLAR, &WineFilter::default())
+                .await
+                .unwrap();
+            let queries = counter.load(std::sync::atomic::Ordering::SeqCst);
+
+            assert_eq!(rows.len(), n as usize);
+            counts.push(queries);
+        }
+        assert_eq!(
+            counts[0], counts[1],
+            "query count must not grow with the cellar"
+        );
+        assert_eq!(counts[0], 1);
+    }
+
+    #[tokio::test]
+    async fn test_add_wine_event_checks_stock() {
+        let db = setup_db().await;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Stocked", 2020))
+            .await
+            .unwrap();
+        let dt = chrono::Local::now().naive_local();
+        let rejection = |res: anyhow::Result<()>| {
+            res.unwrap_err()
+                .downcast::<InventoryError>()
+                .expect("an inventory error")
+        };
+
+        assert_eq!(
+            rejection(
+                add_wine_event(
+                    &db,
+                    CELLAR,
+                    &tester(),
+                    wine.wine_id,
+                    &NewWineEvent::new(EventKind::Purchase, 0, dt)
+                )
+                .await
+            ),
+            InventoryError::NoBottles
+        );
+        assert_eq!(
+            rejection(
+                add_wine_event(
+                    &db,
+                    CELLAR,
+                    &tester(),
+                    wine.wine_id,
+                    &NewWineEvent::new(EventKind::Consumption, -1, dt)
+                )
+                .await
+            ),
+            InventoryError::NotEnoughBottles {
+                in_stock: 0,
+                requested: 1
+            }
+        );
+
+        add_wine_event(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            &NewWineEvent::new(EventKind::Purchase, 3, dt),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            rejection(
+                add_wine_event(
+                    &db,
+                    CELLAR,
+                    &tester(),
+                    wine.wine_id,
+                    &NewWineEvent::new(EventKind::Consumption, -4, dt)
+                )
+                .await
+            ),
+            InventoryError::NotEnoughBottles {
+                in_stock: 3,
+                requested: 4
+            }
+        );
+        assert_eq!(
+            rejection(
+                add_wine_event(
+                    &db,
+                    CELLAR,
+                    &tester(),
+                    wine.wine_id,
+                    &NewWineEvent::new(EventKind::Loss, 1, dt)
+                )
+                .await
+            ),
+            InventoryError::WrongDirection(EventKind::Loss)
+        );
+        add_wine_event(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            &NewWineEvent::new(EventKind::Gift, -1, dt),
+        )
+        .await
+        .unwrap();
+        add_wine_event(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            &NewWineEvent::new(EventKind::Loss, -2, dt),
+        )
+        .await
+        .unwrap();
+
+        // Rejected events leave nothing behind
+        let events = wine_inventory_events(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
+        let kinds: Vec<_> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [EventKind::Purchase, EventKind::Gift, EventKind::Loss]
+        );
+        assert_eq!(
+            wine_row(&db, CELLAR, wine.wine_id).await.unwrap().bottles,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edit_and_delete_wine_event() {
+        let db = setup_db().await;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Mistyped", 2020))
+            .await
+            .unwrap();
+        let other = add_wine(&db, CELLAR, &tester(), &fields("Other", 2020))
+            .await
+            .unwrap();
+        let dt = chrono::Local::now().naive_local();
+        let stock = |events: &[WineInvEvent]| events.iter().map(|e| e.bottles).sum::<i64>();
+
+        let buy = NewWineEvent::new(EventKind::Purchase, 60, dt);
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &buy)
+            .await
+            .unwrap();
+        let drink = NewWineEvent::new(EventKind::Consumption, -4, dt);
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &drink)
+            .await
+            .unwrap();
+        let events = wine_inventory_events(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
+        let (bought, drunk) = (events[0].event_id, events[1].event_id);
+
+        let fixed = NewWineEvent::new(EventKind::Purchase, 6, dt);
+        update_wine_event(&db, CELLAR, &tester(), wine.wine_id, bought, &fixed)
+            .await
+            .unwrap();
+        let events = wine_inventory_events(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(stock(&events), 2);
+
+        // Too few bottles for the consumption that followed
+        let too_few = NewWineEvent::new(EventKind::Purchase, 3, dt);
+        let err = update_wine_event(&db, CELLAR, &tester(), wine.wine_id, bought, &too_few)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<InventoryError>().unwrap(),
+            InventoryError::StockBelowZero(-1)
+        );
+        let err = delete_wine_event(&db, CELLAR, &tester(), wine.wine_id, bought)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<InventoryError>().unwrap(),
+            InventoryError::StockBelowZero(-4)
+        );
+        let wrong_way = NewWineEvent::new(EventKind::Consumption, 4, dt);
+        let err = update_wine_event(&db, CELLAR, &tester(), wine.wine_id, drunk, &wrong_way)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<InventoryError>().unwrap(),
+            InventoryError::WrongDirection(EventKind::Consumption)
+        );
+        let events = wine_inventory_events(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(stock(&events), 2);
+
+        // Events can't be reached through another wine
+        let err = delete_wine_event(&db, CELLAR, &tester(), other.wine_id, drunk)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<sqlx::Error>(),
+            Some(sqlx::Error::RowNotFound)
+        ));
+        assert!(
+            get_wine_event(&db, CELLAR, other.wine_id, drunk)
+                .await
+                .is_err()
+        );
+
+        delete_wine_event(&db, CELLAR, &tester(), wine.wine_id, drunk)
+            .await
+            .unwrap();
+        let events = wine_inventory_events(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(stock(&events), 6);
+        assert_eq!(
+            get_wine_event(&db, CELLAR, wine.wine_id, bought)
+                .await
+                .unwrap()
+                .bottles,
+            6
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wine_events_keep_running_stock() {
+        let db = setup_db().await;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Rioja", 2015))
+            .await
+            .unwrap();
+        let day = |d| {
+            chrono::NaiveDate::from_ymd_opt(2026, 3, d)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        let below = |d, in_stock| InventoryError::StockBelowZeroOn {
+            date: day(d).date(),
+            in_stock,
+        };
+
+        let first = NewWineEvent::new(EventKind::Purchase, 2, day(1));
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &first)
+            .await
+            .unwrap();
+        let second = NewWineEvent::new(EventKind::Purchase, 6, day(10));
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &second)
+            .await
+            .unwrap();
+        // Enough bottles in total, but not yet on the 5th
+        let backdated = NewWineEvent::new(EventKind::Consumption, -3, day(5));
+        let err = add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &backdated)
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<InventoryError>().unwrap(), below(5, -1));
+        let drink = NewWineEvent::new(EventKind::Consumption, -2, day(5));
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &drink)
+            .await
+            .unwrap();
+        let events = wine_inventory_events(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
+        let first_id = events.iter().find(|e| e.dt == day(1)).unwrap().event_id;
+
+        // Moving the purchase after the consumption, or dropping it, leaves the 5th short
+        let moved = NewWineEvent::new(EventKind::Purchase, 2, day(7));
+        let err = update_wine_event(&db, CELLAR, &tester(), wine.wine_id, first_id, &moved)
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<InventoryError>().unwrap(), below(5, -2));
+        let err = delete_wine_event(&db, CELLAR, &tester(), wine.wine_id, first_id)
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<InventoryError>().unwrap(), below(5, -2));
+        // On the day of the consumption is soon enough
+        let same_day = NewWineEvent::new(EventKind::Purchase, 2, day(5));
+        update_wine_event(&db, CELLAR, &tester(), wine.wine_id, first_id, &same_day)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bottle_slots() {
+        let db = setup_db().await;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Placed", 2020))
+            .await
+            .unwrap();
+        let other = add_wine(&db, CELLAR, &tester(), &fields("Other", 2020))
+            .await
+            .unwrap();
+        let dt = chrono::Local::now().naive_local();
+        let cellar = add_location(&db, CELLAR, &tester(), "Cellar")
+            .await
+            .unwrap();
+        let rack = add_rack(&db, CELLAR, &tester(), cellar.location_id, "A", 2, 3)
+            .await
+            .unwrap();
+        let slot = |row, col| Slot {
+            rack_id: rack.rack_id,
+            row,
+            col,
+        };
+        let rejection = |res: anyhow::Result<()>| {
+            res.unwrap_err()
+                .downcast::<InventoryError>()
+                .expect("an inventory error")
+        };
+        let placed = |wine_id| placed_bottles(&db, CELLAR, Some(wine_id), None);
+
+        let slots = [slot(1, 1), slot(1, 2)];
+        let buy = NewWineEvent {
+            slots: &slots,
+            ..NewWineEvent::new(EventKind::Purchase, 3, dt)
+        };
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &buy)
+            .await
+            .unwrap();
+        let where_is_it: Vec<_> = placed(wine.wine_id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|b| b.slot())
+            .collect();
+        assert_eq!(where_is_it, slots);
+
+        let taken = [slot(1, 1)];
+        let buy_other = NewWineEvent {
+            slots: &taken,
+            ..NewWineEvent::new(EventKind::Purchase, 1, dt)
+        };
+        assert_eq!(
+            rejection(add_wine_event(&db, CELLAR, &tester(), other.wine_id, &buy_other).await),
+            InventoryError::SlotTaken(slot(1, 1))
+        );
+        assert!(placed(other.wine_id).await.unwrap().is_empty());
+        assert_eq!(
+            rejection(place_bottles(&db, CELLAR, &tester(), wine.wine_id, &[slot(3, 1)]).await),
+            InventoryError::NoSuchSlot(slot(3, 1))
+        );
+        // The third bottle can be placed later, but there's no fourth
+        place_bottles(&db, CELLAR, &tester(), wine.wine_id, &[slot(2, 3)])
+            .await
+            .unwrap();
+        assert_eq!(
+            rejection(place_bottles(&db, CELLAR, &tester(), wine.wine_id, &[slot(2, 2)]).await),
+            InventoryError::NotEnoughUnplaced(0)
+        );
+
+        // Drinking a bottle means picking it from its slot
+        let drink = NewWineEvent::new(EventKind::Consumption, -1, dt);
+        assert_eq!(
+            rejection(add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &drink).await),
+            InventoryError::BottlesInRacks {
+                in_stock: 2,
+                placed: 3
+            }
+        );
+        let empty = [slot(2, 1)];
+        let drink_from_empty = NewWineEvent {
+            slots: &empty,
+            ..drink
+        };
+        assert_eq!(
+            rejection(
+                add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &drink_from_empty).await
+            ),
+            InventoryError::NotInSlot(slot(2, 1))
+        );
+        let drink = NewWineEvent {
+            slots: &taken,
+            ..drink_from_empty
+        };
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &drink)
+            .await
+            .unwrap();
+        assert_eq!(placed(wine.wine_id).await.unwrap().len(), 2);
+
+        unplace_bottle(&db, CELLAR, &tester(), wine.wine_id, slot(1, 2))
+            .await
+            .unwrap();
+        let in_cellar = placed_bottles(&db, CELLAR, None, Some(cellar.location_id))
+            .await
+            .unwrap();
+        assert_eq!(in_cellar.len(), 1);
+        assert_eq!(in_cellar[0].slot(), slot(2, 3));
+        assert_eq!(in_cellar[0].location, "Cellar");
+
+        // Moving keeps the stock as it is
+        move_bottle(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            slot(2, 3),
+            slot(1, 3),
+            dt,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            rejection(
+                move_bottle(
+                    &db,
+                    CELLAR,
+                    &tester(),
+                    wine.wine_id,
+                    slot(2, 3),
+                    slot(1, 1),
+                    dt
+                )
+                .await
+            ),
+            InventoryError::NotInSlot(slot(2, 3))
+        );
+        assert_eq!(
+            rejection(
+                move_bottle(
+                    &db,
+                    CELLAR,
+                    &tester(),
+                    wine.wine_id,
+                    slot(1, 3),
+                    slot(1, 3),
+                    dt
+                )
+                .await
+            ),
+            InventoryError::SlotTaken(slot(1, 3))
+        );
+        let moves = bottle_moves(&db, CELLAR, wine.wine_id).await.unwrap();
+        assert_eq!(moves.len(), 1);
+        assert_eq!((moves[0].from, moves[0].to), (slot(2, 3), slot(1, 3)));
+        assert_eq!(moves[0].to_rack, "Cellar › A");
+        let events = wine_inventory_events(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(events.iter().map(|e| e.bottles).sum::<i64>(), 2);
+
+        delete_wine(&db, CELLAR, &tester(), wine.wine_id)
+            .await
+            .unwrap();
+        assert!(bottle_moves(&db, CELLAR, wine.wine_id).await.is_err());
+        assert!(placed(wine.wine_id).await.unwrap().is_empty());
+        // In the trash, the wine keeps its bottles and their moves until it is purged
+        assert_eq!(rows_of(&db, "bottle_moves", wine.wine_id).await, 1);
+        purge_wine(&db, CELLAR, &tester(), wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(rows_of(&db, "bottle_moves", wine.wine_id).await, 0);
+    }
+
+    #[test]
+    fn test_slot_round_trip() {
+        let slot = Slot {
+            rack_id: 3,
+            row: 2,
+            col: 10,
+        };
+        assert_eq!(slot.to_string().parse::<Slot>().unwrap(), slot);
+        assert!("3:2".parse::<Slot>().is_err());
+        assert!("3:2:1:0".parse::<Slot>().is_err());
+        assert!("a:b:c".parse::<Slot>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tastings() {
+        let db = setup_db().await;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Tasted", 2015))
+            .await
+            .unwrap();
+        let other = add_wine(&db, CELLAR, &tester(), &fields("Other", 2015))
+            .await
+            .unwrap();
+        let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        let first = add_tasting(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            &NewTasting {
+                tasted_on: date(2024, 12, 24),
+                score: 90,
+                scale: 100,
+                note: Some("Still tight"),
+                occasion: Some("Christmas"),
+                sheet: None,
+            },
+        )
+        .await
+        .unwrap();
+        // Scored on a different scale: 4/5 counts as 0.8
+        add_tasting(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            &NewTasting {
+                tasted_on: date(2026, 6, 1),
+                score: 4,
+                scale: 5,
+                note: None,
+                occasion: None,
+                sheet: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        let tastings = wine_tastings(&db, CELLAR, wine.wine_id).await.unwrap();
+        assert_eq!(tastings.len(), 2);
+        assert_eq!(tastings[0].tasted_on, date(2026, 6, 1));
+        assert_eq!(tastings[1].occasion.as_deref(), Some("Christmas"));
+        let score = wine_row(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap()
+            .score
+            .unwrap();
+        assert!((score - 0.85).abs() < 1e-9);
+        assert_eq!(
+            wine_row(&db, CELLAR, other.wine_id).await.unwrap().score,
+            None
+        );
+
+        assert!(
+            delete_tasting(&db, CELLAR, &tester(), other.wine_id, first.tasting_id)
+                .await
+                .is_err()
+        );
+        delete_tasting(&db, CELLAR, &tester(), wine.wine_id, first.tasting_id)
+            .await
+            .unwrap();
+        let score = wine_row(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap()
+            .score
+            .unwrap();
+        assert!((score - 0.8).abs() < 1e-9);
+
+        delete_wine(&db, CELLAR, &tester(), wine.wine_id)
+            .await
+            .unwrap();
+        assert!(wine_tastings(&db, CELLAR, wine.wine_id).await.is_err());
+        purge_wine(&db, CELLAR, &tester(), wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(rows_of(&db, "tastings", wine.wine_id).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_tasting_sheets() {
+        let db = setup_db().await;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Barolo", 2016))
+            .await
+            .unwrap();
+        let vocabulary = aromas(&db).await.unwrap();
+        let tasting = |sheet| NewTasting {
+            tasted_on: chrono::NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            score: 93,
+            scale: 100,
+            note: None,
+            occasion: None,
+            sheet,
+        };
+
+        let sheet = TastingSheet {
+            sweetness: Some(1),
+            acidity: Some(4),
+            tannin: Some(5),
+            body: Some(4),
+            finish: Some(5),
+            aromas: vec!["rose".to_owned(), "Tar".to_owned()],
+        };
+        sheet.validate(&vocabulary).unwrap();
+        let added = add_tasting(&db, CELLAR, &tester(), wine.wine_id, &tasting(Some(&sheet)))
+            .await
+            .unwrap();
+        let stored = added.sheet.unwrap();
+        assert_eq!(stored.tannin, Some(5));
+        // Names come back as spelled in the vocabulary
+        assert_eq!(stored.aromas.len(), 2);
+        assert!(stored.aromas.contains(&"Rose".to_owned()));
+
+        // An empty sheet isn't stored
+        let empty = TastingSheet::default();
+        let added = add_tasting(&db, CELLAR, &tester(), wine.wine_id, &tasting(Some(&empty)))
+            .await
+            .unwrap();
+        assert_eq!(added.sheet, None);
+
+        let bad = TastingSheet {
+            acidity: Some(6),
+            ..Default::default()
+        };
+        assert!(bad.validate(&vocabulary).is_err());
+        let unknown = TastingSheet {
+            aromas: vec!["Bubblegum".to_owned()],
+            ..Default::default()
+        };
+        assert!(unknown.validate(&vocabulary).is_err());
+        assert!(
+            add_tasting(
+                &db,
+                CELLAR,
+                &tester(),
+                wine.wine_id,
+                &tasting(Some(&unknown))
+            )
+            .await
+            .is_err()
+        );
+        assert_eq!(
+            wine_tastings(&db, CELLAR, wine.wine_id)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        delete_wine(&db, CELLAR, &tester(), wine.wine_id)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wine_costs() {
+        let db = setup_db().await;
+        let dt = chrono::Local::now().naive_local();
+        let buy = |bottles, amount, currency: &str| NewWineEvent {
+            price: Some(Price {
+                amount,
+                currency: currency.to_owned(),
+            }),
+            vendor: Some("Systembolaget"),
+            ..NewWineEvent::new(EventKind::Purchase, bottles, dt)
+        };
+
+        let sek = add_wine(&db, CELLAR, &tester(), &fields("Kronor", 2020))
+            .await
+            .unwrap();
+        add_wine_event(&db, CELLAR, &tester(), sek.wine_id, &buy(2, 10000, "SEK"))
+            .await
+            .unwrap();
+        add_wine_event(&db, CELLAR, &tester(), sek.wine_id, &buy(4, 13000, "SEK"))
+            .await
+            .unwrap();
+        let unpriced = NewWineEvent::new(EventKind::Gift, 2, dt);
+        add_wine_event(&db, CELLAR, &tester(), sek.wine_id, &unpriced)
+            .await
+            .unwrap();
+        let drink = NewWineEvent::new(EventKind::Consumption, -4, dt);
+        add_wine_event(&db, CELLAR, &tester(), sek.wine_id, &drink)
+            .await
+            .unwrap();
+
+        let mixed = add_wine(&db, CELLAR, &tester(), &fields("Mixed", 2020))
+            .await
+            .unwrap();
+        add_wine_event(&db, CELLAR, &tester(), mixed.wine_id, &buy(1, 2000, "EUR"))
+            .await
+            .unwrap();
+        add_wine_event(&db, CELLAR, &tester(), mixed.wine_id, &buy(3, 20000, "SEK"))
+            .await
+            .unwrap();
+
+        let gone = add_wine(&db, CELLAR, &tester(), &fields("Gone", 2020))
+            .await
+            .unwrap();
+        add_wine_event(&db, CELLAR, &tester(), gone.wine_id, &buy(1, 5000, "SEK"))
+            .await
+            .unwrap();
+        let drink = NewWineEvent::new(EventKind::Consumption, -1, dt);
+        add_wine_event(&db, CELLAR, &tester(), gone.wine_id, &drink)
+            .await
+            .unwrap();
+
+        let costs = wine_costs(&db, CELLAR, None).await.unwrap();
+        let summary: Vec<_> = costs
+            .iter()
+            .map(|c| {
+                (
+                    c.name.as_str(),
+                    c.currency.as_str(),
+                    c.average_cost(),
+                    c.value(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                // (2 × 100 + 4 × 130) / 6 = 120, and 4 bottles left
+                ("Kronor", "SEK", 12000, 48000),
+                // A quarter of the 4 bottles were bought in euros
+                ("Mixed", "EUR", 2000, 2000),
+                ("Mixed", "SEK", 20000, 60000),
+            ]
+        );
+
+        assert_eq!(
+            wine_costs(&db, CELLAR, Some(mixed.wine_id))
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        let events = wine_inventory_events(&db, CELLAR, sek.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(events[0].unit_price, Some(10000));
+        assert_eq!(events[0].vendor.as_deref(), Some("Systembolaget"));
+        assert_eq!(
+            last_currency(&db, CELLAR).await.unwrap().as_deref(),
+            Some("SEK")
+        );
+
+        let priced_gift = NewWineEvent {
+            price: buy(1, 100, "SEK").price,
+            ..NewWineEvent::new(EventKind::Gift, 1, dt)
+        };
+        let err = add_wine_event(&db, CELLAR, &tester(), sek.wine_id, &priced_gift)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<InventoryError>().unwrap(),
+            InventoryError::PriceNotAllowed(EventKind::Gift)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_wines() {
+        let db = setup_db().await;
+        let dt = chrono::NaiveDateTime::parse_from_str("2026-03-10 12:00:00", "%Y-%m-%d %H:%M:%S")
+            .unwrap();
+        let existing = add_wine(&db, CELLAR, &tester(), &fields("Existing", 2018))
+            .await
+            .unwrap();
+        add_wine_event(
+            &db,
+            CELLAR,
+            &tester(),
+            existing.wine_id,
+            &NewWineEvent::new(EventKind::Purchase, 6, dt),
+        )
+        .await
+        .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), existing.wine_id, "Salmon")
+            .await
+            .unwrap();
+
+        let imports = [
+            WineImport {
+                wine_id: Some(existing.wine_id),
+                name: "Existing".to_owned(),
+                year: 2018,
+                bottles_delta: -2,
+                grapes: vec!["Riesling".to_owned()],
+                // Already present (case-insensitively), must not fail the import
+                pairings: vec!["salmon".to_owned(), "trout".to_owned()],
+                note: None,
+            },
+            WineImport {
+                wine_id: None,
+                name: "New".to_owned(),
+                year: 2020,
+                bottles_delta: 3,
+                grapes: vec![],
+                pairings: vec![],
+                note: Some("Imported".to_owned()),
+            },
+        ];
+        import_wines(&db, CELLAR, &tester(), &imports, dt)
+            .await
+            .unwrap();
+
+        let rows = wine_rows(&db, CELLAR, &WineFilter::default())
+            .await
+            .unwrap();
+        let row = rows.iter().find(|r| r.name == "Existing").unwrap();
+        assert_eq!(row.bottles, 4);
+        assert_eq!(row.grapes, ["Riesling"]);
+        assert_eq!(row.pairings, ["Salmon", "trout"]);
+        assert!(row.comment.is_none());
+        let row = rows.iter().find(|r| r.name == "New").unwrap();
+        assert_eq!(row.bottles, 3);
+        assert_eq!(row.comment.as_deref(), Some("Imported"));
+    }
+
+    /// File-backed DB for tests that need real files: `VACUUM INTO` from an in-memory database
+    /// produces another in-memory database instead of a file.
+    async fn setup_file_db(path: &std::path::Path) -> sqlx::SqlitePool {
+        let opts = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = sqlx::SqlitePool::connect_with(opts)
+            .await
+            .expect("create test DB file");
+        sqlx::migrate!().run(&pool).await.expect("run migrations");
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let db_path = scratch_path("test-db");
+        let db = setup_file_db(&db_path).await;
+        let kept = add_wine(&db, CELLAR, &tester(), &fields("Kept", 2018))
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), kept.wine_id, "duck")
+            .await
+            .unwrap();
+
+        let path = scratch_path("test-backup");
+        backup_to(&db, &path).await.unwrap();
+
+        delete_wine(&db, CELLAR, &tester(), kept.wine_id)
+            .await
+            .unwrap();
+        add_wine(&db, CELLAR, &tester(), &fields("After Backup", 2020))
+            .await
+            .unwrap();
+        // Accounts belong to the instance, not to the cellars
+        let anna = add_user(&db, "anna", "hash", true).await.unwrap();
+        let later = add_cellar(&db, "Later", anna.user_id).await.unwrap();
+        let now = chrono::Utc::now().naive_utc();
+        add_session(
+            &db,
+            "s1",
+            anna.user_id,
+            now,
+            now + chrono::Duration::days(1),
+        )
+        .await
+        .unwrap();
+        set_session_cellar(&db, "s1", later.cellar_id)
+            .await
+            .unwrap();
+        add_share_link(&db, CELLAR, "l1", "Friends", None)
+            .await
+            .unwrap();
+
+        let res = restore_from(&db, &path).await;
+        std::fs::remove_file(&path).unwrap();
+        res.unwrap();
+
+        let rows = wine_rows(&db, CELLAR, &WineFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].name, "Kept");
+        assert_eq!(rows[0].pairings, ["duck"]);
+        assert_eq!(users(&db).await.unwrap()[0].username, "anna");
+        // The session survives without the cellar it was switched to, and the admin takes
+        // over the restored cellar, which none of the current accounts owned
+        let (_, cellar_id) = session_user(&db, "s1", now).await.unwrap().unwrap();
+        assert_eq!(cellar_id, None);
+        assert!(shared_cellar(&db, "l1", now).await.unwrap().is_none());
+        // The history goes back to what it was at the time of the backup
+        let log = audit_log(&db, CELLAR, &AuditFilter::default(), None, 10)
+            .await
+            .unwrap();
+        assert_eq!(log.len(), 2);
+        let cellars = user_cellars(&db, anna.user_id).await.unwrap();
+        assert_eq!(cellars.len(), 1);
+        assert_eq!(
+            (cellars[0].cellar_id, cellars[0].role),
+            (CELLAR, CellarRole::Owner)
+        );
+        db.close().await;
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_covers_every_table() {
+        let db = setup_db().await;
+        let tables = sqlx::query_scalar!(
+            r#"SELECT name AS "name!" FROM sqlite_master WHERE type = 'table' ORDER BY name"#
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        // See RESTORE_TABLES for why these are left out
+        let excluded = ["_sqlx_migrations", "users", "sessions", "share_links"];
+        let missing: Vec<_> = tables
+            .iter()
+            .filter(|table| {
+                !excluded.contains(&table.as_str())
+                    && !table.starts_with("wine_search")
+                    && !RESTORE_TABLES
+                        .iter()
+                        .any(|(delete, _)| *delete == format!("DELETE FROM {table}"))
+            })
+            .collect();
+        assert!(missing.is_empty(), "not restored: {missing:?}");
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_invalid_files() {
+        let db = setup_db().await;
+
+        let path = scratch_path("test-garbage");
+        std::fs::write(&path, b"definitely not sqlite").unwrap();
+        let res = restore_from(&db, &path).await;
+        std::fs::remove_file(&path).unwrap();
+        let err = res.unwrap_err();
+        assert!(err.downcast_ref::<InvalidBackup>().is_some(), "{err}");
+
+        // A valid SQLite database without our migrations
+        let path = scratch_path("test-foreign");
+        let opts = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let other = sqlx::SqlitePool::connect_with(opts).await.unwrap();
+        sqlx::query("CREATE TABLE t (x)")
+            .execute(&other)
+            .await
+            .unwrap();
+        other.close().await;
+        let res = restore_from(&db, &path).await;
+        std::fs::remove_file(&path).unwrap();
+        let err = res.unwrap_err();
+        assert!(err.downcast_ref::<InvalidBackup>().is_some(), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_users_and_sessions() {
+        let db = setup_db().await;
+        assert!(!has_users(&db).await.unwrap());
+        let anna = add_first_user(&db, "anna", "hash-a")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            add_first_user(&db, "bert", "hash-b")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(anna.is_admin);
+        let bert = add_user(&db, "bert", "hash-b", false).await.unwrap();
+        assert!(!bert.is_admin);
+        assert!(add_user(&db, "Anna", "hash", false).await.is_err());
+        let (user, hash) = user_login(&db, "ANNA").await.unwrap().unwrap();
+        assert_eq!((user.user_id, hash.as_str()), (anna.user_id, "hash-a"));
+
+        let t = |s: &str| chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+        let now = t("2026-10-17 12:00:00");
+        add_session(&db, "s1", anna.user_id, now, t("2026-11-16 12:00:00"))
+            .await
+            .unwrap();
+        add_session(&db, "s2", anna.user_id, now, t("2026-11-16 12:00:00"))
+            .await
+            .unwrap();
+        add_session(&db, "s3", bert.user_id, now, t("2026-10-17 13:00:00"))
+            .await
+            .unwrap();
+        let (user, cellar_id) = session_user(&db, "s1", now).await.unwrap().unwrap();
+        assert_eq!((user.username.as_str(), cellar_id), ("anna", None));
+        set_session_cellar(&db, "s1", CELLAR).await.unwrap();
+        let (_, cellar_id) = session_user(&db, "s1", now).await.unwrap().unwrap();
+        assert_eq!(cellar_id, Some(CELLAR));
+        assert!(
+            session_user(&db, "s3", t("2026-10-17 13:00:00"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(session_user(&db, "nope", now).await.unwrap().is_none());
+
+        // Changing the password logs out the other sessions
+        set_password(&db, anna.user_id, "hash-a2", "s1")
+            .await
+            .unwrap();
+        assert!(session_user(&db, "s1", now).await.unwrap().is_some());
+        assert!(session_user(&db, "s2", now).await.unwrap().is_none());
+        assert_eq!(
+            user_password_hash(&db, anna.user_id).await.unwrap(),
+            "hash-a2"
+        );
+
+        delete_session(&db, "s1").await.unwrap();
+        assert!(session_user(&db, "s1", now).await.unwrap().is_none());
+        delete_user(&db, bert.user_id).await.unwrap();
+        assert!(delete_user(&db, bert.user_id).await.is_err());
+        assert_eq!(users(&db).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cellar_members() {
+        let db = setup_db().await;
+        let anna = add_first_user(&db, "anna", "hash").await.unwrap().unwrap();
+        let bert = add_user(&db, "bert", "hash", false).await.unwrap();
+        assert!(user_cellars(&db, bert.user_id).await.unwrap().is_empty());
+        let cellars = user_cellars(&db, anna.user_id).await.unwrap();
+        assert_eq!(cellars.len(), 1);
+        assert_eq!(cellars[0].role, CellarRole::Owner);
+
+        let summer = add_cellar(&db, "Summer house", bert.user_id).await.unwrap();
+        set_member(&db, summer.cellar_id, "ANNA", CellarRole::Viewer)
+            .await
+            .unwrap();
+        let names = |cellars: Vec<Cellar>| {
+            cellars
+                .into_iter()
+                .map(|c| (c.name, c.role))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(user_cellars(&db, anna.user_id).await.unwrap()),
+            [
+                ("Cellar".to_owned(), CellarRole::Owner),
+                ("Summer house".to_owned(), CellarRole::Viewer)
+            ]
+        );
+        let err = set_member(&db, CELLAR, "carl", CellarRole::Editor)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<AccountError>().is_some(), "{err}");
+
+        // A cellar always keeps an owner
+        let err = set_member(&db, summer.cellar_id, "bert", CellarRole::Editor)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<AccountError>().is_some(), "{err}");
+        assert!(
+            remove_member(&db, summer.cellar_id, bert.user_id)
+                .await
+                .is_err()
+        );
+        assert!(delete_user(&db, bert.user_id).await.is_err());
+        set_member(&db, summer.cellar_id, "anna", CellarRole::Owner)
+            .await
+            .unwrap();
+        remove_member(&db, summer.cellar_id, bert.user_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            cellar_members(&db, summer.cellar_id)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|m| m.username)
+                .collect::<Vec<_>>(),
+            ["anna"]
+        );
+        delete_user(&db, bert.user_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_share_links() {
+        let db = setup_db().await;
+        let anna = add_first_user(&db, "anna", "hash").await.unwrap().unwrap();
+        let other = add_cellar(&db, "Other", anna.user_id)
+            .await
+            .unwrap()
+            .cellar_id;
+        let now = chrono::Utc::now().naive_utc();
+        let link = add_share_link(&db, CELLAR, "t1", "Dinner guests", None)
+            .await
+            .unwrap();
+        add_share_link(
+            &db,
+            CELLAR,
+            "t2",
+            "Last week",
+            Some(now - chrono::Duration::hours(1)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(share_links(&db, CELLAR).await.unwrap().len(), 2);
+        assert!(share_links(&db, other).await.unwrap().is_empty());
+
+        let shared = shared_cellar(&db, "t1", now).await.unwrap().unwrap();
+        assert_eq!((shared.cellar_id, shared.name.as_str()), (CELLAR, "Cellar"));
+        // Expired and unknown links open nothing
+        assert!(shared_cellar(&db, "t2", now).await.unwrap().is_none());
+        assert!(shared_cellar(&db, "t3", now).await.unwrap().is_none());
+
+        // Links are revoked from their own cellar only
+        assert!(delete_share_link(&db, other, link.share_id).await.is_err());
+        delete_share_link(&db, CELLAR, link.share_id).await.unwrap();
+        assert!(shared_cellar(&db, "t1", now).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_trash() {
+        let db = setup_db().await;
+        let anna = add_first_user(&db, "anna", "hash").await.unwrap().unwrap();
+        let other = add_cellar(&db, "Other", anna.user_id)
+            .await
+            .unwrap()
+            .cellar_id;
+        let dt = chrono::Local::now().naive_local();
+        let mut barolo = fields("Barolo", 2016);
+        barolo.producer_id = Some(producer_id(&db, "Vietti").await.unwrap());
+        let wine = add_wine(&db, CELLAR, &tester(), &barolo).await.unwrap();
+        let kept = add_wine(&db, CELLAR, &tester(), &fields("Kept", 2020))
+            .await
+            .unwrap();
+        let cellar = add_location(&db, CELLAR, &tester(), "Cellar")
+            .await
+            .unwrap();
+        let rack = add_rack(&db, CELLAR, &tester(), cellar.location_id, "A", 1, 1)
+            .await
+            .unwrap();
+        let slots = [Slot {
+            rack_id: rack.rack_id,
+            row: 1,
+            col: 1,
+        }];
+        let buy = NewWineEvent {
+            slots: &slots,
+            ..NewWineEvent::new(EventKind::Purchase, 1, dt)
+        };
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &buy)
+            .await
+            .unwrap();
+        let not_found = |res: anyhow::Result<()>| {
+            let err = res.unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(sqlx::Error::RowNotFound)),
+                "{err}"
+            );
+        };
+
+        delete_wine(&db, CELLAR, &anna, wine.wine_id).await.unwrap();
+        let trashed = trash(&db, CELLAR).await.unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].name, "Barolo");
+        assert_eq!(trashed[0].producer.as_deref(), Some("Vietti"));
+        assert_eq!(trashed[0].deleted_by.as_deref(), Some("anna"));
+        // A trashed wine is out of sight and out of reach
+        let rows = wine_rows(&db, CELLAR, &WineFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(producers(&db, CELLAR).await.unwrap().is_empty());
+        assert!(
+            search_wines(&db, CELLAR, "Barolo", 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        not_found(get_wine(&db, CELLAR, wine.wine_id).await.map(|_| ()));
+        not_found(
+            delete_wine(&db, CELLAR, &anna, wine.wine_id)
+                .await
+                .map(|_| ()),
+        );
+        // and can't be purged or restored from another cellar, nor be purged before it's trashed
+        not_found(purge_wine(&db, other, &anna, wine.wine_id).await);
+        not_found(
+            restore_wine(&db, other, &anna, wine.wine_id)
+                .await
+                .map(|_| ()),
+        );
+        not_found(purge_wine(&db, CELLAR, &anna, kept.wine_id).await);
+        assert!(trash(&db, other).await.unwrap().is_empty());
+
+        let restored = restore_wine(&db, CELLAR, &anna, wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(restored.producer.as_deref(), Some("Vietti"));
+        assert_eq!(
+            wine_row(&db, CELLAR, wine.wine_id).await.unwrap().bottles,
+            1
+        );
+        assert_eq!(
+            placed_bottles(&db, CELLAR, Some(wine.wine_id), None)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(trash(&db, CELLAR).await.unwrap().is_empty());
+
+        // Its slot stays taken while it is in the trash, so restoring it loses nothing
+        let placed = placed_bottles(&db, CELLAR, None, None).await.unwrap();
+        delete_wine(&db, CELLAR, &anna, wine.wine_id).await.unwrap();
+        let buy = NewWineEvent {
+            slots: &slots,
+            ..NewWineEvent::new(EventKind::Purchase, 1, dt)
+        };
+        let err = add_wine_event(&db, CELLAR, &tester(), kept.wine_id, &buy)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<InventoryError>().unwrap(),
+            InventoryError::SlotInTrash {
+                slot: slots[0],
+                wine: "Barolo 2016".to_owned(),
+            }
+        );
+        restore_wine(&db, CELLAR, &anna, wine.wine_id)
+            .await
+            .unwrap();
+        let slots_of = |bottles: &[PlacedBottle]| {
+            bottles
+                .iter()
+                .map(|b| (b.wine_id, b.rack_id, b.row, b.col))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            slots_of(&placed_bottles(&db, CELLAR, None, None).await.unwrap()),
+            slots_of(&placed)
+        );
+        delete_wine(&db, CELLAR, &anna, wine.wine_id).await.unwrap();
+
+        // Only what was trashed before the cutoff is purged
+        assert_eq!(purge_trash(&db, dt).await.unwrap(), 0);
+        let later = chrono::Local::now().naive_local() + chrono::Duration::seconds(1);
+        assert_eq!(purge_trash(&db, later).await.unwrap(), 1);
+        assert!(trash(&db, CELLAR).await.unwrap().is_empty());
+        assert_eq!(rows_of(&db, "wine_inventory_events", wine.wine_id).await, 0);
+        // Purging frees its slot
+        add_wine_event(&db, CELLAR, &tester(), kept.wine_id, &buy)
+            .await
+            .unwrap();
+        not_found(
+            restore_wine(&db, CELLAR, &anna, wine.wine_id)
+                .await
+                .map(|_| ()),
+        );
+        // The name is free again
+        add_wine(&db, CELLAR, &tester(), &barolo).await.unwrap();
+
+        let filter = AuditFilter {
+            wine_id: Some(wine.wine_id),
+            entity: Some(AuditEntity::Wine),
+            ..Default::default()
+        };
+        let log = audit_log(&db, CELLAR, &filter, None, 10).await.unwrap();
+        let actions: Vec<_> = log
+            .iter()
+            .map(|e| (e.action, e.username.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (AuditAction::Purge, AUTO_PURGE),
+                (AuditAction::Delete, "anna"),
+                (AuditAction::Restore, "anna"),
+                (AuditAction::Delete, "anna"),
+                (AuditAction::Restore, "anna"),
+                (AuditAction::Delete, "anna"),
+                (AuditAction::Add, "tester"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let db = setup_db().await;
+        let anna = add_first_user(&db, "anna", "hash").await.unwrap().unwrap();
+        let other = add_cellar(&db, "Other", anna.user_id)
+            .await
+            .unwrap()
+            .cellar_id;
+        let dt = chrono::Local::now().naive_local();
+        let wine = add_wine(&db, CELLAR, &anna, &fields("Rioja", 2015))
+            .await
+            .unwrap();
+        let mut renamed = fields("Rioja Reserva", 2015);
+        renamed.drink_from = Some(2022);
+        update_wine(&db, CELLAR, &tester(), wine.wine_id, &renamed)
+            .await
+            .unwrap();
+        // Saving without changes records nothing
+        update_wine(&db, CELLAR, &tester(), wine.wine_id, &renamed)
+            .await
+            .unwrap();
+        set_wine_grapes(&db, CELLAR, &anna, wine.wine_id, &["Tempranillo"])
+            .await
+            .unwrap();
+        let pairing = add_food_pairing(&db, CELLAR, &anna, wine.wine_id, "lamb")
+            .await
+            .unwrap();
+        remove_food_pairing(&db, CELLAR, &anna, pairing.id, wine.wine_id)
+            .await
+            .unwrap();
+        add_wine_event(
+            &db,
+            CELLAR,
+            &anna,
+            wine.wine_id,
+            &NewWineEvent::new(EventKind::Purchase, 6, dt),
+        )
+        .await
+        .unwrap();
+        set_wine_comment(&db, CELLAR, &anna, wine.wine_id, Some("Oaky"), Some(dt))
+            .await
+            .unwrap();
+        set_wine_image(&db, CELLAR, &anna, wine.wine_id, b"jpeg")
+            .await
+            .unwrap();
+        add_wine(&db, other, &anna, &fields("Elsewhere", 2020))
+            .await
+            .unwrap();
+        delete_wine(&db, CELLAR, &anna, wine.wine_id).await.unwrap();
+
+        let all = AuditFilter::default();
+        let log = audit_log(&db, CELLAR, &all, None, 100).await.unwrap();
+        let changes: Vec<_> = log.iter().map(|e| (e.entity, e.action)).collect();
+        assert_eq!(
+            changes,
+            [
+                (AuditEntity::Wine, AuditAction::Delete),
+                (AuditEntity::Image, AuditAction::Add),
+                (AuditEntity::Comment, AuditAction::Add),
+                (AuditEntity::Event, AuditAction::Add),
+                (AuditEntity::Pairing, AuditAction::Delete),
+                (AuditEntity::Pairing, AuditAction::Add),
+                (AuditEntity::Grapes, AuditAction::Add),
+                (AuditEntity::Wine, AuditAction::Update),
+                (AuditEntity::Wine, AuditAction::Add),
+            ]
+        );
+        // The deleted wine's history keeps its name and what it was like
+        assert!(
+            log.iter()
+                .all(|e| matches!(e.wine.as_deref(), Some("Rioja Reserva 2015" | "Rioja 2015")))
+        );
+        assert_eq!(log[0].before.as_ref().unwrap()["grapes"][0], "Tempranillo");
+        let update = &log[7];
+        assert_eq!(update.username, "tester");
+        assert_eq!(update.before.as_ref().unwrap()["name"], "Rioja");
+        assert_eq!(update.after.as_ref().unwrap()["drink_from"], 2022);
 
-/// Searches wines by food pairing using a case-insensitive substring match.
-/// Special LIKE characters (`%`, `_`, `\`) in `q` are escaped so they are
-/// treated as literals.
-#[tracing::instrument(skip(db))]
-pub(crate) async fn search_wines_by_food(
-    db: &sqlx::SqlitePool,
-    q: &str,
-) -> anyhow::Result<Vec<WineWithPairings>> {
-    let escaped = q
-        .replace('\\', "\\\\")
-        .replace('%', "\\%")
-        .replace('_', "\\_");
-    let pattern = format!("%{}%", escaped);
+        let filter = AuditFilter {
+            entity: Some(AuditEntity::Pairing),
+            username: Some("ANNA".to_owned()),
+            ..Default::default()
+        };
+        let pairings = audit_log(&db, CELLAR, &filter, None, 100).await.unwrap();
+        assert_eq!(pairings.len(), 2);
+        assert_eq!(pairings[1].after.as_ref().unwrap()["food"], "lamb");
+        let wine_filter = AuditFilter {
+            wine_id: Some(wine.wine_id),
+            until: Some(dt.date() - chrono::Duration::days(1)),
+            ..Default::default()
+        };
+        assert!(
+            audit_log(&db, CELLAR, &wine_filter, None, 100)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        // Pages continue below the last entry shown
+        let older = audit_log(&db, CELLAR, &all, Some(log[6].audit_id), 100)
+            .await
+            .unwrap();
+        assert_eq!(older.len(), 2);
+        assert_eq!(older[0].audit_id, log[7].audit_id);
 
-    let wines = sqlx::query!(
-        r#"SELECT DISTINCT w.wine_id, w.name, w.year
-           FROM wines w
-           JOIN wine_food_pairings fp ON fp.wine_id = w.wine_id
-           WHERE fp.food LIKE $1 ESCAPE '\'
-           ORDER BY w.name, w.year"#,
-        pattern
-    )
-    .fetch_all(db)
-    .await?;
+        assert_eq!(
+            audit_usernames(&db, CELLAR).await.unwrap(),
+            ["anna", "tester"]
+        );
+        let theirs = audit_log(&db, other, &all, None, 100).await.unwrap();
+        assert_eq!(theirs.len(), 1);
+        assert_eq!(theirs[0].wine.as_deref(), Some("Elsewhere 2020"));
+    }
 
-    let mut result = Vec::new();
-    for wine in wines {
-        let pairings = sqlx::query_scalar!(
-            "SELECT food FROM wine_food_pairings WHERE wine_id = $1 ORDER BY id",
-            wine.wine_id
+    #[tokio::test]
+    async fn test_audit_tastings_bottles_and_storage() {
+        let db = setup_db().await;
+        let dt = chrono::Local::now().naive_local();
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Rioja", 2015))
+            .await
+            .unwrap();
+        let location = add_location(&db, CELLAR, &tester(), "Cellar")
+            .await
+            .unwrap();
+        let rack = add_rack(&db, CELLAR, &tester(), location.location_id, "A", 2, 2)
+            .await
+            .unwrap();
+        let slot = |row, col| Slot {
+            rack_id: rack.rack_id,
+            row,
+            col,
+        };
+        let buy = NewWineEvent {
+            slots: &[slot(1, 1)],
+            ..NewWineEvent::new(EventKind::Purchase, 3, dt)
+        };
+        add_wine_event(&db, CELLAR, &tester(), wine.wine_id, &buy)
+            .await
+            .unwrap();
+        place_bottles(&db, CELLAR, &tester(), wine.wine_id, &[slot(1, 2)])
+            .await
+            .unwrap();
+        move_bottle(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            slot(1, 2),
+            slot(2, 2),
+            dt,
         )
-        .fetch_all(db)
-        .await?;
-        result.push(WineWithPairings {
-            wine_id: wine.wine_id.expect("wine_id is NOT NULL"),
-            name: wine.name,
-            year: wine.year,
-            matched_pairings: pairings,
-        });
+        .await
+        .unwrap();
+        unplace_bottle(&db, CELLAR, &tester(), wine.wine_id, slot(1, 1))
+            .await
+            .unwrap();
+        let tasting = NewTasting {
+            tasted_on: dt.date(),
+            score: 91,
+            scale: 100,
+            note: Some("Ripe"),
+            occasion: None,
+            sheet: None,
+        };
+        let tasting = add_tasting(&db, CELLAR, &tester(), wine.wine_id, &tasting)
+            .await
+            .unwrap();
+        delete_tasting(&db, CELLAR, &tester(), wine.wine_id, tasting.tasting_id)
+            .await
+            .unwrap();
+
+        let log = audit_log(&db, CELLAR, &AuditFilter::default(), None, 100)
+            .await
+            .unwrap();
+        let changes: Vec<_> = log.iter().map(|e| (e.entity, e.action)).collect();
+        assert_eq!(
+            changes,
+            [
+                (AuditEntity::Tasting, AuditAction::Delete),
+                (AuditEntity::Tasting, AuditAction::Add),
+                (AuditEntity::Bottle, AuditAction::Delete),
+                (AuditEntity::Bottle, AuditAction::Update),
+                (AuditEntity::Bottle, AuditAction::Add),
+                (AuditEntity::Event, AuditAction::Add),
+                (AuditEntity::Bottle, AuditAction::Add),
+                (AuditEntity::Rack, AuditAction::Add),
+                (AuditEntity::Location, AuditAction::Add),
+                (AuditEntity::Wine, AuditAction::Add),
+            ]
+        );
+        assert_eq!(log[0].before.as_ref().unwrap()["note"], "Ripe");
+        let moved = &log[3];
+        assert_eq!(moved.wine_id, Some(wine.wine_id));
+        assert_eq!(moved.before.as_ref().unwrap()["rack"], "Cellar › A");
+        assert_eq!(moved.after.as_ref().unwrap()["row"], 2);
+        // Storage isn't any wine's
+        assert_eq!((log[7].wine_id, log[7].wine.as_deref()), (None, None));
+        assert_eq!(log[7].after.as_ref().unwrap()["row_count"], 2);
+        assert_eq!(log[8].entity_id, location.location_id);
+
+        let wine_filter = AuditFilter {
+            wine_id: Some(wine.wine_id),
+            ..Default::default()
+        };
+        let wine_log = audit_log(&db, CELLAR, &wine_filter, None, 100)
+            .await
+            .unwrap();
+        assert_eq!(wine_log.len(), 8);
     }
-    Ok(result)
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn test_cellars_are_separate() {
+        let db = setup_db().await;
+        let anna = add_first_user(&db, "anna", "hash").await.unwrap().unwrap();
+        let other = add_cellar(&db, "Other", anna.user_id)
+            .await
+            .unwrap()
+            .cellar_id;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Barolo", 2016))
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "truffles")
+            .await
+            .unwrap();
+        // The same wine can be in both cellars
+        let theirs = add_wine(&db, other, &tester(), &fields("Barolo", 2016))
+            .await
+            .unwrap();
+        add_location(&db, CELLAR, &tester(), "Basement")
+            .await
+            .unwrap();
+        let location = add_location(&db, other, &tester(), "Basement")
+            .await
+            .unwrap();
+        let rack = add_rack(&db, other, &tester(), location.location_id, "A", 2, 2)
+            .await
+            .unwrap();
 
-    async fn setup_db() -> sqlx::SqlitePool {
-        let pool = sqlx::SqlitePool::connect("sqlite::memory:")
+        let dt = chrono::Utc::now().naive_utc();
+
+        let ids = |rows: Vec<WineRow>| rows.into_iter().map(|r| r.wine_id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(wine_rows(&db, CELLAR, &WineFilter::default())
+                .await
+                .unwrap()),
+            [wine.wine_id]
+        );
+        assert_eq!(
+            ids(wine_rows(&db, other, &WineFilter::default()).await.unwrap()),
+            [theirs.wine_id]
+        );
+        assert_eq!(
+            search_wines(&db, other, "truffles", 10)
+                .await
+                .unwrap()
+                .len(),
+            0
+        );
+        assert_eq!(locations(&db, other).await.unwrap().len(), 1);
+
+        // Reaching into the other cellar by id finds nothing
+        let not_found = |res: anyhow::Result<()>| {
+            let err = res.unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(sqlx::Error::RowNotFound)),
+                "{err}"
+            );
+        };
+        not_found(get_wine(&db, other, wine.wine_id).await.map(|_| ()));
+        not_found(
+            delete_wine(&db, other, &tester(), wine.wine_id)
+                .await
+                .map(|_| ()),
+        );
+        not_found(
+            add_food_pairing(&db, other, &tester(), wine.wine_id, "risotto")
+                .await
+                .map(|_| ()),
+        );
+        not_found(
+            add_wine_event(
+                &db,
+                other,
+                &tester(),
+                wine.wine_id,
+                &NewWineEvent::new(EventKind::Purchase, 3, dt),
+            )
+            .await,
+        );
+        not_found(get_rack(&db, CELLAR, rack.rack_id).await.map(|_| ()));
+        not_found(
+            add_rack(&db, CELLAR, &tester(), location.location_id, "B", 1, 1)
+                .await
+                .map(|_| ()),
+        );
+        // Nor can bottles go into the other cellar's racks
+        add_wine_event(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            &NewWineEvent::new(EventKind::Purchase, 1, dt),
+        )
+        .await
+        .unwrap();
+        let slot = Slot {
+            rack_id: rack.rack_id,
+            row: 1,
+            col: 1,
+        };
+        let err = place_bottles(&db, CELLAR, &tester(), wine.wine_id, &[slot])
             .await
-            .expect("connect to in-memory DB");
-        sqlx::migrate!().run(&pool).await.expect("run migrations");
-        pool
+            .unwrap_err();
+        assert!(err.downcast_ref::<InventoryError>().is_some(), "{err}");
     }
 
     #[tokio::test]
     async fn test_set_wine_comment() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Commented Wine", 2020).await.unwrap();
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Commented Wine", 2020))
+            .await
+            .unwrap();
         let now = chrono::NaiveDateTime::parse_from_str("2026-03-10 12:00:00", "%Y-%m-%d %H:%M:%S")
             .unwrap();
 
         // Set a comment
-        set_wine_comment(&db, wine.wine_id, Some("Great wine"), Some(now))
-            .await
-            .unwrap();
-        let w = get_wine(&db, wine.wine_id).await.unwrap();
+        set_wine_comment(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            Some("Great wine"),
+            Some(now),
+        )
+        .await
+        .unwrap();
+        let w = get_wine(&db, CELLAR, wine.wine_id).await.unwrap();
         assert_eq!(w.comment.as_deref(), Some("Great wine"));
         assert_eq!(w.comment_updated_at, Some(now));
     }
@@ -388,33 +6000,254 @@ mod tests {
     #[tokio::test]
     async fn test_clear_wine_comment() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Clear Wine", 2021).await.unwrap();
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Clear Wine", 2021))
+            .await
+            .unwrap();
         let now = chrono::NaiveDateTime::parse_from_str("2026-03-10 12:00:00", "%Y-%m-%d %H:%M:%S")
             .unwrap();
 
-        set_wine_comment(&db, wine.wine_id, Some("Temporary note"), Some(now))
-            .await
-            .unwrap();
+        set_wine_comment(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            Some("Temporary note"),
+            Some(now),
+        )
+        .await
+        .unwrap();
         // Clear the comment
-        set_wine_comment(&db, wine.wine_id, None, None)
+        set_wine_comment(&db, CELLAR, &tester(), wine.wine_id, None, None)
             .await
             .unwrap();
-        let w = get_wine(&db, wine.wine_id).await.unwrap();
+        let w = get_wine(&db, CELLAR, wine.wine_id).await.unwrap();
         assert!(w.comment.is_none());
         assert!(w.comment_updated_at.is_none());
     }
 
+    #[tokio::test]
+    async fn test_update_wine() {
+        let db = setup_db().await;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Typo Wine", 2019))
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "duck")
+            .await
+            .unwrap();
+
+        let updated = update_wine(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            &fields("Fixed Wine", 2020),
+        )
+        .await
+        .unwrap();
+        assert_eq!(updated.wine_id, wine.wine_id);
+        assert_eq!(updated.name, "Fixed Wine");
+        assert_eq!(updated.year, 2020);
+
+        // Related data stays attached to the wine
+        let pairings = get_wine_food_pairings(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(pairings.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_wine_duplicate_rejected() {
+        let db = setup_db().await;
+        add_wine(&db, CELLAR, &tester(), &fields("Existing", 2020))
+            .await
+            .unwrap();
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Other", 2020))
+            .await
+            .unwrap();
+
+        let err = update_wine(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            &fields("Existing", 2020),
+        )
+        .await;
+        assert!(err.is_err(), "duplicate name and year must be rejected");
+    }
+
+    #[tokio::test]
+    async fn test_wine_style() {
+        let db = setup_db().await;
+        let red = WineFields {
+            style: Some(WineStyle::Red),
+            ..fields("Red", 2020)
+        };
+        let red = add_wine(&db, CELLAR, &tester(), &red).await.unwrap();
+        assert_eq!(red.style, Some(WineStyle::Red));
+        let unknown = add_wine(&db, CELLAR, &tester(), &fields("Unknown", 2020))
+            .await
+            .unwrap();
+        assert_eq!(unknown.style, None);
+
+        let bubbles = WineFields {
+            style: Some(WineStyle::Sparkling),
+            ..fields("Bubbles", 2020)
+        };
+        let updated = update_wine(&db, CELLAR, &tester(), unknown.wine_id, &bubbles)
+            .await
+            .unwrap();
+        assert_eq!(updated.style, Some(WineStyle::Sparkling));
+
+        let style_filter = |style| WineFilter {
+            style: Some(style),
+            ..Default::default()
+        };
+        let rows = wine_rows(&db, CELLAR, &style_filter(WineStyle::Sparkling))
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].name, "Bubbles");
+        let rows = wine_rows(&db, CELLAR, &style_filter(WineStyle::Rose))
+            .await
+            .unwrap();
+        assert!(rows.is_empty());
+        assert_eq!(
+            wine_rows(&db, CELLAR, &WineFilter::default())
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    /// How many rows of `table` are about the wine, whichever cellar it was in.
+    async fn rows_of(db: &sqlx::SqlitePool, table: &str, wine_id: i64) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table} WHERE wine_id = ?"))
+            .bind(wine_id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    async fn region_named(db: &sqlx::SqlitePool, name: &str) -> Region {
+        regions(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|r| r.name == name)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_producer_and_region() {
+        let db = setup_db().await;
+        let producer = producer_id(&db, "Château Latour").await.unwrap();
+        assert_eq!(producer_id(&db, "château latour").await.unwrap(), producer);
+        // Only the producers of the cellar's wines are listed
+        assert!(producers(&db, CELLAR).await.unwrap().is_empty());
+
+        let pauillac = region_named(&db, "Pauillac").await;
+        assert_eq!(pauillac.kind, RegionKind::Appellation);
+        let latour = WineFields {
+            producer_id: Some(producer),
+            region_id: Some(pauillac.region_id),
+            ..fields("Grand Vin", 2010)
+        };
+        let wine = add_wine(&db, CELLAR, &tester(), &latour).await.unwrap();
+        assert_eq!(wine.producer.as_deref(), Some("Château Latour"));
+        assert_eq!(producers(&db, CELLAR).await.unwrap().len(), 1);
+        let rhone = WineFields {
+            region_id: Some(region_named(&db, "Rhône").await.region_id),
+            ..fields("Côtes du Rhône", 2020)
+        };
+        add_wine(&db, CELLAR, &tester(), &rhone).await.unwrap();
+        add_wine(&db, CELLAR, &tester(), &fields("Anonymous", 2020))
+            .await
+            .unwrap();
+
+        let row = wine_row(&db, CELLAR, wine.wine_id).await.unwrap();
+        assert_eq!(row.producer.as_deref(), Some("Château Latour"));
+        assert_eq!(row.origin.as_deref(), Some("Pauillac, Bordeaux, France"));
+
+        // A region filter also matches the regions below it
+        let in_region = |region_id| WineFilter {
+            region_id: Some(region_id),
+            ..Default::default()
+        };
+        let names = |rows: Vec<WineRow>| rows.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        let france = region_named(&db, "France").await.region_id;
+        let mut french = names(wine_rows(&db, CELLAR, &in_region(france)).await.unwrap());
+        french.sort();
+        assert_eq!(french, ["Côtes du Rhône", "Grand Vin"]);
+        let bordeaux = region_named(&db, "Bordeaux").await.region_id;
+        assert_eq!(
+            names(wine_rows(&db, CELLAR, &in_region(bordeaux)).await.unwrap()),
+            ["Grand Vin"]
+        );
+        let by_producer = WineFilter {
+            producer_id: Some(producer),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(wine_rows(&db, CELLAR, &by_producer).await.unwrap()),
+            ["Grand Vin"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_regions_in_hierarchy_order() {
+        let db = setup_db().await;
+        let all = regions(&db).await.unwrap();
+        for (i, region) in all.iter().enumerate() {
+            if let Some(parent_id) = region.parent_id {
+                let parent = all.iter().position(|r| r.region_id == parent_id).unwrap();
+                assert!(parent < i, "{} listed before its parent", region.name);
+            }
+        }
+
+        let sweden = region_named(&db, "Sweden").await;
+        let skane = region_named(&db, "Skåne").await;
+        let added = add_region(
+            &db,
+            Some(skane.region_id),
+            RegionKind::Appellation,
+            "Österlen",
+        )
+        .await
+        .unwrap();
+        assert_eq!(added.parent_id, Some(skane.region_id));
+        assert!(
+            add_region(&db, Some(sweden.region_id), RegionKind::Region, "Skåne")
+                .await
+                .is_err(),
+            "duplicate region names under one parent must be rejected"
+        );
+    }
+
+    #[test]
+    fn test_wine_style_roundtrip() {
+        for style in WineStyle::ALL {
+            assert_eq!(style.as_str().parse::<WineStyle>().unwrap(), style);
+        }
+        assert!("orange".parse::<WineStyle>().is_err());
+    }
+
     #[tokio::test]
     async fn test_add_and_get_food_pairing() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Test Wine", 2020).await.unwrap();
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Test Wine", 2020))
+            .await
+            .unwrap();
 
-        let pairing = add_food_pairing(&db, wine.wine_id, "grilled salmon")
+        let pairing = add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "grilled salmon")
             .await
             .unwrap();
         assert_eq!(pairing.food, "grilled salmon");
 
-        let pairings = get_wine_food_pairings(&db, wine.wine_id).await.unwrap();
+        let pairings = get_wine_food_pairings(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
         assert_eq!(pairings.len(), 1);
         assert_eq!(pairings[0].food, "grilled salmon");
     }
@@ -422,54 +6255,237 @@ mod tests {
     #[tokio::test]
     async fn test_remove_food_pairing() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Test Wine", 2020).await.unwrap();
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Test Wine", 2020))
+            .await
+            .unwrap();
+
+        let other = add_wine(&db, CELLAR, &tester(), &fields("Other Wine", 2020))
+            .await
+            .unwrap();
+        let not_found = |res: anyhow::Result<()>| {
+            let err = res.unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(sqlx::Error::RowNotFound)),
+                "{err}"
+            );
+        };
 
-        let pairing = add_food_pairing(&db, wine.wine_id, "aged cheddar")
+        let pairing = add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "aged cheddar")
             .await
             .unwrap();
-        remove_food_pairing(&db, pairing.id, wine.wine_id)
+        // Not through another wine
+        not_found(remove_food_pairing(&db, CELLAR, &tester(), pairing.id, other.wine_id).await);
+        remove_food_pairing(&db, CELLAR, &tester(), pairing.id, wine.wine_id)
             .await
             .unwrap();
+        not_found(remove_food_pairing(&db, CELLAR, &tester(), pairing.id, wine.wine_id).await);
 
-        let pairings = get_wine_food_pairings(&db, wine.wine_id).await.unwrap();
+        let pairings = get_wine_food_pairings(&db, CELLAR, wine.wine_id)
+            .await
+            .unwrap();
         assert!(pairings.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_update_food_pairing() {
+        let db = setup_db().await;
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Test Wine", 2020))
+            .await
+            .unwrap();
+        let other = add_wine(&db, CELLAR, &tester(), &fields("Other Wine", 2020))
+            .await
+            .unwrap();
+
+        let pairing = add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "salmon")
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "duck")
+            .await
+            .unwrap();
+        let updated = update_food_pairing(
+            &db,
+            CELLAR,
+            &tester(),
+            wine.wine_id,
+            pairing.id,
+            "smoked salmon",
+        )
+        .await
+        .unwrap();
+        assert_eq!(updated.id, pairing.id);
+        let got = get_food_pairing(&db, CELLAR, wine.wine_id, pairing.id)
+            .await
+            .unwrap();
+        assert_eq!(got.food, "smoked salmon");
+
+        let dup =
+            update_food_pairing(&db, CELLAR, &tester(), wine.wine_id, pairing.id, "Duck").await;
+        assert!(dup.is_err(), "duplicate pairing must be rejected");
+        // Scoped to the wine
+        let err = get_food_pairing(&db, CELLAR, other.wine_id, pairing.id)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(sqlx::Error::RowNotFound)));
+        let err = update_food_pairing(&db, CELLAR, &tester(), other.wine_id, pairing.id, "beef")
+            .await
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(sqlx::Error::RowNotFound)));
+    }
+
     #[tokio::test]
     async fn test_duplicate_pairing_rejected() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Test Wine", 2020).await.unwrap();
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Test Wine", 2020))
+            .await
+            .unwrap();
 
-        add_food_pairing(&db, wine.wine_id, "salmon").await.unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "salmon")
+            .await
+            .unwrap();
         // Same pairing, different case — should fail
-        let err = add_food_pairing(&db, wine.wine_id, "Salmon").await;
+        let err = add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "Salmon").await;
         assert!(err.is_err(), "duplicate pairing must be rejected");
     }
 
     #[tokio::test]
     async fn test_cascade_delete_removes_pairings() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Test Wine", 2020).await.unwrap();
-        add_food_pairing(&db, wine.wine_id, "lamb chops")
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Test Wine", 2020))
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "lamb chops")
             .await
             .unwrap();
 
-        delete_wine(&db, wine.wine_id).await.unwrap();
+        delete_wine(&db, CELLAR, &tester(), wine.wine_id)
+            .await
+            .unwrap();
 
-        // The get_wine call should now fail, and pairings should be gone
-        let pairings = get_wine_food_pairings(&db, wine.wine_id).await.unwrap();
-        assert!(pairings.is_empty());
+        // The wine is gone, and its pairings with it once it is purged
+        assert!(
+            get_wine_food_pairings(&db, CELLAR, wine.wine_id)
+                .await
+                .is_err()
+        );
+        assert_eq!(rows_of(&db, "wine_food_pairings", wine.wine_id).await, 1);
+        purge_wine(&db, CELLAR, &tester(), wine.wine_id)
+            .await
+            .unwrap();
+        assert_eq!(rows_of(&db, "wine_food_pairings", wine.wine_id).await, 0);
+    }
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(fts_query("- *"), None);
+        assert_eq!(
+            fts_query("barolo NOT"),
+            Some(r#""barolo"* "NOT"*"#.to_owned())
+        );
+        assert_eq!(fts_query(r#"5"x"#), Some(r#""5""x"*"#.to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_search_wines() {
+        let db = setup_db().await;
+        let cotes = add_wine(&db, CELLAR, &tester(), &fields("Côtes du Rhône", 2019))
+            .await
+            .unwrap();
+        let barolo = add_wine(&db, CELLAR, &tester(), &fields("Barolo", 2016))
+            .await
+            .unwrap();
+        set_wine_grapes(&db, CELLAR, &tester(), cotes.wine_id, &["Syrah/shiraz"])
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), barolo.wine_id, "Truffle risotto")
+            .await
+            .unwrap();
+        set_wine_comment(
+            &db,
+            CELLAR,
+            &tester(),
+            cotes.wine_id,
+            Some("Goes with a Barolo glass"),
+            None,
+        )
+        .await
+        .unwrap();
+        let ids = |hits: Vec<SearchHit>| hits.iter().map(|h| h.wine_id).collect::<Vec<_>>();
+
+        // Accents don't matter, and words match as prefixes
+        let hits = search_wines(&db, CELLAR, "cotes", 10).await.unwrap();
+        assert_eq!(ids(hits), [cotes.wine_id]);
+        assert_eq!(
+            ids(search_wines(&db, CELLAR, "syr", 10).await.unwrap()),
+            [cotes.wine_id]
+        );
+        let hits = search_wines(&db, CELLAR, "risotto", 10).await.unwrap();
+        assert_eq!(hits[0].snippet, "Truffle \u{2}risotto\u{3}");
+
+        // A match in the name ranks above one in a note
+        let hits = search_wines(&db, CELLAR, "barolo", 10).await.unwrap();
+        assert_eq!(hits[0].name, "\u{2}Barolo\u{3}");
+        assert_eq!(ids(hits), [barolo.wine_id, cotes.wine_id]);
+
+        // Tastings are kept in sync by the triggers
+        let tasting = add_tasting(
+            &db,
+            CELLAR,
+            &tester(),
+            barolo.wine_id,
+            &NewTasting {
+                tasted_on: chrono::NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+                score: 95,
+                scale: 100,
+                note: Some("Tar and roses"),
+                occasion: None,
+                sheet: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            ids(search_wines(&db, CELLAR, "tar roses", 10).await.unwrap()),
+            [barolo.wine_id]
+        );
+        delete_tasting(&db, CELLAR, &tester(), barolo.wine_id, tasting.tasting_id)
+            .await
+            .unwrap();
+        assert!(
+            search_wines(&db, CELLAR, "roses", 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        delete_wine(&db, CELLAR, &tester(), barolo.wine_id)
+            .await
+            .unwrap();
+        assert!(
+            search_wines(&db, CELLAR, "truffle", 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            search_wines(&db, CELLAR, "\"", 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
     }
 
     #[tokio::test]
     async fn test_search_wines_by_food_match() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Salmon Wine", 2021).await.unwrap();
-        add_food_pairing(&db, wine.wine_id, "grilled salmon")
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Salmon Wine", 2021))
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "grilled salmon")
             .await
             .unwrap();
 
-        let results = search_wines_by_food(&db, "salmon").await.unwrap();
+        let results = search_wines_by_food(&db, CELLAR, "salmon").await.unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].wine_id, wine.wine_id);
     }
@@ -477,35 +6493,45 @@ mod tests {
     #[tokio::test]
     async fn test_search_wines_by_food_no_match() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Some Wine", 2021).await.unwrap();
-        add_food_pairing(&db, wine.wine_id, "grilled salmon")
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Some Wine", 2021))
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "grilled salmon")
             .await
             .unwrap();
 
-        let results = search_wines_by_food(&db, "xyz_no_match").await.unwrap();
+        let results = search_wines_by_food(&db, CELLAR, "xyz_no_match")
+            .await
+            .unwrap();
         assert!(results.is_empty());
     }
 
     #[tokio::test]
     async fn test_search_partial_match() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Some Wine", 2021).await.unwrap();
-        add_food_pairing(&db, wine.wine_id, "grilled salmon")
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Some Wine", 2021))
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "grilled salmon")
             .await
             .unwrap();
 
-        let results = search_wines_by_food(&db, "sal").await.unwrap();
+        let results = search_wines_by_food(&db, CELLAR, "sal").await.unwrap();
         assert_eq!(results.len(), 1);
     }
 
     #[tokio::test]
     async fn test_search_special_chars_treated_as_literal() {
         let db = setup_db().await;
-        let wine = add_wine(&db, "Some Wine", 2021).await.unwrap();
-        add_food_pairing(&db, wine.wine_id, "steak").await.unwrap();
+        let wine = add_wine(&db, CELLAR, &tester(), &fields("Some Wine", 2021))
+            .await
+            .unwrap();
+        add_food_pairing(&db, CELLAR, &tester(), wine.wine_id, "steak")
+            .await
+            .unwrap();
 
         // '%' should not match everything — should match nothing since no pairing contains "%"
-        let results = search_wines_by_food(&db, "%").await.unwrap();
+        let results = search_wines_by_food(&db, CELLAR, "%").await.unwrap();
         assert!(results.is_empty());
     }
 }
@@ -1,4 +1,6 @@
+mod backup;
 mod db;
+mod trash;
 mod web;
 
 #[tokio::main]
@@ -13,10 +15,31 @@ async fn main() -> anyhow::Result<()> {
     let db_pool = db::connect().await?;
 
     tracing::info!("Migrate DB");
-    sqlx::migrate!().run(&db_pool).await?;
+    db::MIGRATOR.run(&db_pool).await?;
+
+    let backup_config = backup::Config::from_env()?;
+    let backup_status = backup::StatusHandle::new(
+        backup::Status {
+            config: backup_config.clone(),
+            ..Default::default()
+        }
+        .into(),
+    );
+    if let Some(config) = backup_config {
+        tracing::info!("Scheduled backups to {}", config.dir.display());
+        tokio::spawn(backup::run(db_pool.clone(), config, backup_status.clone()));
+    }
+
+    let trash_days = trash::days_from_env()?;
+    if let Some(days) = trash_days {
+        tracing::info!("Purging wines after {days} day(s) in the trash");
+        tokio::spawn(trash::run(db_pool.clone(), days));
+    }
+
+    let score_scale = web::ScoreScale::from_env()?;
 
     tokio::spawn(async move {
-        if let Err(e) = web::run(db_pool).await {
+        if let Err(e) = web::run(db_pool, backup_status, score_scale, trash_days).await {
             tracing::error!("{e}");
         }
     });
@@ -0,0 +1,34 @@
+//! Automatic purging of the trash. Wines are deleted for good once they have been in the
+//! trash for `WINE_TRASH_DAYS` (30 by default); 0 keeps them until they are purged by hand.
+
+use crate::db;
+
+/// How often the trash is checked for wines that have been there long enough.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// How many days wines stay in the trash, `None` if they stay until purged by hand.
+pub(crate) fn days_from_env() -> anyhow::Result<Option<u32>> {
+    let days: u32 = crate::backup::env_or("WINE_TRASH_DAYS", 30)?;
+    Ok((days > 0).then_some(days))
+}
+
+/// When a wine moved to the trash at `deleted_at` is purged.
+pub(crate) fn purge_at(deleted_at: chrono::NaiveDateTime, days: u32) -> chrono::NaiveDateTime {
+    deleted_at + chrono::Duration::days(days.into())
+}
+
+/// Runs forever, purging the wines that have been in the trash for longer than `days` right
+/// away and then every [`CHECK_INTERVAL`].
+pub(crate) async fn run(db: sqlx::SqlitePool, days: u32) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let cutoff = chrono::Local::now().naive_local() - chrono::Duration::days(days.into());
+        match db::purge_trash(&db, cutoff).await {
+            Ok(0) => {}
+            Ok(purged) => tracing::info!("Purged {purged} wine(s) from the trash"),
+            Err(e) => tracing::error!("Purging the trash failed: {e:#}"),
+        }
+    }
+}
@@ -1,25 +1,54 @@
+mod api;
+mod auth;
+mod drinking;
 mod error;
 mod handlers;
+mod history;
+mod import;
 mod markup;
+mod scores;
+mod shared;
+mod table_query;
 
 use axum::response::IntoResponse;
 use error::*;
+pub(crate) use scores::ScoreScale;
 
 const FAVICON: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/favicon.svg"));
 
 pub(crate) const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
 
+/// Database backups include every label image, so they get a far larger limit than images.
+pub(crate) const MAX_RESTORE_BYTES: usize = 512 * 1024 * 1024;
+
 struct StateInner {
     db: sqlx::SqlitePool,
+    backups: crate::backup::StatusHandle,
+    /// What new tastings are scored out of.
+    score_scale: ScoreScale,
+    /// How long wines stay in the trash, if they are purged automatically.
+    trash_days: Option<u32>,
 }
 
 type State = std::sync::Arc<StateInner>;
 
 type MDResult = std::result::Result<maud::Markup, AppError>;
 
-pub async fn run(db: sqlx::SqlitePool) -> anyhow::Result<()> {
-    let state = StateInner { db }.into();
-    let router = axum::Router::new()
+pub async fn run(
+    db: sqlx::SqlitePool,
+    backups: crate::backup::StatusHandle,
+    score_scale: ScoreScale,
+    trash_days: Option<u32>,
+) -> anyhow::Result<()> {
+    let state: State = StateInner {
+        db,
+        backups,
+        score_scale,
+        trash_days,
+    }
+    .into();
+    // Reachable without logging in, share links included
+    let public = axum::Router::new()
         .route(
             "/favicon.ico",
             axum::routing::get(|| async { axum::response::Redirect::permanent("/favicon.svg") }),
@@ -34,7 +63,52 @@ pub async fn run(db: sqlx::SqlitePool) -> anyhow::Result<()> {
                     .into_response()
             }),
         )
+        .route(
+            "/login",
+            axum::routing::get(markup::login_page).post(handlers::login),
+        )
+        .route("/setup", axum::routing::post(handlers::setup))
+        .merge(shared::router());
+    // Accounts, cellars and the admin page, which check roles themselves
+    let accounts = axum::Router::new()
         .route("/", axum::routing::get(markup::index))
+        .route("/logout", axum::routing::post(handlers::logout))
+        .route("/users", axum::routing::post(handlers::add_user))
+        .route(
+            "/users/{user_id}",
+            axum::routing::delete(handlers::delete_user),
+        )
+        .route(
+            "/account/password",
+            axum::routing::post(handlers::change_password),
+        )
+        .route("/admin", axum::routing::get(markup::admin_page))
+        .route("/backup", axum::routing::get(handlers::download_backup))
+        .route(
+            "/restore",
+            axum::routing::post(handlers::restore_backup)
+                .layer(axum::extract::DefaultBodyLimit::max(MAX_RESTORE_BYTES)),
+        )
+        .route("/cellars", axum::routing::post(handlers::add_cellar))
+        .route(
+            "/cellars/switch",
+            axum::routing::post(handlers::switch_cellar),
+        )
+        .route("/cellar/members", axum::routing::post(handlers::set_member))
+        .route(
+            "/cellar/members/{user_id}",
+            axum::routing::delete(handlers::remove_member),
+        )
+        .route(
+            "/cellar/shares",
+            axum::routing::post(handlers::add_share_link),
+        )
+        .route(
+            "/cellar/shares/{share_id}",
+            axum::routing::delete(handlers::delete_share_link),
+        );
+    // The active cellar's data, which viewers may only look at
+    let cellar = axum::Router::new()
         .route("/add-wine", axum::routing::post(handlers::add_wine))
         .route(
             "/wines/{wine_id}/upload-image",
@@ -78,6 +152,72 @@ pub async fn run(db: sqlx::SqlitePool) -> anyhow::Result<()> {
             "/wines/{wine_id}/buy",
             axum::routing::post(handlers::buy_wine),
         )
+        .route(
+            "/wines/{wine_id}/event",
+            axum::routing::get(markup::record_event).post(handlers::record_event),
+        )
+        .route(
+            "/wines/{wine_id}/place",
+            axum::routing::get(markup::place_bottles).post(handlers::place_bottles),
+        )
+        .route(
+            "/wines/{wine_id}/bottles/{slot}",
+            axum::routing::delete(handlers::unplace_bottle),
+        )
+        .route(
+            "/wines/{wine_id}/bottles/{slot}/move",
+            axum::routing::get(markup::move_bottle).post(handlers::move_bottle),
+        )
+        .route(
+            "/wines/{wine_id}/tastings",
+            axum::routing::post(handlers::add_tasting),
+        )
+        .route(
+            "/wines/{wine_id}/tastings/{tasting_id}",
+            axum::routing::delete(handlers::delete_tasting),
+        )
+        .route(
+            "/locations",
+            axum::routing::get(markup::locations).post(handlers::add_location),
+        )
+        .route("/trash", axum::routing::get(markup::trash))
+        .route(
+            "/trash/{wine_id}",
+            axum::routing::delete(handlers::purge_wine),
+        )
+        .route(
+            "/trash/{wine_id}/restore",
+            axum::routing::post(handlers::restore_wine),
+        )
+        .route(
+            "/wines/{wine_id}/restore",
+            axum::routing::post(handlers::undo_delete_wine),
+        )
+        .route("/history", axum::routing::get(markup::history))
+        .route(
+            "/history/entries",
+            axum::routing::get(markup::history_entries),
+        )
+        .route(
+            "/wines/{wine_id}/history",
+            axum::routing::get(markup::wine_history),
+        )
+        .route(
+            "/locations/{location_id}",
+            axum::routing::get(markup::location),
+        )
+        .route(
+            "/locations/{location_id}/racks",
+            axum::routing::post(handlers::add_rack),
+        )
+        .route(
+            "/wines/{wine_id}/events/{event_id}",
+            axum::routing::put(handlers::update_event).delete(handlers::delete_event),
+        )
+        .route(
+            "/wines/{wine_id}/events/{event_id}/edit",
+            axum::routing::get(markup::edit_event),
+        )
         .route(
             "/wines/{wine_id}/grapes",
             axum::routing::get(markup::edit_wine_grapes),
@@ -94,7 +234,22 @@ pub async fn run(db: sqlx::SqlitePool) -> anyhow::Result<()> {
             "/wines/{wine_id}",
             axum::routing::delete(handlers::delete_wine),
         )
+        .route(
+            "/wines/{wine_id}",
+            axum::routing::put(handlers::update_wine),
+        )
+        .route(
+            "/wines/{wine_id}/edit",
+            axum::routing::get(markup::edit_wine),
+        )
         .route("/wines", axum::routing::get(markup::wine_table))
+        .route("/export.csv", axum::routing::get(handlers::export_csv))
+        .route(
+            "/import",
+            axum::routing::get(markup::import_page)
+                .post(handlers::import_csv)
+                .layer(axum::extract::DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
         .route(
             "/wine-table-body",
             axum::routing::get(markup::wine_table_body),
@@ -107,6 +262,7 @@ pub async fn run(db: sqlx::SqlitePool) -> anyhow::Result<()> {
             "/wines/{wine_id}/pairings/{pairing_id}",
             axum::routing::delete(handlers::remove_food_pairing),
         )
+        .route("/search", axum::routing::get(handlers::search))
         .route(
             "/pairings/search",
             axum::routing::get(handlers::pairings_search),
@@ -115,6 +271,20 @@ pub async fn run(db: sqlx::SqlitePool) -> anyhow::Result<()> {
             "/pairings/search/results",
             axum::routing::get(handlers::pairings_search_results),
         )
+        .route(
+            "/drinking-window",
+            axum::routing::get(markup::drinking_window),
+        )
+        .route("/valuation", axum::routing::get(markup::valuation))
+        .nest("/api/v1", api::router())
+        .route_layer(axum::middleware::from_fn(auth::require_editor));
+    let router = accounts
+        .merge(cellar)
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_login,
+        ))
+        .merge(public)
         .with_state(state)
         .layer(axum_tracing_opentelemetry::middleware::OtelInResponseLayer)
         .layer(axum_tracing_opentelemetry::middleware::OtelAxumLayer::default());
@@ -0,0 +1,827 @@
+//! Versioned JSON API mounted under `/api/v1`. Mirrors what the htmx routes can do, reusing
+//! the same `db` functions and input validation, but speaks JSON in both directions.
+
+use axum::Json;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+
+use super::error::ApiError;
+use super::handlers::{
+    duplicate_wine_msg, inventory_error, resolve_producer, unique_violation,
+    validate_drinking_window, validate_food_pairing, validate_occasion, validate_price,
+    validate_rack_size, validate_region, validate_sheet, validate_storage_name, validate_vendor,
+    validate_wine_name,
+};
+use super::{State, auth};
+use crate::db;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// A JSON request body. Unlike [`axum::Json`], a body that is malformed, has the wrong
+/// content type or misses a field is answered with the API's JSON error.
+struct JsonBody<T>(T);
+
+impl<T, S> axum::extract::FromRequest<S> for JsonBody<T>
+where
+    T: serde::de::DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: axum::extract::Request, state: &S) -> ApiResult<Self> {
+        let Json(body) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(super::AppError::json_rejection)?;
+        Ok(Self(body))
+    }
+}
+
+pub(crate) fn router() -> axum::Router<State> {
+    axum::Router::new()
+        .route("/cellars", axum::routing::get(list_cellars))
+        .route("/wines", axum::routing::get(list_wines).post(create_wine))
+        .route(
+            "/wines/{wine_id}",
+            axum::routing::get(get_wine)
+                .put(update_wine)
+                .delete(delete_wine),
+        )
+        .route(
+            "/wines/{wine_id}/events",
+            axum::routing::get(list_events).post(create_event),
+        )
+        .route(
+            "/wines/{wine_id}/events/{event_id}",
+            axum::routing::put(update_event).delete(delete_event),
+        )
+        .route(
+            "/wines/{wine_id}/bottles",
+            axum::routing::get(list_wine_bottles),
+        )
+        .route(
+            "/wines/{wine_id}/moves",
+            axum::routing::get(list_moves).post(create_move),
+        )
+        .route(
+            "/wines/{wine_id}/tastings",
+            axum::routing::get(list_tastings).post(create_tasting),
+        )
+        .route(
+            "/wines/{wine_id}/tastings/{tasting_id}",
+            axum::routing::delete(delete_tasting),
+        )
+        .route(
+            "/locations",
+            axum::routing::get(list_locations).post(create_location),
+        )
+        .route(
+            "/locations/{location_id}/bottles",
+            axum::routing::get(list_location_bottles),
+        )
+        .route("/racks", axum::routing::get(list_racks).post(create_rack))
+        .route("/search", axum::routing::get(search))
+        .route("/aromas", axum::routing::get(list_aromas))
+        .route("/grapes", axum::routing::get(list_grapes))
+        .route("/producers", axum::routing::get(list_producers))
+        .route(
+            "/regions",
+            axum::routing::get(list_regions).post(create_region),
+        )
+        .route(
+            "/wines/{wine_id}/grapes",
+            axum::routing::get(get_wine_grapes).put(set_wine_grapes),
+        )
+        .route(
+            "/wines/{wine_id}/pairings",
+            axum::routing::get(list_pairings).post(create_pairing),
+        )
+        .route(
+            "/wines/{wine_id}/pairings/{pairing_id}",
+            axum::routing::get(get_pairing)
+                .put(update_pairing)
+                .delete(delete_pairing),
+        )
+        .route(
+            "/wines/{wine_id}/note",
+            axum::routing::get(get_note)
+                .put(set_note)
+                .delete(delete_note),
+        )
+}
+
+// ── Cellars ──────────────────────────────────────────────────────────────────
+
+/// The cellars the user has access to. Send the id of one in the `X-Cellar-Id` header to work
+/// in it; without the header requests go to the first.
+#[tracing::instrument(skip(state))]
+async fn list_cellars(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::Extension(user): axum::Extension<db::User>,
+) -> ApiResult<Json<Vec<db::Cellar>>> {
+    Ok(Json(db::user_cellars(&state.db, user.user_id).await?))
+}
+
+// ── Wines ────────────────────────────────────────────────────────────────────
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct WineBody {
+    name: String,
+    year: i64,
+    #[serde(default)]
+    style: Option<db::WineStyle>,
+    #[serde(default)]
+    producer: Option<String>,
+    #[serde(default)]
+    region_id: Option<i64>,
+    #[serde(default)]
+    drink_from: Option<i64>,
+    #[serde(default)]
+    drink_until: Option<i64>,
+}
+
+impl WineBody {
+    async fn fields(&self, db: &sqlx::SqlitePool) -> ApiResult<db::WineFields<'_>> {
+        validate_drinking_window(self.drink_from, self.drink_until)?;
+        Ok(db::WineFields {
+            name: validate_wine_name(&self.name)?,
+            year: self.year,
+            style: self.style,
+            producer_id: resolve_producer(db, self.producer.as_deref().unwrap_or("")).await?,
+            region_id: validate_region(db, self.region_id).await?,
+            drink_from: self.drink_from,
+            drink_until: self.drink_until,
+        })
+    }
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_wines(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> ApiResult<Json<Vec<db::Wine>>> {
+    Ok(Json(db::wines(&state.db, ctx.cellar_id()).await?))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn create_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    JsonBody(body): JsonBody<WineBody>,
+) -> ApiResult<impl IntoResponse> {
+    let fields = body.fields(&state.db).await?;
+    let wine = db::add_wine(&state.db, ctx.cellar_id(), &ctx.user, &fields)
+        .await
+        .map_err(|e| unique_violation(e, &duplicate_wine_msg(fields.name, fields.year)))?;
+    Ok((StatusCode::CREATED, Json(wine)))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn get_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<db::Wine>> {
+    Ok(Json(
+        db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?,
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn update_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    JsonBody(body): JsonBody<WineBody>,
+) -> ApiResult<Json<db::Wine>> {
+    // Fail with 404 rather than silently updating nothing
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let fields = body.fields(&state.db).await?;
+    let wine = db::update_wine(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &fields)
+        .await
+        .map_err(|e| unique_violation(e, &duplicate_wine_msg(fields.name, fields.year)))?;
+    Ok(Json(wine))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn delete_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<StatusCode> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    db::delete_wine(&state.db, ctx.cellar_id(), &ctx.user, wine_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ── Inventory events ─────────────────────────────────────────────────────────
+
+/// A new or edited inventory event. `bottles` is signed: positive when bottles come in,
+/// negative when they go out. Without a `kind` the event is a purchase or a consumption
+/// depending on the sign.
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct EventBody {
+    dt: chrono::NaiveDate,
+    #[serde(default)]
+    kind: Option<db::EventKind>,
+    bottles: i64,
+    /// Purchase price per bottle in minor units (cents), with its three letter `currency`.
+    #[serde(default)]
+    unit_price: Option<i64>,
+    #[serde(default)]
+    currency: String,
+    #[serde(default)]
+    vendor: String,
+    /// Slots to put the bottles in, or to take them from when they go out.
+    #[serde(default)]
+    slots: Vec<db::Slot>,
+}
+
+impl EventBody {
+    /// The event at `time` on the body's date.
+    fn event(&self, time: chrono::NaiveTime) -> Result<db::NewWineEvent<'_>, super::AppError> {
+        let kind = self.kind.unwrap_or(if self.bottles > 0 {
+            db::EventKind::Purchase
+        } else {
+            db::EventKind::Consumption
+        });
+        let dt = chrono::NaiveDateTime::new(self.dt, time);
+        Ok(db::NewWineEvent {
+            price: validate_price(self.unit_price, &self.currency)?,
+            vendor: validate_vendor(&self.vendor)?,
+            slots: &self.slots,
+            ..db::NewWineEvent::new(kind, self.bottles, dt)
+        })
+    }
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_events(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<Vec<db::WineInvEvent>>> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    Ok(Json(
+        db::wine_inventory_events(&state.db, ctx.cellar_id(), wine_id).await?,
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn create_event(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    JsonBody(body): JsonBody<EventBody>,
+) -> ApiResult<impl IntoResponse> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let event = body.event(chrono::Local::now().naive_local().time())?;
+    db::add_wine_event(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &event)
+        .await
+        .map_err(inventory_error)?;
+    let events = db::wine_inventory_events(&state.db, ctx.cellar_id(), wine_id).await?;
+    Ok((StatusCode::CREATED, Json(events)))
+}
+
+/// Replaces an event, keeping its time of day. Returns the wine's events.
+#[tracing::instrument(skip(state, ctx))]
+async fn update_event(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, event_id)): axum::extract::Path<(i64, i64)>,
+    JsonBody(body): JsonBody<EventBody>,
+) -> ApiResult<Json<Vec<db::WineInvEvent>>> {
+    let old = db::get_wine_event(&state.db, ctx.cellar_id(), wine_id, event_id).await?;
+    let event = body.event(old.dt.time())?;
+    db::update_wine_event(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        wine_id,
+        event_id,
+        &event,
+    )
+    .await
+    .map_err(inventory_error)?;
+    Ok(Json(
+        db::wine_inventory_events(&state.db, ctx.cellar_id(), wine_id).await?,
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn delete_event(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, event_id)): axum::extract::Path<(i64, i64)>,
+) -> ApiResult<StatusCode> {
+    db::delete_wine_event(&state.db, ctx.cellar_id(), &ctx.user, wine_id, event_id)
+        .await
+        .map_err(inventory_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ── Grapes ───────────────────────────────────────────────────────────────────
+
+#[tracing::instrument(skip(state))]
+async fn list_grapes(
+    axum::extract::State(state): axum::extract::State<State>,
+) -> ApiResult<Json<Vec<db::Grape>>> {
+    Ok(Json(db::get_grapes(&state.db).await?))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn get_wine_grapes(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<Vec<String>>> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    Ok(Json(
+        db::get_wine_grapes(&state.db, ctx.cellar_id(), wine_id).await?,
+    ))
+}
+
+/// Replaces the wine's grapes with the given list of grape names.
+#[tracing::instrument(skip(state, ctx))]
+async fn set_wine_grapes(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    JsonBody(grapes): JsonBody<Vec<String>>,
+) -> ApiResult<Json<Vec<String>>> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let known: Vec<_> = db::get_grapes(&state.db)
+        .await?
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+    if let Some(unknown) = grapes.iter().find(|g| !known.contains(g)) {
+        return Err(
+            super::AppError::bad_request(anyhow::anyhow!("Unknown grape: {unknown}")).into(),
+        );
+    }
+    let grapes: Vec<_> = grapes.iter().map(|g| g.as_str()).collect();
+    db::set_wine_grapes(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &grapes).await?;
+    Ok(Json(
+        db::get_wine_grapes(&state.db, ctx.cellar_id(), wine_id).await?,
+    ))
+}
+
+// ── Producers and regions ────────────────────────────────────────────────────
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_producers(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> ApiResult<Json<Vec<db::Producer>>> {
+    Ok(Json(db::producers(&state.db, ctx.cellar_id()).await?))
+}
+
+#[tracing::instrument(skip(state))]
+async fn list_regions(
+    axum::extract::State(state): axum::extract::State<State>,
+) -> ApiResult<Json<Vec<db::Region>>> {
+    Ok(Json(db::regions(&state.db).await?))
+}
+
+/// A new region. Without a parent it is a country; below a country it is a region and below
+/// a region an appellation.
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct RegionBody {
+    #[serde(default)]
+    parent_id: Option<i64>,
+    name: String,
+}
+
+/// Regions are shared by all cellars, so only admins add them.
+#[tracing::instrument(skip(state, ctx))]
+async fn create_region(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    JsonBody(body): JsonBody<RegionBody>,
+) -> ApiResult<impl IntoResponse> {
+    auth::require_admin(&ctx.user)?;
+    let name = body.name.trim();
+    if name.is_empty() {
+        return Err(
+            super::AppError::bad_request(anyhow::anyhow!("Region name cannot be empty")).into(),
+        );
+    }
+    let kind = match validate_region(&state.db, body.parent_id).await? {
+        None => db::RegionKind::Country,
+        Some(parent_id) => db::get_region(&state.db, parent_id)
+            .await?
+            .kind
+            .child()
+            .ok_or_else(|| {
+                super::AppError::bad_request(anyhow::anyhow!(
+                    "Appellations cannot contain other regions"
+                ))
+            })?,
+    };
+    let region = db::add_region(&state.db, body.parent_id, kind, name)
+        .await
+        .map_err(|e| unique_violation(e, &format!("The region \"{name}\" already exists")))?;
+    Ok((StatusCode::CREATED, Json(region)))
+}
+
+// ── Storage locations ────────────────────────────────────────────────────────
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_locations(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> ApiResult<Json<Vec<db::Location>>> {
+    Ok(Json(db::locations(&state.db, ctx.cellar_id()).await?))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct LocationBody {
+    name: String,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn create_location(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    JsonBody(body): JsonBody<LocationBody>,
+) -> ApiResult<impl IntoResponse> {
+    let name = validate_storage_name(&body.name, "Location")?;
+    let location = db::add_location(&state.db, ctx.cellar_id(), &ctx.user, name)
+        .await
+        .map_err(|e| unique_violation(e, &format!("There is already a location called {name}")))?;
+    Ok((StatusCode::CREATED, Json(location)))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_location_bottles(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(location_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<Vec<db::PlacedBottle>>> {
+    db::get_location(&state.db, ctx.cellar_id(), location_id).await?;
+    Ok(Json(
+        db::placed_bottles(&state.db, ctx.cellar_id(), None, Some(location_id)).await?,
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_racks(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> ApiResult<Json<Vec<db::Rack>>> {
+    Ok(Json(db::racks(&state.db, ctx.cellar_id(), None).await?))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct RackBody {
+    location_id: i64,
+    name: String,
+    row_count: i64,
+    column_count: i64,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn create_rack(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    JsonBody(body): JsonBody<RackBody>,
+) -> ApiResult<impl IntoResponse> {
+    let name = validate_storage_name(&body.name, "Rack")?;
+    validate_rack_size(body.row_count, body.column_count)?;
+    match db::get_location(&state.db, ctx.cellar_id(), body.location_id).await {
+        Ok(_) => {}
+        Err(e) if matches!(e.downcast_ref(), Some(sqlx::Error::RowNotFound)) => {
+            return Err(super::AppError::bad_request(anyhow::anyhow!(
+                "Unknown location: {}",
+                body.location_id
+            ))
+            .into());
+        }
+        Err(e) => return Err(e.into()),
+    }
+    let rack = db::add_rack(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        body.location_id,
+        name,
+        body.row_count,
+        body.column_count,
+    )
+    .await
+    .map_err(|e| unique_violation(e, &format!("There is already a rack called {name} here")))?;
+    Ok((StatusCode::CREATED, Json(rack)))
+}
+
+/// Where the wine's bottles are.
+#[tracing::instrument(skip(state, ctx))]
+async fn list_wine_bottles(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<Vec<db::PlacedBottle>>> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    Ok(Json(
+        db::placed_bottles(&state.db, ctx.cellar_id(), Some(wine_id), None).await?,
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_moves(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<Vec<db::BottleMove>>> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    Ok(Json(
+        db::bottle_moves(&state.db, ctx.cellar_id(), wine_id).await?,
+    ))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct MoveBody {
+    from: db::Slot,
+    to: db::Slot,
+}
+
+/// Moves one of the wine's bottles to an empty slot. Returns the wine's moves.
+#[tracing::instrument(skip(state, ctx))]
+async fn create_move(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    JsonBody(body): JsonBody<MoveBody>,
+) -> ApiResult<impl IntoResponse> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let now = chrono::Local::now().naive_local();
+    db::move_bottle(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        wine_id,
+        body.from,
+        body.to,
+        now,
+    )
+    .await
+    .map_err(inventory_error)?;
+    let moves = db::bottle_moves(&state.db, ctx.cellar_id(), wine_id).await?;
+    Ok((StatusCode::CREATED, Json(moves)))
+}
+
+// ── Food pairings ────────────────────────────────────────────────────────────
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct PairingBody {
+    food: String,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_pairings(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<Vec<db::FoodPairing>>> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    Ok(Json(
+        db::get_wine_food_pairings(&state.db, ctx.cellar_id(), wine_id).await?,
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn create_pairing(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    JsonBody(body): JsonBody<PairingBody>,
+) -> ApiResult<impl IntoResponse> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let food = validate_food_pairing(&body.food)?;
+    let pairing = db::add_food_pairing(&state.db, ctx.cellar_id(), &ctx.user, wine_id, food)
+        .await
+        .map_err(|e| unique_violation(e, "This food pairing already exists for this wine"))?;
+    Ok((StatusCode::CREATED, Json(pairing)))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn get_pairing(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, pairing_id)): axum::extract::Path<(i64, i64)>,
+) -> ApiResult<Json<db::FoodPairing>> {
+    Ok(Json(
+        db::get_food_pairing(&state.db, ctx.cellar_id(), wine_id, pairing_id).await?,
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn update_pairing(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, pairing_id)): axum::extract::Path<(i64, i64)>,
+    JsonBody(body): JsonBody<PairingBody>,
+) -> ApiResult<Json<db::FoodPairing>> {
+    let food = validate_food_pairing(&body.food)?;
+    let pairing = db::update_food_pairing(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        wine_id,
+        pairing_id,
+        food,
+    )
+    .await
+    .map_err(|e| unique_violation(e, "This food pairing already exists for this wine"))?;
+    Ok(Json(pairing))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn delete_pairing(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, pairing_id)): axum::extract::Path<(i64, i64)>,
+) -> ApiResult<StatusCode> {
+    db::remove_food_pairing(&state.db, ctx.cellar_id(), &ctx.user, pairing_id, wine_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ── Search ───────────────────────────────────────────────────────────────────
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct SearchParams {
+    q: String,
+    limit: Option<i64>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct SearchResult {
+    wine_id: i64,
+    name: String,
+    year: i64,
+    /// The best matching text, with the matches wrapped in `[` and `]`.
+    snippet: String,
+}
+
+/// Full-text search, best matches first.
+#[tracing::instrument(skip(state, ctx))]
+async fn search(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Query(params): axum::extract::Query<SearchParams>,
+) -> ApiResult<Json<Vec<SearchResult>>> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let hits = db::search_wines(&state.db, ctx.cellar_id(), &params.q, limit).await?;
+    let unmark = |text: &str| text.replace([db::MATCH_START, db::MATCH_END], "");
+    Ok(Json(
+        hits.into_iter()
+            .map(|hit| SearchResult {
+                wine_id: hit.wine_id,
+                name: unmark(&hit.name),
+                year: hit.year,
+                snippet: hit
+                    .snippet
+                    .replace(db::MATCH_START, "[")
+                    .replace(db::MATCH_END, "]"),
+            })
+            .collect(),
+    ))
+}
+
+// ── Tastings ─────────────────────────────────────────────────────────────────
+
+#[tracing::instrument(skip(state, ctx))]
+async fn list_tastings(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<Vec<db::Tasting>>> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    Ok(Json(
+        db::wine_tastings(&state.db, ctx.cellar_id(), wine_id).await?,
+    ))
+}
+
+/// The vocabulary for the aromas on a tasting sheet.
+#[tracing::instrument(skip(state))]
+async fn list_aromas(
+    axum::extract::State(state): axum::extract::State<State>,
+) -> ApiResult<Json<Vec<db::Aroma>>> {
+    Ok(Json(db::aromas(&state.db).await?))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct TastingBody {
+    tasted_on: chrono::NaiveDate,
+    /// On the configured score scale, which is stored with the tasting.
+    score: i64,
+    note: Option<String>,
+    occasion: Option<String>,
+    /// The optional structured tasting sheet.
+    sheet: Option<db::TastingSheet>,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn create_tasting(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    JsonBody(body): JsonBody<TastingBody>,
+) -> ApiResult<impl IntoResponse> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    if let Some(sheet) = &body.sheet {
+        validate_sheet(&state.db, sheet).await?;
+    }
+    let note = body.note.as_deref().map(str::trim).unwrap_or_default();
+    let tasting = db::NewTasting {
+        tasted_on: body.tasted_on,
+        score: state
+            .score_scale
+            .validate(body.score)
+            .map_err(super::AppError::bad_request)?,
+        scale: state.score_scale.max(),
+        note: (!note.is_empty()).then_some(note),
+        occasion: validate_occasion(body.occasion.as_deref().unwrap_or_default())?,
+        sheet: body.sheet.as_ref(),
+    };
+    let tasting = db::add_tasting(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &tasting).await?;
+    Ok((StatusCode::CREATED, Json(tasting)))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn delete_tasting(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, tasting_id)): axum::extract::Path<(i64, i64)>,
+) -> ApiResult<StatusCode> {
+    db::delete_tasting(&state.db, ctx.cellar_id(), &ctx.user, wine_id, tasting_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ── Note ─────────────────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Debug)]
+pub(crate) struct Note {
+    comment: Option<String>,
+    updated_at: Option<chrono::NaiveDateTime>,
+}
+
+impl From<db::Wine> for Note {
+    fn from(wine: db::Wine) -> Self {
+        Self {
+            comment: wine.comment,
+            updated_at: wine.comment_updated_at,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct NoteBody {
+    comment: String,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn get_note(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<Json<Note>> {
+    Ok(Json(
+        db::get_wine(&state.db, ctx.cellar_id(), wine_id)
+            .await?
+            .into(),
+    ))
+}
+
+/// Sets the note. An empty (or whitespace only) comment clears it, like the HTML form does.
+#[tracing::instrument(skip(state, ctx))]
+async fn set_note(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    JsonBody(body): JsonBody<NoteBody>,
+) -> ApiResult<Json<Note>> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let text = body.comment.trim();
+    let (comment, dt) = if text.is_empty() {
+        (None, None)
+    } else {
+        (Some(text), Some(chrono::Local::now().naive_local()))
+    };
+    db::set_wine_comment(&state.db, ctx.cellar_id(), &ctx.user, wine_id, comment, dt).await?;
+    Ok(Json(
+        db::get_wine(&state.db, ctx.cellar_id(), wine_id)
+            .await?
+            .into(),
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+async fn delete_note(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> ApiResult<StatusCode> {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    db::set_wine_comment(&state.db, ctx.cellar_id(), &ctx.user, wine_id, None, None).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
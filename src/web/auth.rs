@@ -0,0 +1,416 @@
+//! Local accounts and login sessions. Every route but the login page and the favicon goes
+//! through [`require_login`], which accepts a session cookie or, for the JSON API, HTTP basic
+//! authentication. Logged in requests carry the [`db::User`] as an extension, and the
+//! [`Context`] of the cellar they work in when the user has access to one.
+//!
+//! Session cookies hold a random token; the database only has its SHA-256, see the `sessions`
+//! migration.
+
+use argon2::{PasswordHasher, PasswordVerifier};
+use axum::response::IntoResponse;
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+
+use super::{AppError, State, error::ApiError};
+use crate::db;
+
+pub(crate) const SESSION_COOKIE: &str = "wine_session";
+/// Picks the cellar an API request works in; browsers switch cellars in their session.
+pub(crate) const CELLAR_HEADER: &str = "X-Cellar-Id";
+const SESSION_DAYS: i64 = 30;
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_USERNAME_LEN: usize = 50;
+/// The hash of a random password nobody knows, made with the default parameters. Logins with
+/// an unknown username are checked against it, so they take as long as those with a wrong
+/// password and don't give away which usernames exist.
+const DUMMY_HASH: &str = concat!(
+    "$argon2id$v=19$m=19456,t=2,p=1$pTlaHKU7dFAQ4eIuMHcJeQ",
+    "$iVfaa+LdHjySpR2saiXKNNXZ0wCg0cHwAhzRKgo4/Gg"
+);
+
+/// Hashes a password into an argon2 PHC string with a fresh salt. Slow on purpose, so it
+/// runs on the blocking pool.
+pub(crate) async fn hash_password(password: &str) -> anyhow::Result<String> {
+    let password = password.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let salt = argon2::password_hash::SaltString::generate(
+            &mut argon2::password_hash::rand_core::OsRng,
+        );
+        let hash = argon2::Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!("Could not hash password: {e}"))?;
+        Ok(hash.to_string())
+    })
+    .await?
+}
+
+/// Whether `password` matches the PHC string `hash`.
+pub(crate) async fn verify_password(password: &str, hash: &str) -> anyhow::Result<bool> {
+    let (password, hash) = (password.to_owned(), hash.to_owned());
+    tokio::task::spawn_blocking(move || {
+        let hash = argon2::PasswordHash::new(&hash)
+            .map_err(|e| anyhow::anyhow!("Invalid password hash: {e}"))?;
+        Ok(argon2::Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok())
+    })
+    .await?
+}
+
+/// A new session or share link token: 32 random bytes, hex encoded.
+pub(crate) fn new_token() -> String {
+    use argon2::password_hash::rand_core::RngCore;
+
+    let mut bytes = [0u8; 32];
+    argon2::password_hash::rand_core::OsRng.fill_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+/// What the `sessions` and `share_links` tables store for a token.
+pub(crate) fn token_hash(token: &str) -> String {
+    use sha2::Digest;
+
+    hex(&sha2::Sha256::digest(token.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Trims and checks a new account's username.
+pub(crate) fn validate_username(username: &str) -> Result<&str, AppError> {
+    let username = username.trim();
+    if username.is_empty() || username.chars().count() > MAX_USERNAME_LEN {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Username must be between 1 and {MAX_USERNAME_LEN} characters"
+        )));
+    }
+    Ok(username)
+}
+
+/// Checks a new password, typed twice.
+pub(crate) fn validate_password<'a>(password: &'a str, confirm: &str) -> Result<&'a str, AppError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Password must be at least {MIN_PASSWORD_LEN} characters"
+        )));
+    }
+    if password != confirm {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Passwords do not match"
+        )));
+    }
+    Ok(password)
+}
+
+/// The account with `username` if `password` is right.
+pub(crate) async fn check_login(
+    db: &sqlx::SqlitePool,
+    username: &str,
+    password: &str,
+) -> anyhow::Result<Option<db::User>> {
+    let Some((user, hash)) = db::user_login(db, username.trim()).await? else {
+        verify_password(password, DUMMY_HASH).await?;
+        return Ok(None);
+    };
+    Ok(verify_password(password, &hash).await?.then_some(user))
+}
+
+/// Starts a session for `user` and returns the cookie that carries it.
+pub(crate) async fn start_session(
+    db: &sqlx::SqlitePool,
+    user: &db::User,
+) -> anyhow::Result<Cookie<'static>> {
+    let token = new_token();
+    let now = chrono::Utc::now().naive_utc();
+    let expires_at = now + chrono::Duration::days(SESSION_DAYS);
+    db::add_session(db, &token_hash(&token), user.user_id, now, expires_at).await?;
+    Ok(Cookie::build((SESSION_COOKIE, token))
+        .path("/")
+        .http_only(true)
+        // Keeps other sites from posting to the cellar with the cookie
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::days(SESSION_DAYS))
+        .build())
+}
+
+/// What the `sessions` table stores for the session in `jar`, if there is one.
+pub(crate) fn session_hash(jar: &CookieJar) -> Option<String> {
+    jar.get(SESSION_COOKIE)
+        .map(|cookie| token_hash(cookie.value()))
+}
+
+/// Ends the session in `jar`, if any, and returns the jar without it.
+pub(crate) async fn end_session(
+    db: &sqlx::SqlitePool,
+    jar: CookieJar,
+) -> anyhow::Result<CookieJar> {
+    if let Some(session) = session_hash(&jar) {
+        db::delete_session(db, &session).await?;
+    }
+    Ok(jar.remove(Cookie::build(SESSION_COOKIE).path("/")))
+}
+
+/// The user making the request and the cellar their session was switched to, if any.
+async fn authenticate(
+    state: &super::StateInner,
+    jar: &CookieJar,
+    headers: &axum::http::HeaderMap,
+    api: bool,
+) -> anyhow::Result<Option<(db::User, Option<i64>)>> {
+    if let Some(cookie) = jar.get(SESSION_COOKIE) {
+        let now = chrono::Utc::now().naive_utc();
+        let session = db::session_user(&state.db, &token_hash(cookie.value()), now).await?;
+        if session.is_some() {
+            return Ok(session);
+        }
+    }
+    // Scripts talking to the API can send their credentials along instead
+    if api {
+        use headers::HeaderMapExt;
+
+        if let Some(headers::Authorization(basic)) =
+            headers.typed_get::<headers::Authorization<headers::authorization::Basic>>()
+        {
+            let user = check_login(&state.db, basic.username(), basic.password()).await?;
+            return Ok(user.map(|user| (user, None)));
+        }
+    }
+    Ok(None)
+}
+
+/// Who is asking and which of their cellars they are working in, for handlers to take as an
+/// extractor. Every `db` call about a cellar's contents goes through [`Context::cellar_id`].
+#[derive(Debug, Clone)]
+pub(crate) struct Context {
+    pub user: db::User,
+    pub cellar: db::Cellar,
+}
+
+impl Context {
+    pub(crate) fn cellar_id(&self) -> i64 {
+        self.cellar.cellar_id
+    }
+
+    pub(crate) fn is_owner(&self) -> bool {
+        self.cellar.role == db::CellarRole::Owner
+    }
+}
+
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for Context {
+    type Rejection = axum::response::Response;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<Self>().cloned().ok_or_else(|| {
+            // Nested routers only see the rest of the path
+            let path = parts
+                .extensions
+                .get::<axum::extract::OriginalUri>()
+                .map_or(parts.uri.path(), |uri| uri.path());
+            reject(
+                is_api(path),
+                AppError::forbidden(anyhow::anyhow!("You don't have access to a cellar yet")),
+            )
+        })
+    }
+}
+
+impl<S: Send + Sync> axum::extract::OptionalFromRequestParts<S> for Context {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned())
+    }
+}
+
+/// The cellar `user` works in: `wanted` if they have access to it, else the first of theirs.
+async fn active_cellar(
+    db: &sqlx::SqlitePool,
+    user: &db::User,
+    wanted: Option<i64>,
+) -> anyhow::Result<Option<db::Cellar>> {
+    let mut cellars = db::user_cellars(db, user.user_id).await?;
+    let index = wanted
+        .and_then(|id| cellars.iter().position(|c| c.cellar_id == id))
+        .unwrap_or(0);
+    Ok((index < cellars.len()).then(|| cellars.swap_remove(index)))
+}
+
+/// The cellar an API request asks for in the [`CELLAR_HEADER`], if any.
+fn requested_cellar(headers: &axum::http::HeaderMap) -> Result<Option<i64>, AppError> {
+    let Some(value) = headers.get(CELLAR_HEADER) else {
+        return Ok(None);
+    };
+    let id = value.to_str().ok().and_then(|v| v.trim().parse().ok());
+    id.map(Some).ok_or_else(|| {
+        AppError::bad_request(anyhow::anyhow!("{CELLAR_HEADER} must be a cellar id"))
+    })
+}
+
+fn is_api(path: &str) -> bool {
+    path.starts_with("/api/")
+}
+
+fn reject(api: bool, error: AppError) -> axum::response::Response {
+    if api {
+        ApiError::from(error).into_response()
+    } else {
+        error.into_response()
+    }
+}
+
+/// Middleware that lets logged in requests through, with the [`Context`] of the cellar they
+/// work in. Others get a 401 from the API, and are sent to the login page otherwise. API
+/// requests asking for a cellar they can't access get a 403.
+pub(crate) async fn require_login(
+    axum::extract::State(state): axum::extract::State<State>,
+    jar: CookieJar,
+    mut request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let api = is_api(request.uri().path());
+    let user = match authenticate(&state, &jar, request.headers(), api).await {
+        Ok(user) => user,
+        Err(e) => return reject(api, e.into()),
+    };
+    if let Some((user, session_cellar)) = user {
+        // Only the API can ask for a cellar per request; the browser switches its session
+        let requested = if api {
+            match requested_cellar(request.headers()) {
+                Ok(requested) => requested,
+                Err(e) => return reject(api, e),
+            }
+        } else {
+            None
+        };
+        let wanted = requested.or(session_cellar);
+        let cellar = match active_cellar(&state.db, &user, wanted).await {
+            Ok(cellar) => cellar,
+            Err(e) => return reject(api, e.into()),
+        };
+        if let Some(id) = requested
+            && cellar.as_ref().is_none_or(|c| c.cellar_id != id)
+        {
+            return reject(
+                api,
+                AppError::forbidden(anyhow::anyhow!("You don't have access to cellar {id}")),
+            );
+        }
+        if let Some(cellar) = cellar {
+            request.extensions_mut().insert(Context {
+                user: user.clone(),
+                cellar,
+            });
+        }
+        request.extensions_mut().insert(user);
+        return next.run(request).await;
+    }
+    if api {
+        let error = ApiError::from(AppError::unauthorized(anyhow::anyhow!("Not logged in")));
+        return (
+            [(
+                axum::http::header::WWW_AUTHENTICATE,
+                "Basic realm=\"wine-cellar\"",
+            )],
+            error,
+        )
+            .into_response();
+    }
+    if request.headers().contains_key("HX-Request") {
+        // A redirect would be followed by htmx and swapped into the page; have it load the
+        // login page instead
+        return (axum_htmx::HxRedirect("/login".to_owned()), ()).into_response();
+    }
+    axum::response::Redirect::to("/login").into_response()
+}
+
+/// Middleware that keeps viewers to looking: anything but a GET needs a role that can edit
+/// the cellar, and users without a cellar can't change anything. Runs inside
+/// [`require_login`].
+pub(crate) async fn require_editor(
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    if request.method().is_safe() {
+        return next.run(request).await;
+    }
+    let error = match request.extensions().get::<Context>() {
+        Some(ctx) if ctx.cellar.role.can_edit() => return next.run(request).await,
+        Some(_) => "Viewers can't make changes to the cellar",
+        None => "You don't have access to a cellar yet",
+    };
+    let api = is_api(request.uri().path());
+    reject(api, AppError::forbidden(anyhow::anyhow!(error)))
+}
+
+/// Turns away users who aren't admins.
+pub(crate) fn require_admin(user: &db::User) -> Result<(), AppError> {
+    if !user.is_admin {
+        return Err(AppError::forbidden(anyhow::anyhow!(
+            "Only admins can do that"
+        )));
+    }
+    Ok(())
+}
+
+/// Turns away users who don't own the cellar.
+pub(crate) fn require_owner(ctx: &Context) -> Result<(), AppError> {
+    if !ctx.is_owner() {
+        return Err(AppError::forbidden(anyhow::anyhow!(
+            "Only the cellar's owners can do that"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hash_and_verify_password() {
+        let hash = hash_password("correct horse").await.unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &hash).await.unwrap());
+        assert!(!verify_password("Correct horse", &hash).await.unwrap());
+        // Salted, so the same password hashes differently
+        assert_ne!(hash_password("correct horse").await.unwrap(), hash);
+    }
+
+    #[tokio::test]
+    async fn test_dummy_hash() {
+        // As slow to check as a real hash
+        let dummy = argon2::PasswordHash::new(DUMMY_HASH).unwrap();
+        let real = hash_password("correct horse").await.unwrap();
+        let real = argon2::PasswordHash::new(&real).unwrap();
+        assert_eq!(dummy.algorithm, real.algorithm);
+        assert_eq!(dummy.params, real.params);
+        assert!(!verify_password("", DUMMY_HASH).await.unwrap());
+    }
+
+    #[test]
+    fn test_tokens() {
+        let token = new_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(new_token(), token);
+        assert_eq!(
+            token_hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_validate_credentials() {
+        assert_eq!(validate_username("  anna ").ok(), Some("anna"));
+        assert!(validate_username(" ").is_err());
+        assert!(validate_username(&"x".repeat(51)).is_err());
+        assert!(validate_password("long enough", "long enough").is_ok());
+        assert!(validate_password("short", "short").is_err());
+        assert!(validate_password("long enough", "long enougH").is_err());
+    }
+}
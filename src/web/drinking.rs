@@ -0,0 +1,128 @@
+//! Where the wines in the cellar are in their drinking window. Wines without a window, and
+//! wines that won't be ready for a while yet, are left out.
+
+use crate::db;
+
+/// How many years before `drink_from` a wine counts as approaching its window.
+pub(crate) const APPROACHING_YEARS: i64 = 2;
+
+/// Ordered by urgency: wines past their window should be drunk first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Status {
+    Past,
+    Ready,
+    Approaching,
+}
+
+impl Status {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Past => "Past their window",
+            Self::Ready => "Ready to drink",
+            Self::Approaching => "Approaching their window",
+        }
+    }
+}
+
+/// Classifies a drinking window relative to `year`. Returns `None` for wines without a window
+/// and for wines that are more than [`APPROACHING_YEARS`] away from it.
+pub(crate) fn status(
+    drink_from: Option<i64>,
+    drink_until: Option<i64>,
+    year: i64,
+) -> Option<Status> {
+    if drink_from.is_none() && drink_until.is_none() {
+        return None;
+    }
+    match (drink_from, drink_until) {
+        (_, Some(until)) if until < year => Some(Status::Past),
+        (Some(from), _) if from > year + APPROACHING_YEARS => None,
+        (Some(from), _) if from > year => Some(Status::Approaching),
+        _ => Some(Status::Ready),
+    }
+}
+
+/// Picks the rows that are past, in or approaching their window in `year` and sorts them by
+/// urgency: the longest overdue first, then ready wines whose window closes soonest, then
+/// those whose window opens soonest.
+pub(crate) fn by_urgency(rows: Vec<db::WineRow>, year: i64) -> Vec<(Status, db::WineRow)> {
+    let mut wines: Vec<_> = rows
+        .into_iter()
+        .filter_map(|row| Some((status(row.drink_from, row.drink_until, year)?, row)))
+        .collect();
+    wines.sort_by_key(|(status, row)| {
+        let key = match status {
+            Status::Past | Status::Ready => row.drink_until.unwrap_or(i64::MAX),
+            Status::Approaching => row.drink_from.unwrap_or(i64::MAX),
+        };
+        (*status, key, row.name.clone())
+    });
+    wines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str, drink_from: Option<i64>, drink_until: Option<i64>) -> db::WineRow {
+        db::WineRow {
+            wine_id: 0,
+            name: name.to_owned(),
+            year: 2015,
+            style: None,
+            producer: None,
+            origin: None,
+            drink_from,
+            drink_until,
+            has_image: false,
+            comment: None,
+            bottles: 1,
+            grapes: Vec::new(),
+            pairings: Vec::new(),
+            score: None,
+            last_activity: None,
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        assert_eq!(status(None, None, 2026), None);
+        assert_eq!(status(Some(2020), Some(2025), 2026), Some(Status::Past));
+        assert_eq!(status(None, Some(2025), 2026), Some(Status::Past));
+        assert_eq!(status(Some(2020), Some(2026), 2026), Some(Status::Ready));
+        assert_eq!(status(Some(2026), None, 2026), Some(Status::Ready));
+        assert_eq!(status(None, Some(2030), 2026), Some(Status::Ready));
+        assert_eq!(
+            status(Some(2028), Some(2035), 2026),
+            Some(Status::Approaching)
+        );
+        assert_eq!(status(Some(2029), Some(2035), 2026), None);
+    }
+
+    #[test]
+    fn test_by_urgency() {
+        let rows = vec![
+            row("Not yet", Some(2040), None),
+            row("Soon", Some(2027), Some(2035)),
+            row("Open ended", Some(2020), None),
+            row("Closing", Some(2020), Some(2027)),
+            row("Recently past", Some(2015), Some(2025)),
+            row("Long past", Some(2010), Some(2018)),
+            row("No window", None, None),
+        ];
+        let sorted: Vec<_> = by_urgency(rows, 2026)
+            .into_iter()
+            .map(|(status, row)| (status, row.name))
+            .collect();
+        assert_eq!(
+            sorted,
+            [
+                (Status::Past, "Long past".to_owned()),
+                (Status::Past, "Recently past".to_owned()),
+                (Status::Ready, "Closing".to_owned()),
+                (Status::Ready, "Open ended".to_owned()),
+                (Status::Approaching, "Soon".to_owned()),
+            ]
+        );
+    }
+}
@@ -16,6 +16,34 @@ impl AppError {
         }
     }
 
+    pub(crate) fn unauthorized<E>(err: E) -> Self
+    where
+        E: Into<anyhow::Error>,
+    {
+        Self {
+            error: err.into(),
+            status: axum::http::StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    pub(crate) fn forbidden<E>(err: E) -> Self
+    where
+        E: Into<anyhow::Error>,
+    {
+        Self {
+            error: err.into(),
+            status: axum::http::StatusCode::FORBIDDEN,
+        }
+    }
+
+    /// A request body that couldn't be read as JSON, with the status and message axum gives it.
+    pub(crate) fn json_rejection(rejection: axum::extract::rejection::JsonRejection) -> Self {
+        Self {
+            error: anyhow::anyhow!(rejection.body_text()),
+            status: rejection.status(),
+        }
+    }
+
     pub(crate) fn payload_too_large<E>(err: E) -> Self
     where
         E: Into<anyhow::Error>,
@@ -32,22 +60,42 @@ where
     E: Into<anyhow::Error>,
 {
     fn from(err: E) -> Self {
+        let error = err.into();
+        // A lookup by id that found nothing is the client's problem, not ours
+        if let Some(sqlx::Error::RowNotFound) = error.downcast_ref::<sqlx::Error>() {
+            return Self {
+                error: anyhow::anyhow!("Not found"),
+                status: axum::http::StatusCode::NOT_FOUND,
+            };
+        }
         Self {
-            error: err.into(),
+            error,
             status: axum::http::StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> axum::response::Response {
-        let (err, class) = if self.status.is_client_error() {
+impl AppError {
+    /// Logs the error and returns the message that is safe to show to the client.
+    fn client_message(&self) -> String {
+        if self.status.is_client_error() {
             tracing::warn!("{}", self.error);
-            (self.error.to_string(), "text-bg-warning")
+            self.error.to_string()
         } else {
             tracing::error!("{}", self.error);
-            ("Internal Error".to_owned(), "text-bg-danger")
+            "Internal Error".to_owned()
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> axum::response::Response {
+        let class = if self.status.is_client_error() {
+            "text-bg-warning"
+        } else {
+            "text-bg-danger"
         };
+        let err = self.client_message();
         (
             self.status,
             maud::html! {
@@ -59,3 +107,33 @@ impl IntoResponse for AppError {
             .into_response()
     }
 }
+
+/// JSON flavour of [`AppError`] used by the `/api/v1` routes. Renders
+/// `{"status": <code>, "error": <message>}` instead of an HTML fragment.
+pub(crate) struct ApiError(AppError);
+
+impl<E> From<E> for ApiError
+where
+    E: Into<AppError>,
+{
+    fn from(err: E) -> Self {
+        Self(err.into())
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ApiErrorBody {
+    status: u16,
+    error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.0.status;
+        let body = ApiErrorBody {
+            status: status.as_u16(),
+            error: self.0.client_message(),
+        };
+        (status, axum::Json(body)).into_response()
+    }
+}
@@ -1,23 +1,226 @@
-use super::{MDResult, State};
+use super::{MDResult, State, auth};
 use anyhow::Context;
 use image::GenericImageView;
 
 use crate::{db, web::AppError};
 
-// ── Food Pairings ────────────────────────────────────────────────────────────
+/// Turns a unique-constraint violation into a 400 carrying `msg`; any other error stays a 500.
+pub(super) fn unique_violation(e: anyhow::Error, msg: &str) -> AppError {
+    if let Some(sqlx::Error::Database(db_err)) = e.downcast_ref::<sqlx::Error>()
+        && db_err.is_unique_violation()
+    {
+        return AppError::bad_request(anyhow::anyhow!("{msg}"));
+    }
+    e.into()
+}
 
-#[derive(serde::Deserialize, Debug)]
-pub(crate) struct AddFoodPairing {
-    food: String,
+/// Trims and validates a wine name, shared by the add and edit forms and the JSON API.
+pub(super) fn validate_wine_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Wine name cannot be empty"
+        )));
+    }
+    Ok(name)
 }
 
-#[tracing::instrument(skip(state))]
-pub(crate) async fn add_food_pairing(
-    axum::extract::State(state): axum::extract::State<State>,
-    axum::extract::Path(wine_id): axum::extract::Path<i64>,
-    axum::extract::Form(form): axum::extract::Form<AddFoodPairing>,
-) -> MDResult {
-    let food = form.food.trim();
+/// Parses an optional form field, where an empty value means "not set".
+fn parse_optional<T>(value: &str) -> Result<Option<T>, AppError>
+where
+    T: std::str::FromStr,
+    T::Err: Into<anyhow::Error>,
+{
+    match value.trim() {
+        "" => Ok(None),
+        v => v.parse().map(Some).map_err(AppError::bad_request),
+    }
+}
+
+/// Checks that a drinking window, if both ends are given, doesn't end before it starts.
+pub(super) fn validate_drinking_window(
+    drink_from: Option<i64>,
+    drink_until: Option<i64>,
+) -> Result<(), AppError> {
+    if let (Some(from), Some(until)) = (drink_from, drink_until)
+        && until < from
+    {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "The drinking window can't end ({until}) before it starts ({from})"
+        )));
+    }
+    Ok(())
+}
+
+/// Trims a producer name and looks it up, adding the producer if it's new. An empty name means
+/// the producer isn't known.
+pub(super) async fn resolve_producer(
+    db: &sqlx::SqlitePool,
+    name: &str,
+) -> Result<Option<i64>, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Ok(None);
+    }
+    if name.len() > 100 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Producer must be 100 characters or less"
+        )));
+    }
+    Ok(Some(db::producer_id(db, name).await?))
+}
+
+/// Checks that `region_id`, if given, refers to an existing region.
+pub(super) async fn validate_region(
+    db: &sqlx::SqlitePool,
+    region_id: Option<i64>,
+) -> Result<Option<i64>, AppError> {
+    let Some(region_id) = region_id else {
+        return Ok(None);
+    };
+    match db::get_region(db, region_id).await {
+        Ok(_) => Ok(Some(region_id)),
+        Err(e) if matches!(e.downcast_ref(), Some(sqlx::Error::RowNotFound)) => Err(
+            AppError::bad_request(anyhow::anyhow!("Unknown region: {region_id}")),
+        ),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Turns a rejected inventory change into a 400 with its message; any other error stays a 500.
+pub(super) fn inventory_error(e: anyhow::Error) -> AppError {
+    if e.downcast_ref::<db::InventoryError>().is_some() {
+        AppError::bad_request(e)
+    } else {
+        e.into()
+    }
+}
+
+/// Turns a refused account or membership change into a 400 with its message; any other error
+/// stays a 500.
+pub(super) fn account_error(e: anyhow::Error) -> AppError {
+    if e.downcast_ref::<db::AccountError>().is_some() {
+        AppError::bad_request(e)
+    } else {
+        e.into()
+    }
+}
+
+/// Checks a bottle count entered in the buy and consume forms.
+fn validate_bottles(bottles: i64) -> Result<i64, AppError> {
+    if bottles < 1 {
+        return Err(AppError::bad_request(db::InventoryError::NoBottles));
+    }
+    Ok(bottles)
+}
+
+/// Parses a price such as `249.50` or `249,5` into minor units (`24950`). Empty means no price.
+fn parse_amount(amount: &str) -> Result<Option<i64>, AppError> {
+    let amount = amount.trim();
+    if amount.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || AppError::bad_request(anyhow::anyhow!("Invalid price: {amount}"));
+    let (whole, fraction) = amount.split_once(['.', ',']).unwrap_or((amount, ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !digits(whole) || !digits(fraction) || fraction.len() > 2 {
+        return Err(invalid());
+    }
+    let whole: i64 = whole.parse().map_err(|_| invalid())?;
+    let fraction: i64 = format!("{fraction:0<2}").parse().map_err(|_| invalid())?;
+    whole
+        .checked_mul(100)
+        .and_then(|w| w.checked_add(fraction))
+        .map(Some)
+        .ok_or_else(invalid)
+}
+
+/// Checks a purchase price in minor units and its ISO 4217 currency code, shared by the buy
+/// form and the JSON API. A price needs a currency; a currency without a price is ignored.
+pub(super) fn validate_price(
+    amount: Option<i64>,
+    currency: &str,
+) -> Result<Option<db::Price>, AppError> {
+    let Some(amount) = amount else {
+        return Ok(None);
+    };
+    if amount < 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "The price can't be negative"
+        )));
+    }
+    let currency = currency.trim().to_ascii_uppercase();
+    if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "The currency must be a three letter code such as EUR"
+        )));
+    }
+    Ok(Some(db::Price { amount, currency }))
+}
+
+/// Trims a vendor name; empty means none.
+pub(super) fn validate_vendor(vendor: &str) -> Result<Option<&str>, AppError> {
+    let vendor = vendor.trim();
+    if vendor.chars().count() > 100 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Vendor name too long (max 100 characters)"
+        )));
+    }
+    Ok((!vendor.is_empty()).then_some(vendor))
+}
+
+/// The slots ticked in a slot picker, which posts a `slot` field (`rack_id:row:col`) for each.
+fn picked_slots(form: &[u8]) -> Result<Vec<db::Slot>, AppError> {
+    let pairs: Vec<(String, String)> =
+        serde_urlencoded::from_bytes(form).map_err(AppError::bad_request)?;
+    pairs
+        .iter()
+        .filter(|(key, _)| key == "slot")
+        .map(|(_, slot)| slot.parse())
+        .collect::<anyhow::Result<_>>()
+        .map_err(AppError::bad_request)
+}
+
+/// Parses a form with a slot picker: the slots, and the other fields deserialized into `T`.
+fn form_with_slots<T>(form: &[u8]) -> Result<(T, Vec<db::Slot>), AppError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let fields = serde_urlencoded::from_bytes(form).map_err(AppError::bad_request)?;
+    Ok((fields, picked_slots(form)?))
+}
+
+/// Trims and validates the name of a location or a rack, shared by the forms and the JSON API.
+pub(super) fn validate_storage_name<'a>(name: &'a str, what: &str) -> Result<&'a str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "{what} name cannot be empty"
+        )));
+    }
+    if name.chars().count() > 100 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "{what} name too long (max 100 characters)"
+        )));
+    }
+    Ok(name)
+}
+
+/// Checks a rack's size against the limits in the schema.
+pub(super) fn validate_rack_size(row_count: i64, column_count: i64) -> Result<(), AppError> {
+    for (count, what) in [(row_count, "rows"), (column_count, "columns")] {
+        if !(1..=100).contains(&count) {
+            return Err(AppError::bad_request(anyhow::anyhow!(
+                "A rack must have between 1 and 100 {what}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Trims and validates a food pairing, shared by the pairings form and the JSON API.
+pub(super) fn validate_food_pairing(food: &str) -> Result<&str, AppError> {
+    let food = food.trim();
     if food.is_empty() {
         return Err(AppError::bad_request(anyhow::anyhow!(
             "Food pairing cannot be empty"
@@ -28,30 +231,46 @@ pub(crate) async fn add_food_pairing(
             "Food pairing must be 100 characters or less"
         )));
     }
-    match db::add_food_pairing(&state.db, wine_id, food).await {
-        Ok(_) => {}
-        Err(e) => {
-            if let Some(sqlx::Error::Database(db_err)) = e.downcast_ref::<sqlx::Error>()
-                && db_err.is_unique_violation()
-            {
-                return Err(AppError::bad_request(anyhow::anyhow!(
-                    "This food pairing already exists for this wine"
-                )));
-            }
-            return Err(e.into());
-        }
+    // It would come back from a CSV export as two pairings
+    let separator = super::import::LIST_SEPARATOR;
+    if food.contains(separator) {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Food pairing cannot contain \"{separator}\""
+        )));
     }
-    let pairings = db::get_wine_food_pairings(&state.db, wine_id).await?;
+    Ok(food)
+}
+
+// ── Food Pairings ────────────────────────────────────────────────────────────
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct AddFoodPairing {
+    food: String,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn add_food_pairing(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    axum::extract::Form(form): axum::extract::Form<AddFoodPairing>,
+) -> MDResult {
+    let food = validate_food_pairing(&form.food)?;
+    db::add_food_pairing(&state.db, ctx.cellar_id(), &ctx.user, wine_id, food)
+        .await
+        .map_err(|e| unique_violation(e, "This food pairing already exists for this wine"))?;
+    let pairings = db::get_wine_food_pairings(&state.db, ctx.cellar_id(), wine_id).await?;
     Ok(super::markup::food_pairings_list_items(&pairings, wine_id))
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn remove_food_pairing(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path((wine_id, pairing_id)): axum::extract::Path<(i64, i64)>,
 ) -> MDResult {
-    db::remove_food_pairing(&state.db, pairing_id, wine_id).await?;
-    let pairings = db::get_wine_food_pairings(&state.db, wine_id).await?;
+    db::remove_food_pairing(&state.db, ctx.cellar_id(), &ctx.user, pairing_id, wine_id).await?;
+    let pairings = db::get_wine_food_pairings(&state.db, ctx.cellar_id(), wine_id).await?;
     Ok(super::markup::food_pairings_list_items(&pairings, wine_id))
 }
 
@@ -67,71 +286,631 @@ pub(crate) struct PairingsSearchQuery {
     q: Option<String>,
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn pairings_search_results(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Query(query): axum::extract::Query<PairingsSearchQuery>,
 ) -> MDResult {
     let q = query.q.as_deref().unwrap_or("").trim();
     if q.is_empty() {
         return Ok(super::markup::pairings_search_prompt());
     }
-    let wines = db::search_wines_by_food(&state.db, q).await?;
+    let wines = db::search_wines_by_food(&state.db, ctx.cellar_id(), q).await?;
     Ok(super::markup::pairings_search_results_markup(&wines, q))
 }
 
+// ── Search ───────────────────────────────────────────────────────────────────
+
+/// How many wines the search page shows.
+const SEARCH_LIMIT: i64 = 50;
+
 #[derive(serde::Deserialize, Debug)]
-pub(crate) struct AddWine {
+pub(crate) struct SearchQuery {
+    q: Option<String>,
+}
+
+/// Searches everything about the wines, for the search box at the top of every page.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn search(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Query(query): axum::extract::Query<SearchQuery>,
+) -> MDResult {
+    let q = query.q.as_deref().unwrap_or("").trim();
+    let hits = db::search_wines(&state.db, ctx.cellar_id(), q, SEARCH_LIMIT).await?;
+    Ok(super::markup::search_results(&hits, q))
+}
+
+pub(super) fn duplicate_wine_msg(name: &str, year: i64) -> String {
+    format!("A wine named \"{name}\" from {year} already exists, perhaps in the trash")
+}
+
+// ── CSV export / import ──────────────────────────────────────────────────────
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn export_csv(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> std::result::Result<axum::response::Response, AppError> {
+    let rows = db::wine_rows(&state.db, ctx.cellar_id(), &db::WineFilter::default()).await?;
+    let data = super::import::export(&rows)?;
+    axum::response::Response::builder()
+        .status(axum::http::StatusCode::OK)
+        .header(axum::http::header::CONTENT_TYPE, "text/csv; charset=utf-8")
+        .header(
+            axum::http::header::CONTENT_DISPOSITION,
+            "attachment; filename=\"wine-cellar.csv\"",
+        )
+        .body(data.into())
+        .map_err(|e| e.into())
+}
+
+/// Previews a CSV import, or applies it when the `apply` field is present. The plan is always
+/// recomputed against the current cellar, and nothing is written if any line has an error.
+#[tracing::instrument(skip(state, ctx, mp))]
+pub(crate) async fn import_csv(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    mut mp: axum::extract::Multipart,
+) -> MDResult {
+    let mut data = None;
+    let mut apply = false;
+    while let Some(field) = mp.next_field().await? {
+        match field.name() {
+            Some("file") => data = Some(field.bytes().await?),
+            Some("apply") => apply = true,
+            _ => {}
+        }
+    }
+    let data =
+        data.ok_or_else(|| AppError::bad_request(anyhow::anyhow!("No CSV file uploaded")))?;
+
+    let existing = db::wine_rows(&state.db, ctx.cellar_id(), &db::WineFilter::default()).await?;
+    let known_grapes: Vec<_> = db::get_grapes(&state.db)
+        .await?
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+    let plan =
+        super::import::plan(&data, &existing, &known_grapes).map_err(AppError::bad_request)?;
+    if !apply || !plan.errors.is_empty() {
+        return Ok(super::markup::import_preview(
+            &plan,
+            &String::from_utf8_lossy(&data),
+        ));
+    }
+
+    let imports: Vec<_> = plan.changes.into_iter().map(|c| c.import).collect();
+    db::import_wines(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        &imports,
+        chrono::Local::now().naive_local(),
+    )
+    .await?;
+    super::markup::wine_table_populated(&state, ctx.cellar_id()).await
+}
+
+// ── Backup / restore ─────────────────────────────────────────────────────────
+
+/// Streams a `VACUUM INTO` snapshot of the database, every cellar in it, as a download.
+#[tracing::instrument(skip(state, user))]
+pub(crate) async fn download_backup(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::Extension(user): axum::Extension<db::User>,
+) -> std::result::Result<axum::response::Response, AppError> {
+    auth::require_admin(&user)?;
+    let path = db::scratch_path("backup");
+    db::backup_to(&state.db, &path).await?;
+    let file = tokio::fs::File::open(&path).await;
+    // An unlinked file stays readable through the open handle, so the snapshot is cleaned up
+    // as soon as the response has been streamed (or the client goes away)
+    tokio::fs::remove_file(&path).await?;
+    let file = file?;
+
+    let filename = format!(
+        "wine-cellar-{}.db",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    axum::response::Response::builder()
+        .status(axum::http::StatusCode::OK)
+        .header(axum::http::header::CONTENT_TYPE, "application/vnd.sqlite3")
+        .header(
+            axum::http::header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{filename}\""),
+        )
+        .body(axum::body::Body::from_stream(
+            tokio_util::io::ReaderStream::new(file),
+        ))
+        .map_err(|e| e.into())
+}
+
+/// Replaces every cellar with an uploaded backup, see [`db::restore_from`], and reloads the
+/// page, as the cellars may have changed.
+#[tracing::instrument(skip(state, user, mp))]
+pub(crate) async fn restore_backup(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::Extension(user): axum::Extension<db::User>,
+    mut mp: axum::extract::Multipart,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    use tokio::io::AsyncWriteExt;
+
+    auth::require_admin(&user)?;
+
+    let path = db::scratch_path("restore");
+    let mut uploaded = false;
+    let res = async {
+        while let Some(mut field) = mp.next_field().await? {
+            if let Some("file") = field.name() {
+                let mut file = tokio::fs::File::create(&path).await?;
+                while let Some(chunk) = field.chunk().await? {
+                    file.write_all(&chunk).await?;
+                }
+                file.flush().await?;
+                uploaded = true;
+            }
+        }
+        if !uploaded {
+            return Err(AppError::bad_request(anyhow::anyhow!(
+                "No backup file uploaded"
+            )));
+        }
+        db::restore_from(&state.db, &path).await.map_err(|e| {
+            if e.downcast_ref::<db::InvalidBackup>().is_some() {
+                AppError::bad_request(e)
+            } else {
+                e.into()
+            }
+        })
+    }
+    .await;
+    if uploaded {
+        tokio::fs::remove_file(&path).await?;
+        // Left behind if the upload was a copy of a database in WAL mode
+        for sidecar in ["-wal", "-shm"] {
+            let mut sidecar_path = path.clone().into_os_string();
+            sidecar_path.push(sidecar);
+            let _ = tokio::fs::remove_file(sidecar_path).await;
+        }
+    }
+    res?;
+    tracing::info!("Database restored from backup");
+    Ok((axum_htmx::HxRedirect("/".to_owned()), ()))
+}
+
+// ── Accounts ─────────────────────────────────────────────────────────────────
+
+#[derive(serde::Deserialize)]
+pub(crate) struct LoginForm {
+    username: String,
+    password: String,
+}
+
+/// Logs in and sends the browser to the wine table.
+#[tracing::instrument(skip_all, fields(username = %form.username))]
+pub(crate) async fn login(
+    axum::extract::State(state): axum::extract::State<State>,
+    jar: axum_extra::extract::CookieJar,
+    axum::extract::Form(form): axum::extract::Form<LoginForm>,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let Some(user) = auth::check_login(&state.db, &form.username, &form.password).await? else {
+        tracing::warn!("Failed login");
+        return Err(AppError::unauthorized(anyhow::anyhow!(
+            "Wrong username or password"
+        )));
+    };
+    let cookie = auth::start_session(&state.db, &user).await?;
+    tracing::info!("Logged in");
+    Ok((jar.add(cookie), axum_htmx::HxRedirect("/".to_owned()), ()))
+}
+
+/// A new account's details, with the password typed twice.
+#[derive(serde::Deserialize)]
+pub(crate) struct NewUserForm {
+    username: String,
+    password: String,
+    confirm: String,
+    /// The checkbox in the add user form; the first account is always an admin.
+    admin: Option<String>,
+}
+
+/// Creates the first account on a fresh install and logs it in. Refused once there is one.
+#[tracing::instrument(skip_all, fields(username = %form.username))]
+pub(crate) async fn setup(
+    axum::extract::State(state): axum::extract::State<State>,
+    jar: axum_extra::extract::CookieJar,
+    axum::extract::Form(form): axum::extract::Form<NewUserForm>,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let username = auth::validate_username(&form.username)?;
+    let password = auth::validate_password(&form.password, &form.confirm)?;
+    let hash = auth::hash_password(password).await?;
+    let Some(user) = db::add_first_user(&state.db, username, &hash).await? else {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "There already is an account, log in instead"
+        )));
+    };
+    let cookie = auth::start_session(&state.db, &user).await?;
+    tracing::info!("First account created");
+    Ok((jar.add(cookie), axum_htmx::HxRedirect("/".to_owned()), ()))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn logout(
+    axum::extract::State(state): axum::extract::State<State>,
+    jar: axum_extra::extract::CookieJar,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let jar = auth::end_session(&state.db, jar).await?;
+    Ok((jar, axum_htmx::HxRedirect("/login".to_owned()), ()))
+}
+
+#[tracing::instrument(skip_all, fields(username = %form.username))]
+pub(crate) async fn add_user(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::Extension(current): axum::Extension<db::User>,
+    axum::extract::Form(form): axum::extract::Form<NewUserForm>,
+) -> MDResult {
+    auth::require_admin(&current)?;
+    let username = auth::validate_username(&form.username)?;
+    let password = auth::validate_password(&form.password, &form.confirm)?;
+    let hash = auth::hash_password(password).await?;
+    db::add_user(&state.db, username, &hash, form.admin.is_some())
+        .await
+        .map_err(|e| unique_violation(e, &format!("There is already a user called {username}")))?;
+    super::markup::users_html(&state, &current).await
+}
+
+#[tracing::instrument(skip(state, current))]
+pub(crate) async fn delete_user(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::Extension(current): axum::Extension<db::User>,
+    axum::extract::Path(user_id): axum::extract::Path<i64>,
+) -> MDResult {
+    auth::require_admin(&current)?;
+    if user_id == current.user_id {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "You can't delete your own account"
+        )));
+    }
+    db::delete_user(&state.db, user_id)
+        .await
+        .map_err(account_error)?;
+    super::markup::users_html(&state, &current).await
+}
+
+#[derive(serde::Deserialize)]
+pub(crate) struct PasswordForm {
+    current_password: String,
+    password: String,
+    confirm: String,
+}
+
+/// Changes the logged in user's password. Their other sessions are logged out.
+#[tracing::instrument(skip_all, fields(user_id = current.user_id))]
+pub(crate) async fn change_password(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::Extension(current): axum::Extension<db::User>,
+    jar: axum_extra::extract::CookieJar,
+    axum::extract::Form(form): axum::extract::Form<PasswordForm>,
+) -> MDResult {
+    let hash = db::user_password_hash(&state.db, current.user_id).await?;
+    if !auth::verify_password(&form.current_password, &hash).await? {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "The current password is wrong"
+        )));
+    }
+    let password = auth::validate_password(&form.password, &form.confirm)?;
+    let hash = auth::hash_password(password).await?;
+    let keep = auth::session_hash(&jar).unwrap_or_default();
+    db::set_password(&state.db, current.user_id, &hash, &keep).await?;
+    tracing::info!("Password changed");
+    Ok(maud::html! {
+        div class="alert alert-success" role="alert" { "Password changed." }
+    })
+}
+
+// ── Cellars ──────────────────────────────────────────────────────────────────
+
+#[derive(serde::Deserialize)]
+pub(crate) struct CellarForm {
+    name: String,
+}
+
+/// Adds a cellar owned by the admin creating it, and switches to it.
+#[tracing::instrument(skip_all, fields(name = %form.name))]
+pub(crate) async fn add_cellar(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::Extension(user): axum::Extension<db::User>,
+    jar: axum_extra::extract::CookieJar,
+    axum::extract::Form(form): axum::extract::Form<CellarForm>,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    auth::require_admin(&user)?;
+    let name = form.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Cellar name must be between 1 and 100 characters"
+        )));
+    }
+    let cellar = db::add_cellar(&state.db, name, user.user_id).await?;
+    if let Some(session) = auth::session_hash(&jar) {
+        db::set_session_cellar(&state.db, &session, cellar.cellar_id).await?;
+    }
+    tracing::info!("Added cellar {}", cellar.cellar_id);
+    Ok((axum_htmx::HxRedirect("/".to_owned()), ()))
+}
+
+#[derive(serde::Deserialize)]
+pub(crate) struct SwitchCellarForm {
+    cellar_id: i64,
+}
+
+/// Makes the browser's session work in another of the user's cellars.
+#[tracing::instrument(skip_all, fields(cellar_id = form.cellar_id))]
+pub(crate) async fn switch_cellar(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::Extension(user): axum::Extension<db::User>,
+    jar: axum_extra::extract::CookieJar,
+    axum::extract::Form(form): axum::extract::Form<SwitchCellarForm>,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let cellars = db::user_cellars(&state.db, user.user_id).await?;
+    if !cellars.iter().any(|c| c.cellar_id == form.cellar_id) {
+        return Err(AppError::forbidden(anyhow::anyhow!(
+            "You don't have access to that cellar"
+        )));
+    }
+    if let Some(session) = auth::session_hash(&jar) {
+        db::set_session_cellar(&state.db, &session, form.cellar_id).await?;
+    }
+    Ok((axum_htmx::HxRedirect("/".to_owned()), ()))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct MemberForm {
+    username: String,
+    role: String,
+}
+
+/// Gives a user a role in the current cellar, adding them to it if needed.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn set_member(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Form(form): axum::extract::Form<MemberForm>,
+) -> MDResult {
+    auth::require_owner(&ctx)?;
+    let role = form.role.parse().map_err(AppError::bad_request)?;
+    db::set_member(&state.db, ctx.cellar_id(), form.username.trim(), role)
+        .await
+        .map_err(account_error)?;
+    super::markup::members_html(&state, &ctx).await
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn remove_member(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(user_id): axum::extract::Path<i64>,
+) -> MDResult {
+    auth::require_owner(&ctx)?;
+    if user_id == ctx.user.user_id {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "You can't remove yourself from the cellar"
+        )));
+    }
+    db::remove_member(&state.db, ctx.cellar_id(), user_id)
+        .await
+        .map_err(account_error)?;
+    super::markup::members_html(&state, &ctx).await
+}
+
+/// A new share link: who it is for, and how many days it lasts (empty for no end).
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct ShareLinkForm {
+    label: String,
+    #[serde(default)]
+    days: String,
+}
+
+/// The longest a share link can be made to last; links without an end last until revoked.
+const MAX_SHARE_DAYS: i64 = 365;
+
+/// Makes a read-only link to the current cellar. Its address is only shown this once.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn add_share_link(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Form(form): axum::extract::Form<ShareLinkForm>,
+) -> MDResult {
+    auth::require_owner(&ctx)?;
+    let label = form.label.trim();
+    if label.is_empty() || label.chars().count() > 100 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Say who the link is for, in at most 100 characters"
+        )));
+    }
+    let days = match form.days.trim() {
+        "" => None,
+        days => match days.parse::<i64>() {
+            Ok(days) if (1..=MAX_SHARE_DAYS).contains(&days) => Some(days),
+            _ => {
+                return Err(AppError::bad_request(anyhow::anyhow!(
+                    "A link lasts between 1 and {MAX_SHARE_DAYS} days"
+                )));
+            }
+        },
+    };
+    let expires_at = days.map(|days| chrono::Utc::now().naive_utc() + chrono::Duration::days(days));
+    let token = auth::new_token();
+    let link = db::add_share_link(
+        &state.db,
+        ctx.cellar_id(),
+        &auth::token_hash(&token),
+        label,
+        expires_at,
+    )
+    .await?;
+    tracing::info!("Added share link {}", link.share_id);
+    super::markup::share_links_html(&state, &ctx, Some(&token)).await
+}
+
+/// Revokes a share link: it stops working at once.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn delete_share_link(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(share_id): axum::extract::Path<i64>,
+) -> MDResult {
+    auth::require_owner(&ctx)?;
+    db::delete_share_link(&state.db, ctx.cellar_id(), share_id).await?;
+    super::markup::share_links_html(&state, &ctx, None).await
+}
+
+/// The add and edit wine forms. Empty optional fields mean "not set".
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct WineForm {
     name: String,
     year: i64,
+    #[serde(default)]
+    style: String,
+    #[serde(default)]
+    producer: String,
+    #[serde(default)]
+    region_id: String,
+    #[serde(default)]
+    drink_from: String,
+    #[serde(default)]
+    drink_until: String,
+}
+
+impl WineForm {
+    async fn fields(&self, db: &sqlx::SqlitePool) -> Result<db::WineFields<'_>, AppError> {
+        let drink_from = parse_optional(&self.drink_from)?;
+        let drink_until = parse_optional(&self.drink_until)?;
+        validate_drinking_window(drink_from, drink_until)?;
+        Ok(db::WineFields {
+            name: validate_wine_name(&self.name)?,
+            year: self.year,
+            style: parse_optional(&self.style)?,
+            producer_id: resolve_producer(db, &self.producer).await?,
+            region_id: validate_region(db, parse_optional(&self.region_id)?).await?,
+            drink_from,
+            drink_until,
+        })
+    }
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn add_wine(
     axum::extract::State(state): axum::extract::State<State>,
-    axum::Form(wine): axum::Form<AddWine>,
+    ctx: auth::Context,
+    axum::Form(form): axum::Form<WineForm>,
 ) -> MDResult {
     tracing::info!("add_wine");
-    let wine = db::add_wine(&state.db, &wine.name, wine.year).await?;
+    let fields = form.fields(&state.db).await?;
+    let wine = db::add_wine(&state.db, ctx.cellar_id(), &ctx.user, &fields)
+        .await
+        .map_err(|e| unique_violation(e, &duplicate_wine_msg(fields.name, fields.year)))?;
     tracing::info!("Added: {wine:?}");
-    super::markup::wine_table_row(&state, wine, None).await
+    let row = db::wine_row(&state.db, ctx.cellar_id(), wine.wine_id).await?;
+    Ok(super::markup::wine_table_row(
+        &row,
+        state.score_scale,
+        super::markup::Audience::Members,
+    ))
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn update_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    axum::Form(form): axum::Form<WineForm>,
+) -> MDResult {
+    let fields = form.fields(&state.db).await?;
+    let wine = db::update_wine(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &fields)
+        .await
+        .map_err(|e| unique_violation(e, &duplicate_wine_msg(fields.name, fields.year)))?;
+    tracing::info!("Updated: {wine:?}");
+    super::markup::wine_table_populated(&state, ctx.cellar_id()).await
 }
 
-#[tracing::instrument(skip(state))]
+/// Moves the wine to the trash. The table row removes itself; the response is the toast
+/// offering to undo it.
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn delete_wine(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
 ) -> MDResult {
-    db::delete_wine(&state.db, wine_id).await?;
-    super::markup::wine_table_populated(&state).await
+    let wine = db::delete_wine(&state.db, ctx.cellar_id(), &ctx.user, wine_id).await?;
+    Ok(super::markup::trash_toast(Some(&wine)))
 }
 
-#[tracing::instrument(skip(state))]
+/// The toast's "Undo": takes the wine back out of the trash and shows it in the table.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn undo_delete_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    db::restore_wine(&state.db, ctx.cellar_id(), &ctx.user, wine_id).await?;
+    let table = super::markup::wine_table_populated(&state, ctx.cellar_id()).await?;
+    Ok(maud::html! {
+        (table)
+        (super::markup::trash_toast(None))
+    })
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn restore_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    db::restore_wine(&state.db, ctx.cellar_id(), &ctx.user, wine_id).await?;
+    super::markup::trash_html(&state, ctx.cellar_id()).await
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn purge_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    db::purge_wine(&state.db, ctx.cellar_id(), &ctx.user, wine_id).await?;
+    super::markup::trash_html(&state, ctx.cellar_id()).await
+}
+
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn post_wine_grapes(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
     axum::extract::Form(form): axum::extract::Form<std::collections::HashMap<String, String>>,
 ) -> MDResult {
     let grapes: Vec<_> = form.values().map(|v| v.as_ref()).collect();
-    db::set_wine_grapes(&state.db, wine_id, &grapes).await?;
-    super::markup::wine_table_populated(&state).await
+    db::set_wine_grapes(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &grapes).await?;
+    super::markup::wine_table_populated(&state, ctx.cellar_id()).await
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn get_note(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
 ) -> MDResult {
-    let wine = db::get_wine(&state.db, wine_id).await?;
+    let wine = db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
     Ok(super::markup::note_read_view(&wine))
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn get_note_edit(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
 ) -> MDResult {
-    let wine = db::get_wine(&state.db, wine_id).await?;
+    let wine = db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
     Ok(super::markup::note_edit_form(
         wine_id,
         wine.comment.as_deref().unwrap_or(""),
@@ -144,9 +923,10 @@ pub(crate) struct SaveComment {
     comment: String,
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn save_comment(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
     axum::extract::Form(form): axum::extract::Form<SaveComment>,
 ) -> MDResult {
@@ -156,9 +936,18 @@ pub(crate) async fn save_comment(
     } else {
         (Some(text), Some(chrono::Local::now().naive_local()))
     };
-    match db::set_wine_comment(&state.db, wine_id, comment_value, dt).await {
+    match db::set_wine_comment(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        wine_id,
+        comment_value,
+        dt,
+    )
+    .await
+    {
         Ok(()) => {
-            let wine = db::get_wine(&state.db, wine_id).await?;
+            let wine = db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
             Ok(super::markup::note_read_view(&wine))
         }
         Err(e) => Ok(super::markup::note_edit_form(
@@ -173,19 +962,37 @@ pub(crate) async fn save_comment(
 pub(crate) struct BuyWine {
     dt: String,
     bottles: i64,
+    /// Price per bottle, e.g. `249.50`.
+    #[serde(default)]
+    price: String,
+    #[serde(default)]
+    currency: String,
+    #[serde(default)]
+    vendor: String,
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn buy_wine(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
-    axum::extract::Form(event): axum::extract::Form<BuyWine>,
+    axum::extract::RawForm(form): axum::extract::RawForm,
 ) -> MDResult {
     tracing::info!("buy wine");
+    let (event, slots) = form_with_slots::<BuyWine>(&form)?;
     let date = chrono::NaiveDate::parse_from_str(&event.dt, "%Y-%m-%d")?;
     let dt = chrono::NaiveDateTime::new(date, chrono::Local::now().naive_local().time());
-    db::add_wine_event(&state.db, wine_id, event.bottles, dt).await?;
-    super::markup::wine_table_populated(&state).await
+    let bottles = validate_bottles(event.bottles)?;
+    let purchase = db::NewWineEvent {
+        price: validate_price(parse_amount(&event.price)?, &event.currency)?,
+        vendor: validate_vendor(&event.vendor)?,
+        slots: &slots,
+        ..db::NewWineEvent::new(db::EventKind::Purchase, bottles, dt)
+    };
+    db::add_wine_event(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &purchase)
+        .await
+        .map_err(inventory_error)?;
+    super::markup::wine_table_populated(&state, ctx.cellar_id()).await
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -194,20 +1001,375 @@ pub(crate) struct ConsumeWine {
     bottles: i64,
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn consume_wine(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
-    axum::extract::Form(event): axum::extract::Form<ConsumeWine>,
+    axum::extract::RawForm(form): axum::extract::RawForm,
 ) -> MDResult {
     tracing::info!("consume wine");
+    let (event, slots) = form_with_slots::<ConsumeWine>(&form)?;
     let date = chrono::NaiveDate::parse_from_str(&event.dt, "%Y-%m-%d")?;
     let dt = chrono::NaiveDateTime::new(date, chrono::Local::now().naive_local().time());
 
     // Consuming is negative bottles
-    let bottles = -event.bottles;
-    db::add_wine_event(&state.db, wine_id, bottles, dt).await?;
-    super::markup::wine_table_populated(&state).await
+    let bottles = -validate_bottles(event.bottles)?;
+    let consumption = db::NewWineEvent {
+        slots: &slots,
+        ..db::NewWineEvent::new(db::EventKind::Consumption, bottles, dt)
+    };
+    db::add_wine_event(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &consumption)
+        .await
+        .map_err(inventory_error)?;
+    super::markup::wine_table_populated(&state, ctx.cellar_id()).await
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct RecordEvent {
+    dt: String,
+    kind: String,
+    /// `in` when bottles come into the cellar, `out` when they leave it.
+    direction: String,
+    bottles: i64,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn record_event(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    axum::extract::Form(event): axum::extract::Form<RecordEvent>,
+) -> MDResult {
+    let date = chrono::NaiveDate::parse_from_str(&event.dt, "%Y-%m-%d")?;
+    let dt = chrono::NaiveDateTime::new(date, chrono::Local::now().naive_local().time());
+    let kind: db::EventKind = event.kind.parse().map_err(AppError::bad_request)?;
+    let bottles = signed_bottles(&event.direction, event.bottles)?;
+    db::add_wine_event(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        wine_id,
+        &db::NewWineEvent::new(kind, bottles, dt),
+    )
+    .await
+    .map_err(inventory_error)?;
+    super::markup::wine_table_populated(&state, ctx.cellar_id()).await
+}
+
+/// Signs a bottle count from the event forms: positive when they come `in`, negative when
+/// they go `out`.
+fn signed_bottles(direction: &str, bottles: i64) -> Result<i64, AppError> {
+    let bottles = validate_bottles(bottles)?;
+    match direction {
+        "in" => Ok(bottles),
+        "out" => Ok(-bottles),
+        other => Err(AppError::bad_request(anyhow::anyhow!(
+            "Unknown direction: {other}"
+        ))),
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct EditEvent {
+    dt: String,
+    kind: String,
+    /// `in` when bottles come into the cellar, `out` when they leave it.
+    direction: String,
+    bottles: i64,
+    #[serde(default)]
+    price: String,
+    #[serde(default)]
+    currency: String,
+    #[serde(default)]
+    vendor: String,
+}
+
+/// Saves a corrected event, keeping its time of day, and shows the wine again.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn update_event(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, event_id)): axum::extract::Path<(i64, i64)>,
+    axum::extract::Form(form): axum::extract::Form<EditEvent>,
+) -> MDResult {
+    let old = db::get_wine_event(&state.db, ctx.cellar_id(), wine_id, event_id).await?;
+    let date = chrono::NaiveDate::parse_from_str(&form.dt, "%Y-%m-%d")?;
+    let kind: db::EventKind = form.kind.parse().map_err(AppError::bad_request)?;
+    let event = db::NewWineEvent {
+        price: validate_price(parse_amount(&form.price)?, &form.currency)?,
+        vendor: validate_vendor(&form.vendor)?,
+        ..db::NewWineEvent::new(
+            kind,
+            signed_bottles(&form.direction, form.bottles)?,
+            chrono::NaiveDateTime::new(date, old.dt.time()),
+        )
+    };
+    db::update_wine_event(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        wine_id,
+        event_id,
+        &event,
+    )
+    .await
+    .map_err(inventory_error)?;
+    super::markup::wine_information_html(
+        &state,
+        ctx.cellar_id(),
+        wine_id,
+        super::markup::Audience::Members,
+    )
+    .await
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn delete_event(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, event_id)): axum::extract::Path<(i64, i64)>,
+) -> MDResult {
+    db::delete_wine_event(&state.db, ctx.cellar_id(), &ctx.user, wine_id, event_id)
+        .await
+        .map_err(inventory_error)?;
+    super::markup::wine_information_html(
+        &state,
+        ctx.cellar_id(),
+        wine_id,
+        super::markup::Audience::Members,
+    )
+    .await
+}
+
+// ── Tastings ─────────────────────────────────────────────────────────────────
+
+/// Trims a tasting's occasion, which is shown on one line in the timeline.
+pub(super) fn validate_occasion(occasion: &str) -> Result<Option<&str>, AppError> {
+    let occasion = occasion.trim();
+    if occasion.chars().count() > 100 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Occasion too long (max 100 characters)"
+        )));
+    }
+    Ok((!occasion.is_empty()).then_some(occasion))
+}
+
+/// The tasting sheet part of the tasting form: a select per [`db::SheetDimension`], empty
+/// when not assessed, and an `aroma` checkbox per aroma. `None` when nothing was filled in.
+fn sheet_from_form(form: &[u8]) -> Result<Option<db::TastingSheet>, AppError> {
+    let pairs: Vec<(String, String)> =
+        serde_urlencoded::from_bytes(form).map_err(AppError::bad_request)?;
+    let mut sheet = db::TastingSheet::default();
+    for (key, value) in pairs.into_iter().filter(|(_, value)| !value.is_empty()) {
+        if key == "aroma" {
+            sheet.aromas.push(value);
+        } else if let Some(dimension) = db::SheetDimension::ALL
+            .into_iter()
+            .find(|d| d.as_str() == key)
+        {
+            let level = value.parse().map_err(AppError::bad_request)?;
+            sheet.set_level(dimension, Some(level));
+        }
+    }
+    Ok((!sheet.is_empty()).then_some(sheet))
+}
+
+/// Checks a tasting sheet against the levels and the aroma vocabulary, shared by the tasting
+/// form and the JSON API.
+pub(super) async fn validate_sheet(
+    db: &sqlx::SqlitePool,
+    sheet: &db::TastingSheet,
+) -> Result<(), AppError> {
+    let vocabulary = db::aromas(db).await?;
+    sheet.validate(&vocabulary).map_err(AppError::bad_request)
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct AddTasting {
+    tasted_on: String,
+    score: i64,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    occasion: String,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn add_tasting(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    axum::extract::RawForm(raw): axum::extract::RawForm,
+) -> MDResult {
+    db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let form: AddTasting = serde_urlencoded::from_bytes(&raw).map_err(AppError::bad_request)?;
+    let sheet = sheet_from_form(&raw)?;
+    if let Some(sheet) = &sheet {
+        validate_sheet(&state.db, sheet).await?;
+    }
+    let note = form.note.trim();
+    let tasting = db::NewTasting {
+        tasted_on: chrono::NaiveDate::parse_from_str(&form.tasted_on, "%Y-%m-%d")?,
+        score: state
+            .score_scale
+            .validate(form.score)
+            .map_err(AppError::bad_request)?,
+        scale: state.score_scale.max(),
+        note: (!note.is_empty()).then_some(note),
+        occasion: validate_occasion(&form.occasion)?,
+        sheet: sheet.as_ref(),
+    };
+    db::add_tasting(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &tasting).await?;
+    super::markup::wine_information_html(
+        &state,
+        ctx.cellar_id(),
+        wine_id,
+        super::markup::Audience::Members,
+    )
+    .await
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn delete_tasting(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, tasting_id)): axum::extract::Path<(i64, i64)>,
+) -> MDResult {
+    db::delete_tasting(&state.db, ctx.cellar_id(), &ctx.user, wine_id, tasting_id).await?;
+    super::markup::wine_information_html(
+        &state,
+        ctx.cellar_id(),
+        wine_id,
+        super::markup::Audience::Members,
+    )
+    .await
+}
+
+// ── Storage locations ────────────────────────────────────────────────────────
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct AddLocation {
+    name: String,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn add_location(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Form(form): axum::extract::Form<AddLocation>,
+) -> MDResult {
+    let name = validate_storage_name(&form.name, "Location")?;
+    db::add_location(&state.db, ctx.cellar_id(), &ctx.user, name)
+        .await
+        .map_err(|e| unique_violation(e, &format!("There is already a location called {name}")))?;
+    super::markup::locations_html(&state, ctx.cellar_id()).await
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct AddRack {
+    name: String,
+    row_count: i64,
+    column_count: i64,
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn add_rack(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(location_id): axum::extract::Path<i64>,
+    axum::extract::Form(form): axum::extract::Form<AddRack>,
+) -> MDResult {
+    db::get_location(&state.db, ctx.cellar_id(), location_id).await?;
+    let name = validate_storage_name(&form.name, "Rack")?;
+    validate_rack_size(form.row_count, form.column_count)?;
+    db::add_rack(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        location_id,
+        name,
+        form.row_count,
+        form.column_count,
+    )
+    .await
+    .map_err(|e| unique_violation(e, &format!("There is already a rack called {name} here")))?;
+    super::markup::location_html(&state, ctx.cellar_id(), location_id).await
+}
+
+/// Puts bottles that are in stock but not in a rack into the ticked slots.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn place_bottles(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    axum::extract::RawForm(form): axum::extract::RawForm,
+) -> MDResult {
+    let slots = picked_slots(&form)?;
+    db::place_bottles(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &slots)
+        .await
+        .map_err(inventory_error)?;
+    super::markup::wine_information_html(
+        &state,
+        ctx.cellar_id(),
+        wine_id,
+        super::markup::Audience::Members,
+    )
+    .await
+}
+
+/// Takes a bottle out of its slot; it stays in stock.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn unplace_bottle(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, slot)): axum::extract::Path<(i64, String)>,
+) -> MDResult {
+    let slot = slot.parse().map_err(AppError::bad_request)?;
+    db::unplace_bottle(&state.db, ctx.cellar_id(), &ctx.user, wine_id, slot)
+        .await
+        .map_err(inventory_error)?;
+    super::markup::wine_information_html(
+        &state,
+        ctx.cellar_id(),
+        wine_id,
+        super::markup::Audience::Members,
+    )
+    .await
+}
+
+/// Moves a bottle to the picked slot, without changing the stock.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn move_bottle(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, from)): axum::extract::Path<(i64, String)>,
+    axum::extract::RawForm(form): axum::extract::RawForm,
+) -> MDResult {
+    let from = from.parse().map_err(AppError::bad_request)?;
+    let [to] = picked_slots(&form)?[..] else {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Pick the slot to move the bottle to"
+        )));
+    };
+    db::move_bottle(
+        &state.db,
+        ctx.cellar_id(),
+        &ctx.user,
+        wine_id,
+        from,
+        to,
+        chrono::Local::now().naive_local(),
+    )
+    .await
+    .map_err(inventory_error)?;
+    super::markup::wine_information_html(
+        &state,
+        ctx.cellar_id(),
+        wine_id,
+        super::markup::Audience::Members,
+    )
+    .await
 }
 
 fn parse_image(image_data: &[u8]) -> anyhow::Result<image::DynamicImage> {
@@ -231,9 +1393,10 @@ fn convert_image(image_data: &[u8], is_iphone: bool) -> anyhow::Result<Vec<u8>>
     png_encode_image(image)
 }
 
-#[tracing::instrument(skip(state, user_agent))]
+#[tracing::instrument(skip(state, ctx, user_agent))]
 pub(crate) async fn set_wine_image(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
     axum_extra::extract::TypedHeader(user_agent): axum_extra::extract::TypedHeader<
         headers::UserAgent,
@@ -275,10 +1438,10 @@ pub(crate) async fn set_wine_image(
             tracing::info!("Got image with size: {}", image_data.len());
             let is_iphone = user_agent.as_str().contains("iPhone");
             let image = convert_image(&image_data, is_iphone).context("Image conversion")?;
-            db::set_wine_image(&state.db, wine_id, &image).await?;
+            db::set_wine_image(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &image).await?;
         }
     }
-    super::markup::wine_table_populated(&state).await
+    super::markup::wine_table_populated(&state, ctx.cellar_id()).await
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -289,14 +1452,15 @@ pub struct EditImage {
     h: u32,
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn edit_image(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
     axum::Form(edit_image): axum::Form<EditImage>,
 ) -> MDResult {
     tracing::info!("edit_image");
-    let image_data = db::wine_image(&state.db, wine_id)
+    let image_data = db::wine_image(&state.db, ctx.cellar_id(), wine_id)
         .await?
         .ok_or(anyhow::anyhow!("No image data"))?;
     let image = parse_image(&image_data)?;
@@ -317,17 +1481,25 @@ pub(crate) async fn edit_image(
     let crop_h = edit_image.h.min(max_h);
     let image = image.crop_imm(edit_image.x, edit_image.y, crop_w, crop_h);
     let image_data = png_encode_image(image)?;
-    db::set_wine_image(&state.db, wine_id, &image_data).await?;
+    db::set_wine_image(&state.db, ctx.cellar_id(), &ctx.user, wine_id, &image_data).await?;
 
-    super::markup::wine_table_populated(&state).await
+    super::markup::wine_table_populated(&state, ctx.cellar_id()).await
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn wine_image(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
 ) -> std::result::Result<axum::response::Response, AppError> {
-    let img = db::wine_image(&state.db, wine_id).await?;
+    let img = db::wine_image(&state.db, ctx.cellar_id(), wine_id).await?;
+    image_response(img)
+}
+
+/// A wine's label image as a PNG, or a 404 if it has none.
+pub(super) fn image_response(
+    img: Option<Vec<u8>>,
+) -> std::result::Result<axum::response::Response, AppError> {
     if let Some(img) = img {
         axum::response::Response::builder()
             .status(axum::http::StatusCode::OK)
@@ -341,3 +1513,38 @@ pub(crate) async fn wine_image(
             .map_err(|e| e.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("").ok(), Some(None));
+        assert_eq!(parse_amount(" 249.50 ").ok().flatten(), Some(24950));
+        assert_eq!(parse_amount("249,5").ok().flatten(), Some(24950));
+        assert_eq!(parse_amount("12").ok().flatten(), Some(1200));
+        assert_eq!(parse_amount("0.05").ok().flatten(), Some(5));
+        for invalid in [
+            "-3",
+            "1.234",
+            ".5",
+            "1.2.3",
+            "12 kr",
+            "99999999999999999999",
+        ] {
+            assert!(parse_amount(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_validate_food_pairing() {
+        assert_eq!(
+            validate_food_pairing(" Fish and chips ").ok(),
+            Some("Fish and chips")
+        );
+        for invalid in ["", "  ", "Fish; chips", &"x".repeat(101)] {
+            assert!(validate_food_pairing(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! The cellar's history, read from the audit log: what the history page is filtered by,
+//! which travels in the query string like the wine table's state, and how a recorded change
+//! is shown field by field.
+
+use crate::db;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct HistoryQuery {
+    pub filter: db::AuditFilter,
+    /// Only entries older than this one, for the "Older" button.
+    pub older_than: Option<i64>,
+}
+
+fn number(key: &str, value: &str) -> anyhow::Result<i64> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid {key}: {value}"))
+}
+
+fn date(key: &str, value: &str) -> anyhow::Result<chrono::NaiveDate> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid {key}: {value}"))
+}
+
+impl HistoryQuery {
+    /// Parses a query string. Empty values and unknown keys are ignored.
+    pub(crate) fn parse(query: &str) -> anyhow::Result<Self> {
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(query)?;
+        let mut res = Self::default();
+        for (key, value) in pairs.iter().filter(|(_, value)| !value.is_empty()) {
+            let filter = &mut res.filter;
+            match key.as_str() {
+                "entity" => filter.entity = Some(value.parse()?),
+                "action" => filter.action = Some(value.parse()?),
+                "user" => filter.username = Some(value.clone()),
+                "wine" => filter.wine_id = Some(number(key, value)?),
+                "from" => filter.from = Some(date(key, value)?),
+                "until" => filter.until = Some(date(key, value)?),
+                "older_than" => res.older_than = Some(number(key, value)?),
+                _ => {}
+            }
+        }
+        Ok(res)
+    }
+
+    /// The query string [`HistoryQuery::parse`] reads back, leaving out what is unset.
+    pub(crate) fn query_string(&self) -> String {
+        let filter = &self.filter;
+        let pairs = [
+            ("entity", filter.entity.map(|e| e.as_str().to_owned())),
+            ("action", filter.action.map(|a| a.as_str().to_owned())),
+            ("user", filter.username.clone()),
+            ("wine", filter.wine_id.map(|id| id.to_string())),
+            ("from", filter.from.map(|d| d.to_string())),
+            ("until", filter.until.map(|d| d.to_string())),
+            ("older_than", self.older_than.map(|id| id.to_string())),
+        ];
+        let pairs: Vec<_> = pairs
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect();
+        serde_urlencoded::to_string(pairs).unwrap_or_default()
+    }
+}
+
+/// One field of a recorded change. `old` is `None` for what was added, `new` for what was
+/// deleted.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Ids say nothing to the reader and never change.
+const HIDDEN_FIELDS: [&str; 6] = [
+    "id",
+    "wine_id",
+    "event_id",
+    "tasting_id",
+    "location_id",
+    "rack_id",
+];
+
+/// The fields `entry` changed, or set or cleared when it added or deleted something.
+/// Snapshots that aren't objects, like a wine's list of grapes, are a single field named
+/// after the entity.
+pub(crate) fn field_changes(entry: &db::AuditEntry) -> Vec<FieldChange> {
+    let old = fields(entry.entity, entry.before.as_ref());
+    let new = fields(entry.entity, entry.after.as_ref());
+    let mut names: Vec<&String> = Vec::new();
+    for (name, _) in old.iter().chain(&new) {
+        if !names.contains(&name) && !HIDDEN_FIELDS.iter().any(|hidden| label(hidden) == *name) {
+            names.push(name);
+        }
+    }
+    let mut res = Vec::new();
+    for name in names {
+        let find = |fields: &[(String, &serde_json::Value)]| {
+            fields
+                .iter()
+                .find(|(n, _)| n == name)
+                .and_then(|(_, v)| display(v))
+        };
+        let (old, new) = (find(&old), find(&new));
+        if old != new {
+            res.push(FieldChange {
+                field: name.clone(),
+                old,
+                new,
+            });
+        }
+    }
+    res
+}
+
+/// A snapshot's fields by their labels.
+fn fields(
+    entity: db::AuditEntity,
+    snapshot: Option<&serde_json::Value>,
+) -> Vec<(String, &serde_json::Value)> {
+    match snapshot {
+        Some(serde_json::Value::Object(map)) => {
+            map.iter().map(|(key, value)| (label(key), value)).collect()
+        }
+        Some(value) => vec![(entity.label().to_owned(), value)],
+        None => Vec::new(),
+    }
+}
+
+/// `drink_from` as "Drink from".
+fn label(key: &str) -> String {
+    let mut label = key.replace('_', " ");
+    if let Some(first) = label.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    label
+}
+
+/// A snapshot's value as text, `None` when it is unset.
+fn display(value: &serde_json::Value) -> Option<String> {
+    use serde_json::Value;
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        Value::Array(items) if items.is_empty() => None,
+        Value::Array(items) if items.iter().all(|item| !item.is_object()) => Some(
+            items
+                .iter()
+                .filter_map(display)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        // What a deleted wine had of its pairings and events; the JSON has the details
+        Value::Array(items) => Some(format!("{} item(s)", items.len())),
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+    ) -> db::AuditEntry {
+        db::AuditEntry {
+            audit_id: 1,
+            dt: chrono::NaiveDateTime::default(),
+            username: "anna".to_owned(),
+            entity: db::AuditEntity::Wine,
+            entity_id: 1,
+            wine_id: Some(1),
+            wine: Some("Rioja 2015".to_owned()),
+            action: db::AuditAction::Update,
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let query = HistoryQuery {
+            filter: db::AuditFilter {
+                entity: Some(db::AuditEntity::Comment),
+                action: Some(db::AuditAction::Delete),
+                username: Some("anna b".to_owned()),
+                wine_id: Some(7),
+                from: Some(chrono::NaiveDate::from_ymd_opt(2026, 1, 31).unwrap()),
+                until: None,
+            },
+            older_than: Some(42),
+        };
+        let qs = query.query_string();
+        assert_eq!(HistoryQuery::parse(&qs).unwrap(), query);
+        assert_eq!(HistoryQuery::default().query_string(), "");
+
+        let form = HistoryQuery::parse("entity=&action=&user=&from=&until=2026-02-01").unwrap();
+        assert_eq!(
+            form.filter.until,
+            chrono::NaiveDate::from_ymd_opt(2026, 2, 1)
+        );
+        assert!(HistoryQuery::parse("entity=cork").is_err());
+        assert!(HistoryQuery::parse("from=yesterday").is_err());
+    }
+
+    #[test]
+    fn test_field_changes() {
+        let update = entry(
+            Some(serde_json::json!({
+                "wine_id": 1, "name": "Rioja", "drink_from": null, "year": 2015
+            })),
+            Some(serde_json::json!({
+                "wine_id": 1, "name": "Rioja Reserva", "drink_from": 2022, "year": 2015
+            })),
+        );
+        assert_eq!(
+            field_changes(&update),
+            [
+                FieldChange {
+                    field: "Drink from".to_owned(),
+                    old: None,
+                    new: Some("2022".to_owned()),
+                },
+                FieldChange {
+                    field: "Name".to_owned(),
+                    old: Some("Rioja".to_owned()),
+                    new: Some("Rioja Reserva".to_owned()),
+                },
+            ]
+        );
+
+        let grapes = db::AuditEntry {
+            entity: db::AuditEntity::Grapes,
+            ..entry(Some(serde_json::json!(["Merlot"])), None)
+        };
+        assert_eq!(
+            field_changes(&grapes),
+            [FieldChange {
+                field: "Grapes".to_owned(),
+                old: Some("Merlot".to_owned()),
+                new: None,
+            }]
+        );
+
+        let deleted = entry(
+            Some(serde_json::json!({"name": "Rioja", "pairings": [{"id": 1, "food": "lamb"}]})),
+            None,
+        );
+        let changes = field_changes(&deleted);
+        assert_eq!(changes[1].old.as_deref(), Some("1 item(s)"));
+    }
+}
@@ -0,0 +1,343 @@
+//! CSV export and import of the whole cellar. The file has one row per wine with the columns
+//! in [`HEADER`]; grapes and pairings are `;`-separated lists. Importing matches wines on
+//! `(name, year)` and treats the `bottles` column as the current count, so importing an
+//! unmodified export changes nothing.
+
+use crate::db;
+
+pub(crate) const HEADER: [&str; 6] = ["name", "year", "bottles", "grapes", "pairings", "note"];
+
+/// Separates the items of a list column, so pairings can't contain it.
+pub(crate) const LIST_SEPARATOR: char = ';';
+
+/// Serialises the wine table rows to CSV, header included.
+pub(crate) fn export(rows: &[db::WineRow]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(HEADER)?;
+    let separator = format!("{LIST_SEPARATOR} ");
+    for row in rows {
+        writer.write_record([
+            row.name.as_str(),
+            &row.year.to_string(),
+            &row.bottles.to_string(),
+            &row.grapes.join(&separator),
+            &row.pairings.join(&separator),
+            row.comment.as_deref().unwrap_or(""),
+        ])?;
+    }
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+#[derive(Debug)]
+pub(crate) struct LineError {
+    pub line: u64,
+    pub message: String,
+}
+
+/// A change to a single wine, tagged with the CSV line it came from.
+#[derive(Debug)]
+pub(crate) struct PlannedImport {
+    pub line: u64,
+    pub import: db::WineImport,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ImportPlan {
+    pub changes: Vec<PlannedImport>,
+    pub errors: Vec<LineError>,
+    /// Lines that match the cellar exactly and need no change.
+    pub unchanged: usize,
+}
+
+/// Column positions found in the header row. Only `name` and `year` are mandatory.
+struct Columns {
+    name: usize,
+    year: usize,
+    bottles: Option<usize>,
+    grapes: Option<usize>,
+    pairings: Option<usize>,
+    note: Option<usize>,
+}
+
+/// A successfully parsed CSV line, before it is compared with the cellar.
+struct ImportLine {
+    name: String,
+    year: i64,
+    bottles: Option<i64>,
+    grapes: Vec<String>,
+    pairings: Vec<String>,
+    note: Option<String>,
+}
+
+fn field(record: &csv::StringRecord, col: Option<usize>) -> &str {
+    col.and_then(|c| record.get(c)).unwrap_or("")
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(LIST_SEPARATOR)
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+fn parse_line(
+    record: &csv::StringRecord,
+    cols: &Columns,
+    known_grapes: &[String],
+) -> Result<ImportLine, String> {
+    let name = field(record, Some(cols.name));
+    if name.is_empty() {
+        return Err("Name cannot be empty".to_owned());
+    }
+    let year = field(record, Some(cols.year));
+    let year = year
+        .parse()
+        .map_err(|_| format!("Invalid year \"{year}\""))?;
+    let bottles = match field(record, cols.bottles) {
+        "" => None,
+        b => match b.parse::<i64>() {
+            Ok(b) if b >= 0 => Some(b),
+            _ => return Err(format!("Invalid bottle count \"{b}\"")),
+        },
+    };
+
+    let mut grapes: Vec<String> = Vec::new();
+    for grape in split_list(field(record, cols.grapes)) {
+        let known = known_grapes
+            .iter()
+            .find(|g| g.to_lowercase() == grape.to_lowercase())
+            .ok_or_else(|| format!("Unknown grape \"{grape}\""))?;
+        if !grapes.contains(known) {
+            grapes.push(known.clone());
+        }
+    }
+
+    let mut pairings: Vec<String> = Vec::new();
+    for food in split_list(field(record, cols.pairings)) {
+        if food.len() > 100 {
+            return Err("Food pairing must be 100 characters or less".to_owned());
+        }
+        if !pairings
+            .iter()
+            .any(|p| p.to_lowercase() == food.to_lowercase())
+        {
+            pairings.push(food.to_owned());
+        }
+    }
+
+    let note = Some(field(record, cols.note))
+        .filter(|n| !n.is_empty())
+        .map(str::to_owned);
+
+    Ok(ImportLine {
+        name: name.to_owned(),
+        year,
+        bottles,
+        grapes,
+        pairings,
+        note,
+    })
+}
+
+/// Works out what importing `line` would change, given the wine's current row if it exists.
+/// Returns `None` when the cellar already matches the line.
+fn diff_line(line: ImportLine, existing: Option<&db::WineRow>) -> Option<db::WineImport> {
+    let Some(row) = existing else {
+        return Some(db::WineImport {
+            wine_id: None,
+            name: line.name,
+            year: line.year,
+            bottles_delta: line.bottles.unwrap_or(0),
+            grapes: line.grapes,
+            pairings: line.pairings,
+            note: line.note,
+        });
+    };
+    let import = db::WineImport {
+        wine_id: Some(row.wine_id),
+        bottles_delta: line.bottles.map_or(0, |b| b - row.bottles),
+        grapes: line
+            .grapes
+            .into_iter()
+            .filter(|g| !row.grapes.contains(g))
+            .collect(),
+        pairings: line
+            .pairings
+            .into_iter()
+            .filter(|p| {
+                !row.pairings
+                    .iter()
+                    .any(|rp| rp.to_lowercase() == p.to_lowercase())
+            })
+            .collect(),
+        note: line.note.filter(|n| row.comment.as_ref() != Some(n)),
+        name: line.name,
+        year: line.year,
+    };
+    let unchanged = import.bottles_delta == 0
+        && import.grapes.is_empty()
+        && import.pairings.is_empty()
+        && import.note.is_none();
+    (!unchanged).then_some(import)
+}
+
+/// Parses `data` and compares it with the current cellar. Fails only when the header row is
+/// unusable; problems with individual lines are collected in [`ImportPlan::errors`].
+pub(crate) fn plan(
+    data: &[u8],
+    existing: &[db::WineRow],
+    known_grapes: &[String],
+) -> anyhow::Result<ImportPlan> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    let headers: Vec<String> = reader.headers()?.iter().map(str::to_lowercase).collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let (Some(name), Some(year)) = (column("name"), column("year")) else {
+        anyhow::bail!("The CSV must start with a header row containing \"name\" and \"year\"");
+    };
+    let cols = Columns {
+        name,
+        year,
+        bottles: column("bottles"),
+        grapes: column("grapes"),
+        pairings: column("pairings"),
+        note: column("note"),
+    };
+
+    let mut plan = ImportPlan::default();
+    let mut seen = std::collections::HashSet::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                plan.errors.push(LineError {
+                    line: e.position().map_or(0, |p| p.line()),
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let parsed = match parse_line(&record, &cols, known_grapes) {
+            Ok(parsed) => parsed,
+            Err(message) => {
+                plan.errors.push(LineError { line, message });
+                continue;
+            }
+        };
+        if !seen.insert((parsed.name.clone(), parsed.year)) {
+            plan.errors.push(LineError {
+                line,
+                message: format!(
+                    "\"{}\" from {} appears more than once",
+                    parsed.name, parsed.year
+                ),
+            });
+            continue;
+        }
+        let row = existing
+            .iter()
+            .find(|r| r.name == parsed.name && r.year == parsed.year);
+        match diff_line(parsed, row) {
+            Some(import) => plan.changes.push(PlannedImport { line, import }),
+            None => plan.unchanged += 1,
+        }
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(wine_id: i64, name: &str, year: i64, bottles: i64) -> db::WineRow {
+        db::WineRow {
+            wine_id,
+            name: name.to_owned(),
+            year,
+            style: None,
+            producer: None,
+            origin: None,
+            drink_from: None,
+            drink_until: None,
+            has_image: false,
+            comment: Some("Lovely".to_owned()),
+            bottles,
+            grapes: vec!["Merlot".to_owned()],
+            pairings: vec!["Lamb".to_owned()],
+            score: None,
+            last_activity: None,
+        }
+    }
+
+    fn grapes() -> Vec<String> {
+        vec!["Merlot".to_owned(), "Cabernet franc".to_owned()]
+    }
+
+    #[test]
+    fn test_export_roundtrip_is_noop() {
+        let rows = [row(1, "Château, \"Le\" Pin", 2015, 3)];
+        let data = export(&rows).unwrap();
+        let plan = plan(&data, &rows, &grapes()).unwrap();
+        assert!(plan.errors.is_empty(), "{:?}", plan.errors);
+        assert!(plan.changes.is_empty());
+        assert_eq!(plan.unchanged, 1);
+    }
+
+    #[test]
+    fn test_export_roundtrip_keeps_lists() {
+        let mut wine = row(1, "Rioja", 2015, 3);
+        wine.grapes = grapes();
+        wine.pairings = vec!["Fish and chips".to_owned(), "Duck".to_owned()];
+        let data = export(std::slice::from_ref(&wine)).unwrap();
+        let plan = plan(&data, &[], &grapes()).unwrap();
+        assert!(plan.errors.is_empty(), "{:?}", plan.errors);
+        let import = &plan.changes[0].import;
+        assert_eq!(import.grapes, wine.grapes);
+        assert_eq!(import.pairings, wine.pairings);
+    }
+
+    #[test]
+    fn test_plan_new_and_updated() {
+        let rows = [row(1, "Existing", 2015, 3)];
+        let data = b"name,year,bottles,grapes,pairings\n\
+            Existing,2015,5,merlot; cabernet franc,lamb; duck\n\
+            New,2020,6,,\n";
+        let plan = plan(data, &rows, &grapes()).unwrap();
+        assert!(plan.errors.is_empty(), "{:?}", plan.errors);
+        assert_eq!(plan.changes.len(), 2);
+
+        let updated = &plan.changes[0].import;
+        assert_eq!(updated.wine_id, Some(1));
+        assert_eq!(updated.bottles_delta, 2);
+        assert_eq!(updated.grapes, ["Cabernet franc"]);
+        assert_eq!(updated.pairings, ["duck"]);
+        assert!(updated.note.is_none());
+
+        let new = &plan.changes[1].import;
+        assert_eq!(new.wine_id, None);
+        assert_eq!(new.bottles_delta, 6);
+        assert_eq!(plan.changes[1].line, 3);
+    }
+
+    #[test]
+    fn test_plan_line_errors() {
+        let data = b"name,year,bottles,grapes\n\
+            ,2015,1,\n\
+            Wine,twenty,1,\n\
+            Wine,2015,-1,\n\
+            Wine,2016,1,Unobtainium\n\
+            Wine,2017,1,\n\
+            Wine,2017,2,\n";
+        let plan = plan(data, &[], &grapes()).unwrap();
+        let lines: Vec<_> = plan.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [2, 3, 4, 5, 7]);
+        assert_eq!(plan.changes.len(), 1);
+    }
+
+    #[test]
+    fn test_plan_requires_header() {
+        assert!(plan(b"Wine,2015\n", &[], &grapes()).is_err());
+    }
+}
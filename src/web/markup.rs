@@ -1,9 +1,14 @@
-use super::State;
-use crate::{db, web::MDResult};
+use super::{State, auth};
+use crate::{
+    db,
+    web::{MDResult, history::HistoryQuery, table_query::TableQuery},
+};
 use chrono::Datelike;
 use maud::Markup;
 
 pub(crate) mod image;
+mod rack_map;
+mod radar;
 
 const TRACE_SCRIPT: &str = r#"
 document.body.addEventListener("htmx:afterRequest", (e) => {
@@ -18,24 +23,68 @@ if (window.currentTraceparent) {
 });
 "#;
 
-#[tracing::instrument]
-pub(crate) async fn index() -> Markup {
-    use maud::DOCTYPE;
-    maud::html! {
-     (DOCTYPE)
-     meta name="viewport" content="width=device-width, initial-scale=1";
-     meta charset="utf-8";
-     link href="https://cdn.jsdelivr.net/npm/bootstrap@5.3.8/dist/css/bootstrap.min.css"
-         rel="stylesheet" integrity="sha384-sRIl4kxILFvY47J16cr9ZwB07vP4J8+LH7qKQnuqkuIAvNWLzeN8tE5YBujZqJLB"
-         crossorigin="anonymous";
-     script src="https://cdn.jsdelivr.net/npm/htmx.org@2.0.8/dist/htmx.min.js" {}
-     script src="https://cdn.jsdelivr.net/npm/htmx-ext-response-targets@2.0.4" integrity="sha384-T41oglUPvXLGBVyRdZsVRxNWnOOqCynaPubjUVjxhsjFTKrFJGEMm3/0KGmNQ+Pg" crossorigin="anonymous" {}
+#[tracing::instrument(skip(state))]
+/// The page around everything else. Its query string is the wine table's, see [`TableQuery`].
+/// Users who haven't been given a cellar yet only get the admin page.
+pub(crate) async fn index(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: Option<auth::Context>,
+    axum::Extension(user): axum::Extension<db::User>,
+    axum::extract::RawQuery(raw): axum::extract::RawQuery,
+) -> MDResult {
+    let query = table_query(raw.as_deref())?;
+    let producers = match &ctx {
+        Some(ctx) => db::producers(&state.db, ctx.cellar_id()).await?,
+        None => Vec::new(),
+    };
+    let regions = db::regions(&state.db).await?;
+    let cellars = db::user_cellars(&state.db, user.user_id).await?;
+    Ok(maud::html! {
+     (document_head())
      body hx-ext="response-targets" {
+       nav class="navbar bg-body-tertiary mb-3" {
+         div class="container" {
+           a class="navbar-brand" href="/" { "Wine Cellar" }
+           form class="d-flex" role="search" hx-get="/search" hx-target="#main" hx-target-error="#error"
+             hx-trigger="input changed delay:300ms from:find input, submit" {
+             input name="q" type="search" class="form-control" placeholder="Search wines, notes, grapes…"
+               aria-label="Search" {}
+           }
+           div class="d-flex align-items-center gap-2" {
+             @if let Some(ctx) = &ctx {
+               @if cellars.len() > 1 {
+                 select name="cellar_id" class="form-select form-select-sm w-auto" aria-label="Cellar"
+                   hx-post="/cellars/switch" hx-trigger="change" hx-target-error="#error" {
+                   @for cellar in &cellars {
+                     option value=(cellar.cellar_id) selected[cellar.cellar_id == ctx.cellar_id()] {
+                       (cellar.name)
+                     }
+                   }
+                 }
+               } @else {
+                 span class="navbar-text" { (ctx.cellar.name) " ·" }
+               }
+             }
+             span class="navbar-text" { (user.username) }
+             button class="btn btn-outline-secondary btn-sm" hx-post="/logout" { "Log out" }
+           }
+         }
+       }
        div id="main" class="container" {
          div id="error" {}
-         div hx-get="/wines" hx-trigger="load" hx-target="#main" hx-target-error="#error" {}
+         @if ctx.is_some() {
+           div hx-get=(format!("/wines?{}", query.query_string())) hx-trigger="load"
+             hx-target="#main" hx-target-error="#error" {}
+         } @else {
+           div class="alert alert-info" role="alert" {
+             "You don't have access to a cellar yet. Ask the owner of one to add you to it."
+           }
+           button class="btn btn-outline-secondary" hx-get="/admin" hx-target="#main"
+             hx-target-error="#error" { "Admin" }
+         }
        }
-       (add_wine_modal())
+       (add_wine_modal(&producers, &regions))
+       (trash_toast(None))
        script src="https://cdn.jsdelivr.net/npm/bootstrap@5.3.8/dist/js/bootstrap.bundle.min.js"
          integrity="sha384-FKyoEForCGlyvwx9Hj09JcYn3nv7wiPVlz7YYwJrWVcXK/BmnVDxM+D2scQbITxI"
          crossorigin="anonymous" {}
@@ -43,16 +92,283 @@ pub(crate) async fn index() -> Markup {
            (maud::PreEscaped(TRACE_SCRIPT))
        }
       }
+    })
+}
+
+/// The page a share link opens: the cellar's wine table, read-only. Its query string is the
+/// wine table's, see [`TableQuery`].
+pub(crate) fn shared_index(
+    cellar: &db::SharedCellar,
+    audience: Audience,
+    raw: Option<&str>,
+) -> MDResult {
+    let query = table_query(raw)?;
+    Ok(maud::html! {
+     (document_head())
+     // The link is as good as a password; keep it out of search engines and referrers
+     meta name="robots" content="noindex";
+     meta name="referrer" content="no-referrer";
+     title { (cellar.name) }
+     body hx-ext="response-targets" {
+       nav class="navbar bg-body-tertiary mb-3" {
+         div class="container" {
+           a class="navbar-brand" href=(audience.home()) { (cellar.name) }
+           span class="navbar-text" { "Shared with you, read-only" }
+         }
+       }
+       div id="main" class="container" {
+         div id="error" {}
+         div hx-get=(audience.url(&format!("/wines?{}", query.query_string()))) hx-trigger="load"
+           hx-target="#main" hx-target-error="#error" {}
+       }
+       script src="https://cdn.jsdelivr.net/npm/bootstrap@5.3.8/dist/js/bootstrap.bundle.min.js"
+         integrity="sha384-FKyoEForCGlyvwx9Hj09JcYn3nv7wiPVlz7YYwJrWVcXK/BmnVDxM+D2scQbITxI"
+         crossorigin="anonymous" {}
+     }
+    })
+}
+
+/// Everything before `<body>`, shared by the index, the shared cellar and the login page.
+fn document_head() -> Markup {
+    maud::html! {
+     (maud::DOCTYPE)
+     meta name="viewport" content="width=device-width, initial-scale=1";
+     meta charset="utf-8";
+     link href="https://cdn.jsdelivr.net/npm/bootstrap@5.3.8/dist/css/bootstrap.min.css"
+         rel="stylesheet" integrity="sha384-sRIl4kxILFvY47J16cr9ZwB07vP4J8+LH7qKQnuqkuIAvNWLzeN8tE5YBujZqJLB"
+         crossorigin="anonymous";
+     script src="https://cdn.jsdelivr.net/npm/htmx.org@2.0.8/dist/htmx.min.js" {}
+     script src="https://cdn.jsdelivr.net/npm/htmx-ext-response-targets@2.0.4" integrity="sha384-T41oglUPvXLGBVyRdZsVRxNWnOOqCynaPubjUVjxhsjFTKrFJGEMm3/0KGmNQ+Pg" crossorigin="anonymous" {}
     }
 }
 
+/// The login page. On a fresh install, with no accounts yet, it creates the first one
+/// instead.
+#[tracing::instrument(skip(state))]
+pub(crate) async fn login_page(
+    axum::extract::State(state): axum::extract::State<State>,
+) -> MDResult {
+    let setup = !db::has_users(&state.db).await?;
+    Ok(maud::html! {
+     (document_head())
+     title { "Wine Cellar" }
+     body hx-ext="response-targets" {
+       div class="container" style="max-width: 24rem" {
+         h1 class="display-5 my-4" { "Wine Cellar" }
+         div id="error" {}
+         @if setup {
+           p { "Create the first account to get started." }
+           form hx-post="/setup" hx-target-error="#error" {
+             div class="mb-3" {
+               label for="username" class="form-label" { "Username" }
+               input type="text" name="username" id="username" class="form-control"
+                 autocomplete="username" required autofocus {}
+             }
+             div class="mb-3" {
+               label for="password" class="form-label" { "Password" }
+               input type="password" name="password" id="password" class="form-control"
+                 autocomplete="new-password" required {}
+             }
+             div class="mb-3" {
+               label for="confirm" class="form-label" { "Repeat password" }
+               input type="password" name="confirm" id="confirm" class="form-control"
+                 autocomplete="new-password" required {}
+             }
+             input type="submit" class="btn btn-primary" value="Create account" {}
+           }
+         } @else {
+           form hx-post="/login" hx-target-error="#error" {
+             div class="mb-3" {
+               label for="username" class="form-label" { "Username" }
+               input type="text" name="username" id="username" class="form-control"
+                 autocomplete="username" required autofocus {}
+             }
+             div class="mb-3" {
+               label for="password" class="form-label" { "Password" }
+               input type="password" name="password" id="password" class="form-control"
+                 autocomplete="current-password" required {}
+             }
+             input type="submit" class="btn btn-primary" value="Log in" {}
+           }
+         }
+       }
+     }
+    })
+}
+
 fn page_header(header: &str) -> Markup {
     maud::html! {
         h1 class="display-1" {(header)}
     }
 }
 
-fn add_wine_modal() -> Markup {
+/// A `<select name="style">` with every [`db::WineStyle`] and a blank first option, which
+/// the handlers read as "no style".
+fn style_select(selected: Option<db::WineStyle>) -> Markup {
+    maud::html! {
+        select name="style" id="style" class="form-select" {
+            option value="" selected[selected.is_none()] { "Unknown" }
+            @for style in db::WineStyle::ALL {
+                option value=(style.as_str()) selected[selected == Some(style)] { (style.label()) }
+            }
+        }
+    }
+}
+
+/// Background and text colour for a style, shared by the badges and the rack maps.
+fn style_colours(style: db::WineStyle) -> (&'static str, &'static str) {
+    match style {
+        db::WineStyle::Red => ("#7b1e2b", "white"),
+        db::WineStyle::White => ("#f1e6a8", "black"),
+        db::WineStyle::Rose => ("#f4b6c2", "black"),
+        db::WineStyle::Sparkling => ("#d8e2a6", "black"),
+        db::WineStyle::Fortified => ("#5c2a1a", "white"),
+        db::WineStyle::Dessert => ("#d9a441", "black"),
+    }
+}
+
+fn style_badge(style: db::WineStyle) -> Markup {
+    let (background, text) = style_colours(style);
+    maud::html! {
+        span class="badge" style=(format!("background-color: {background}; color: {text}")) {
+            (style.label())
+        }
+    }
+}
+
+/// A free-text producer input that suggests the producers already in the cellar.
+fn producer_input(producers: &[db::Producer], value: Option<&str>) -> Markup {
+    maud::html! {
+        input name="producer" id="producer" class="form-control" list="producerList"
+            value=[value] {}
+        datalist id="producerList" {
+            @for producer in producers {
+                option value=(producer.name) {}
+            }
+        }
+    }
+}
+
+/// The `<option>`s of a region select, indented to show the country → region → appellation
+/// hierarchy. [`db::regions`] returns them in the right order.
+fn region_options(regions: &[db::Region], selected: Option<i64>) -> Markup {
+    maud::html! {
+        @for region in regions {
+            @let indent = match region.kind {
+                db::RegionKind::Country => "",
+                db::RegionKind::Region => "\u{2003}",
+                db::RegionKind::Appellation => "\u{2003}\u{2003}",
+            };
+            option value=(region.region_id) selected[selected == Some(region.region_id)] {
+                (indent) (region.name)
+            }
+        }
+    }
+}
+
+fn region_select(regions: &[db::Region], selected: Option<i64>) -> Markup {
+    maud::html! {
+        select name="region_id" id="region_id" class="form-select" {
+            option value="" selected[selected.is_none()] { "Unknown" }
+            (region_options(regions, selected))
+        }
+    }
+}
+
+/// "2024–2030", "from 2024" or "until 2030", or `None` when the wine has no drinking window.
+fn drinking_window_label(drink_from: Option<i64>, drink_until: Option<i64>) -> Option<String> {
+    match (drink_from, drink_until) {
+        (Some(from), Some(until)) => Some(format!("{from}–{until}")),
+        (Some(from), None) => Some(format!("from {from}")),
+        (None, Some(until)) => Some(format!("until {until}")),
+        (None, None) => None,
+    }
+}
+
+/// Formats an amount in minor units, e.g. "249.50 SEK". Every currency is assumed to have
+/// two decimals.
+fn format_money(amount: i64, currency: &str) -> String {
+    let sign = if amount < 0 { "-" } else { "" };
+    let amount = amount.abs();
+    format!("{sign}{}.{:02} {currency}", amount / 100, amount % 100)
+}
+
+/// "Cellar › Rack A, row 2, column 3"
+fn bottle_place(bottle: &db::PlacedBottle) -> String {
+    format!(
+        "{} › {}, row {}, column {}",
+        bottle.location, bottle.rack, bottle.row, bottle.col
+    )
+}
+
+/// A grid of checkboxes named `slot` for every rack, or radio buttons when only one slot may
+/// be picked. With `pick_from`, only the slots holding that wine can be ticked and racks
+/// without any are left out; otherwise only empty slots can.
+fn slot_picker(
+    racks: &[db::Rack],
+    bottles: &[db::PlacedBottle],
+    pick_from: Option<i64>,
+    single: bool,
+) -> Markup {
+    let occupied: std::collections::HashMap<_, _> = bottles.iter().map(|b| (b.slot(), b)).collect();
+    let selectable = |bottle: Option<&&db::PlacedBottle>| match pick_from {
+        Some(wine_id) => bottle.is_some_and(|b| b.wine_id == wine_id),
+        None => bottle.is_none(),
+    };
+    maud::html! {
+        @for rack in racks {
+            @if pick_from.is_none() || bottles.iter().any(|b| b.rack_id == rack.rack_id) {
+                div class="mb-2" {
+                    div class="small fw-semibold" { (rack.location) " › " (rack.name) }
+                    table class="table table-sm table-bordered text-center w-auto mb-0" {
+                        tr {
+                            th {}
+                            @for col in 1..=rack.column_count {
+                                th class="small text-body-secondary" { (col) }
+                            }
+                        }
+                        @for row in 1..=rack.row_count {
+                            tr {
+                                th class="small text-body-secondary" { (row) }
+                                @for col in 1..=rack.column_count {
+                                    @let slot = db::Slot { rack_id: rack.rack_id, row, col };
+                                    @let bottle = occupied.get(&slot);
+                                    td {
+                                        @if selectable(bottle) {
+                                            input type=(if single { "radio" } else { "checkbox" })
+                                                class="form-check-input"
+                                                name="slot" value=(slot)
+                                                aria-label=(format!("Row {row}, column {col}")) {}
+                                        } @else if let Some(b) = bottle {
+                                            span class="text-body-secondary"
+                                                title=(format!("{} {}", b.name, b.year)) { "●" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn drinking_window_inputs(drink_from: Option<i64>, drink_until: Option<i64>) -> Markup {
+    maud::html! {
+        div class="mb-3" {
+            label class="form-label" { "Drinking window" }
+            div class="input-group" {
+                input name="drink_from" type="number" class="form-control" placeholder="From"
+                    aria-label="Drink from" value=[drink_from] {}
+                input name="drink_until" type="number" class="form-control" placeholder="Until"
+                    aria-label="Drink until" value=[drink_until] {}
+            }
+        }
+    }
+}
+
+fn add_wine_modal(producers: &[db::Producer], regions: &[db::Region]) -> Markup {
     let this_year = chrono::Local::now().year();
     maud::html! {
         div class="modal" id="addWineModal" tabindex="-1" {
@@ -73,6 +389,19 @@ fn add_wine_modal() -> Markup {
                                 label for="year" class="form-label" { "Year" }
                                 input name="year" id="year" class="form-control" type="number" value=(this_year) {}
                             }
+                            div class="mb-3" {
+                                label for="style" class="form-label" { "Style" }
+                                (style_select(None))
+                            }
+                            div class="mb-3" {
+                                label for="producer" class="form-label" { "Producer" }
+                                (producer_input(producers, None))
+                            }
+                            div class="mb-3" {
+                                label for="region_id" class="form-label" { "Region" }
+                                (region_select(regions, None))
+                            }
+                            (drinking_window_inputs(None, None))
                         }
                         div class="modal-footer" {
                             button type="button" class="btn btn-secondary" data-bs-dismiss="modal" { "Close " }
@@ -85,72 +414,102 @@ fn add_wine_modal() -> Markup {
     }
 }
 
-#[tracing::instrument(skip(state))]
-pub(crate) async fn wine_table_row(
-    state: &crate::web::StateInner,
-    wine: crate::db::Wine,
-    grape_filter: Option<&str>,
-) -> MDResult {
-    tracing::info!("Rendering row for {}", wine.name);
-    let wine_grapes = db::get_wine_grapes(&state.db, wine.wine_id).await?;
-    if let Some(grape_filter) = grape_filter.map(|gf| gf.to_lowercase())
-        && !grape_filter.is_empty()
-        && !wine_grapes
-            .iter()
-            .any(|grape| grape.to_lowercase().starts_with(&grape_filter))
-    {
-        return Ok(maud::html! {});
-    }
-
-    #[derive(Debug)]
-    struct MainWine {
-        id: i64,
-        name: String,
-        year: i64,
-        num_bottles: i64,
-        last_comment: Option<String>,
-        has_image: bool,
-    }
-    let inv_events = db::wine_inventory_events(&state.db, wine.wine_id).await?;
-    let inventory: i64 = inv_events.iter().map(|ie| ie.bottles).sum();
-    let food_pairings = db::get_wine_food_pairings(&state.db, wine.wine_id).await?;
-    let w = MainWine {
-        id: wine.wine_id,
-        name: wine.name.clone(),
-        year: wine.year,
-        num_bottles: inventory,
-        last_comment: wine.comment.clone(),
-        has_image: wine.has_image,
-    };
+/// Who the wine table and the wines' pages are rendered for: the cellar's members, or
+/// visitors with a share link, who get a read-only copy of them under `/shared/{token}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Audience<'a> {
+    Members,
+    Shared(&'a str),
+}
 
-    Ok(maud::html! {
-        tr id=(format!("wine-{}", w.id)) {
+impl Audience<'_> {
+    /// The page the table is on.
+    fn home(self) -> String {
+        match self {
+            Self::Members => "/".to_owned(),
+            Self::Shared(token) => format!("/shared/{token}"),
+        }
+    }
+
+    /// A link to `path` (starting with `/`) for this audience.
+    fn url(self, path: &str) -> String {
+        match self {
+            Self::Members => path.to_owned(),
+            Self::Shared(token) => format!("/shared/{token}{path}"),
+        }
+    }
+
+    /// Whether the page is for visitors, who can't change anything.
+    fn read_only(self) -> bool {
+        matches!(self, Self::Shared(_))
+    }
+}
+
+/// Renders one `<tr>` of the wine table from an already aggregated [`db::WineRow`].
+pub(crate) fn wine_table_row(
+    w: &db::WineRow,
+    scale: crate::web::ScoreScale,
+    audience: Audience,
+) -> Markup {
+    tracing::info!("Rendering row for {}", w.name);
+    maud::html! {
+        tr id=(format!("wine-{}", w.wine_id)) {
             td style="text-align: center" {
                 @if w.has_image {
-                    img src=(format!("/wines/{}/image", w.id)) height="80";
+                    img src=(audience.url(&format!("/wines/{}/image", w.wine_id))) height="80";
                 }
             }
             td {
                 a href="#"
                   class="link-primary"
-                  hx-trigger="click" hx-target="#main" hx-target-error="#error" hx-get=(format!("/wines/{}", w.id))
+                  hx-trigger="click" hx-target="#main" hx-target-error="#error"
+                  hx-get=(audience.url(&format!("/wines/{}", w.wine_id)))
                     { (w.name)}
+                @if let Some(producer) = &w.producer {
+                    div class="small text-body-secondary" { (producer) }
+                }
+            }
+            td {
+                (w.year)
+                @if let Some(window) = drinking_window_label(w.drink_from, w.drink_until) {
+                    div class="small text-body-secondary" { "Drink " (window) }
+                }
+            }
+            td {
+                @if let Some(style) = w.style {
+                    (style_badge(style))
+                }
+            }
+            td {
+                @if let Some(origin) = &w.origin {
+                    (origin)
+                }
+            }
+            td {(w.bottles)}
+            td class="text-nowrap" {
+                @if let Some(score) = w.score {
+                    (scale.format(score))
+                }
+            }
+            td class="text-nowrap" {
+                @if let Some(dt) = w.last_activity {
+                    (dt.date())
+                }
             }
-            td {(w.year)}
-            td {(w.num_bottles)}
             td {
-                @if let Some(comment) = w.last_comment {
+                @if let Some(comment) = &w.comment {
                     (comment)
                 }
             }
             td {
                 ul {
-                    @for pairing in &food_pairings {
-                        li { (pairing.food) }
+                    @for pairing in &w.pairings {
+                        li { (pairing) }
                     }
                 }
             }
             td {
+                @if !audience.read_only() {
                 div class="dropdown" {
                     button class="btn btn-secondary dropdown-toggle" type="button" data-bs-toggle="dropdown" aria-expanded="false" {
                         "Action"
@@ -159,325 +518,2303 @@ pub(crate) async fn wine_table_row(
                         li { a class="dropdown-item"
                             hx-target="#main"
                             hx-target-error="#error"
-                            hx-get=(format!("/wines/{}/consume", w.id))
+                            hx-get=(format!("/wines/{}/edit", w.wine_id))
+                            { "Edit" }
+                        }
+
+                        li { a class="dropdown-item"
+                            hx-target="#main"
+                            hx-target-error="#error"
+                            hx-get=(format!("/wines/{}/consume", w.wine_id))
                             { "Consume" }
                         }
 
                         li { a class="dropdown-item"
                             hx-target="#main"
                             hx-target-error="#error"
-                            hx-get=(format!("/wines/{}/buy", w.id))
+                            hx-get=(format!("/wines/{}/buy", w.wine_id))
                             { "Buy" }
                         }
 
                         li { a class="dropdown-item"
                             hx-target="#main"
                             hx-target-error="#error"
-                            hx-get=(format!("/wines/{}/grapes", w.id))
+                            hx-get=(format!("/wines/{}/event", w.wine_id))
+                            { "Gift, Loss, Correction…" }
+                        }
+
+                        li { a class="dropdown-item"
+                            hx-target="#main"
+                            hx-target-error="#error"
+                            hx-get=(format!("/wines/{}/grapes", w.wine_id))
                             { "Grapes" } }
                         li { a class="dropdown-item"
                             hx-target="#main"
                             hx-target-error="#error"
-                            hx-get=(format!("/wines/{}/pairings", w.id))
+                            hx-get=(format!("/wines/{}/pairings", w.wine_id))
                             { "Pairings" } }
                         li { a class="dropdown-item"
                             hx-trigger="click"
                             hx-target="#main"
                             hx-target-error="#error"
-                            hx-get=(format!("/wines/{}/upload-image", w.id)) class="dropdown-item"
+                            hx-get=(format!("/wines/{}/upload-image", w.wine_id)) class="dropdown-item"
                             { "Upload Image" }}
 
                         li { a class="dropdown-item"
                             hx-trigger="click"
                             hx-target="#main"
                             hx-target-error="#error"
-                            hx-get=(format!("/wines/{}/edit-image", w.id)) class="dropdown-item"
+                            hx-get=(format!("/wines/{}/edit-image", w.wine_id)) class="dropdown-item"
                             { "Edit Image" }}
 
                         li { a class="dropdown-item"
-                            hx-target=(format!("#wine-{}", w.id))
+                            hx-target=(format!("#wine-{}", w.wine_id))
                             hx-swap="delete"
                             hx-target-error="#error"
-                            hx-delete=(format!("/wines/{}", w.id))
-                            hx-confirm="Are you sure you wish to delete this wine?"
+                            hx-delete=(format!("/wines/{}", w.wine_id))
+                            hx-confirm="Move this wine to the trash?"
                             { "Delete" }
                         }
                     }
                 }
+                }
             }
         }
-    })
+    }
+}
+
+/// Parses the wine table's query string, see [`TableQuery`].
+fn table_query(raw: Option<&str>) -> Result<TableQuery, crate::web::AppError> {
+    TableQuery::parse(raw.unwrap_or_default()).map_err(crate::web::AppError::bad_request)
 }
 
-#[derive(Debug, serde::Deserialize)]
-pub(crate) struct WineTableBody {
-    grape_filter: Option<String>,
+/// Keeps the address bar in step with the table, so a filtered, sorted table can be
+/// bookmarked and survives a reload.
+fn replace_url(query: &TableQuery, audience: Audience) -> axum_htmx::HxReplaceUrl {
+    let query = TableQuery {
+        page: 0,
+        ..query.clone()
+    };
+    axum_htmx::HxReplaceUrl(format!("{}?{}", audience.home(), query.query_string()))
 }
 
-async fn render_wine_rows(state: &crate::web::StateInner, grape_filter: Option<&str>) -> MDResult {
-    let wines = db::wines(&state.db).await?;
+/// Rows per page of the wine table; scrolling to the end loads the next page.
+const PAGE_SIZE: i64 = 50;
+
+/// One page of the wine table's rows, followed by a row that loads the next page when it
+/// scrolls into view, if there is one.
+async fn render_wine_rows(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+    query: &TableQuery,
+    audience: Audience<'_>,
+) -> MDResult {
+    // One more than fits, to know whether there is a next page
+    let mut wines = db::wine_rows_page(
+        &state.db,
+        cellar_id,
+        &query.filter,
+        query.order,
+        PAGE_SIZE + 1,
+        query.page * PAGE_SIZE,
+    )
+    .await?;
+    let more = wines.len() as i64 > PAGE_SIZE;
+    wines.truncate(PAGE_SIZE as usize);
+    let next = TableQuery {
+        page: query.page + 1,
+        ..query.clone()
+    };
     Ok(maud::html! {
-        @for wine in wines {
-            (wine_table_row(state, wine, grape_filter).await?)
+        @for wine in &wines {
+            (wine_table_row(wine, state.score_scale, audience))
+        }
+        @if wines.is_empty() && query.page == 0 && query.is_filtered() {
+            tr {
+                td colspan=(TABLE_COLUMNS) class="text-center text-body-secondary" {
+                    "No wines match these filters."
+                }
+            }
+        }
+        @if more {
+            tr hx-get=(audience.url(&format!("/wine-table-body?{}", next.query_string())))
+                hx-trigger="revealed" hx-swap="outerHTML" hx-target-error="#error" {
+                td colspan=(TABLE_COLUMNS) class="text-center text-body-secondary" { "Loading…" }
+            }
         }
     })
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn wine_table_body(
     axum::extract::State(state): axum::extract::State<State>,
-    axum::extract::Query(query): axum::extract::Query<WineTableBody>,
-) -> MDResult {
+    ctx: auth::Context,
+    axum::extract::RawQuery(raw): axum::extract::RawQuery,
+) -> Result<impl axum::response::IntoResponse, crate::web::AppError> {
     tracing::info!("enter");
-    render_wine_rows(&state, query.grape_filter.as_deref()).await
+    wine_table_body_html(&state, ctx.cellar_id(), raw.as_deref(), Audience::Members).await
 }
 
-fn wine_table_html(body: Option<Markup>) -> Markup {
+/// The rows the filters and the infinite scroll ask for, see [`render_wine_rows`].
+pub(crate) async fn wine_table_body_html(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+    raw: Option<&str>,
+    audience: Audience<'_>,
+) -> Result<impl axum::response::IntoResponse + use<>, crate::web::AppError> {
+    let query = table_query(raw)?;
+    Ok((
+        replace_url(&query, audience),
+        render_wine_rows(state, cellar_id, &query, audience).await?,
+    ))
+}
+
+/// A column header that sorts the table by `sort`, or reverses the order if it already is.
+/// The filters' current values are sent along.
+fn sort_header(
+    label: &str,
+    sort: db::WineSort,
+    current: db::WineOrder,
+    audience: Audience,
+) -> Markup {
+    let active = current.sort == sort;
+    let order = db::WineOrder {
+        sort,
+        // Counts, scores and dates are most interesting from the top
+        descending: if active {
+            !current.descending
+        } else {
+            !matches!(sort, db::WineSort::Name | db::WineSort::Year)
+        },
+    };
+    let query = TableQuery {
+        order,
+        ..Default::default()
+    }
+    .query_string();
     maud::html! {
-        (page_header("Wine Cellar"))
-        a href="#" data-bs-toggle="modal" data-bs-target="#addWineModal" {"Add Wine"}
-        " "
-        a href="#"
-          class="ms-2"
-          hx-get="/pairings/search"
-          hx-target="#main"
-          hx-target-error="#error"
-        { "Food Pairings Search" }
-        div id="error" {}
-        table class="table table-striped" {
-            thead {
-                tr {
-                    th scope="col" {}
-                    th scope="col" { "Name" }
-                    th scope="col" { "Year" }
-                    th scope="col" { "Bottles" }
-                    th scope="col" { "Comment" }
-                    th scope="col" {
-                        "Pairings"
-                        svg xmlns="http://www.w3.org/2000/svg" width="16" height="16"
-                            fill="currentColor" class="bi bi-filter" viewBox="0 0 16 16"
-                            data-bs-toggle="collapse" data-bs-target="#filterGrapes"
-                        {
-                          path d="M6 10.5a.5.5 0 0 1 .5-.5h3a.5.5 0 0 1 0 1h-3a.5.5 0 0 1-.5-.5m-2-3a.5.5 0 0 1 .5-.5h7a.5.5 0 0 1 0 1h-7a.5.5 0 0 1-.5-.5m-2-3a.5.5 0 0 1 .5-.5h11a.5.5 0 0 1 0 1h-11a.5.5 0 0 1-.5-.5";
-                        }
-                        div id="filterGrapes" class="accordion-collapse collapse" {
-                            input name="grape_filter" id="grapeFilter" class="form-control"
-                            hx-get="/wine-table-body"
-                            hx-trigger="input changed delay:500ms, keyup[key=='Enter'],load"
-                            hx-target="#wineTableBody"
-                            {}
-                        }
-                    }
-                    th scope="col" {}
-                }
-            }
-            tbody id="wineTableBody" {
-                @if let Some(body) = body {
-                    (body)
-                } @else {
-                    div hx-get="/wine-table-body" hx-trigger="load" hx-target="#wineTableBody" hx-target-error="#error" {}
-                }
+        a href=(format!("{}?{query}", audience.home()))
+            class="link-body-emphasis text-decoration-none text-nowrap"
+            hx-get=(audience.url(&format!("/wines?{query}"))) hx-include=(FILTER_FORM)
+            hx-target="#main" hx-target-error="#error" {
+            (label)
+            @if active {
+                " " (if current.descending { "▼" } else { "▲" })
             }
         }
     }
 }
 
-#[tracing::instrument]
-pub(crate) async fn wine_table() -> MDResult {
-    tracing::info!("wine_table");
-    Ok(wine_table_html(None))
-}
-
-pub(crate) async fn wine_table_populated(state: &crate::web::StateInner) -> MDResult {
-    let rows = render_wine_rows(state, None).await?;
-    Ok(wine_table_html(Some(rows)))
-}
+/// The filter sidebar's form. Sorting sends its values along, and it sends the table's
+/// order, kept in hidden inputs with the [`ORDER_INPUTS`] class, along with its own.
+const FILTER_FORM: &str = "#wine-filters";
+const ORDER_INPUTS: &str = ".wine-order";
+const TABLE_COLUMNS: usize = 11;
 
-#[tracing::instrument(skip(state))]
-pub(crate) async fn wine_information(
-    axum::extract::State(state): axum::extract::State<State>,
-    axum::extract::Path(wine_id): axum::extract::Path<i64>,
-) -> MDResult {
-    tracing::info!("enter");
-    let wine = db::get_wine(&state.db, wine_id).await?;
-    let events = db::wine_inventory_events(&state.db, wine_id).await?;
-    Ok(maud::html! {
-        (page_header(&wine.name))
-        a href="/" { "Back" }
-        div class="row align-items-start" {
-            div class="col" {
-                h3 { "Events" }
-                table class="table table-striped" {
-                  thead {
-                      tr {
-                          th { "Date" }
-                          th { "Bottles" }
-                      }
-                  }
-                  tbody {
-                    @for evt in events {
-                        tr {
-                            td {(evt.dt.date())}
-                            td {(evt.bottles)}
+/// The filters for the wine table. Every change reloads the table's rows.
+fn filter_sidebar(
+    filter: &db::WineFilter,
+    order: db::WineOrder,
+    grapes: &[db::GrapeFacet],
+    producers: &[db::Producer],
+    regions: &[db::Region],
+    audience: Audience,
+) -> Markup {
+    let clear = TableQuery {
+        order,
+        ..Default::default()
+    }
+    .query_string();
+    let check = |name: &str, label: &str, checked: bool| {
+        maud::html! {
+            div class="form-check" {
+                input class="form-check-input" type="checkbox" name=(name) id=(format!("filter-{name}"))
+                    checked[checked] {}
+                label class="form-check-label" for=(format!("filter-{name}")) { (label) }
+            }
+        }
+    };
+    maud::html! {
+        form id="wine-filters" class="small"
+            hx-get=(audience.url("/wine-table-body")) hx-trigger="input delay:400ms, submit"
+            hx-include=(ORDER_INPUTS) hx-target="#wineTableBody" hx-target-error="#error" {
+            div class="d-flex justify-content-between align-items-baseline" {
+                h2 class="h5" { "Filters" }
+                a href=(format!("{}?{clear}", audience.home()))
+                    hx-get=(audience.url(&format!("/wines?{clear}")))
+                    hx-target="#main" hx-target-error="#error" { "Clear" }
+            }
+            div class="mb-3" {
+                (check("in_stock", "In stock", filter.in_stock))
+                (check("has_image", "Has a label image", filter.has_image))
+                (check("has_note", "Has a note", filter.has_note))
+            }
+            div class="mb-3" {
+                label class="form-label" for="filter-year_from" { "Vintage" }
+                div class="input-group input-group-sm" {
+                    input type="number" name="year_from" id="filter-year_from" class="form-control"
+                        placeholder="From" value=[filter.year_from] {}
+                    input type="number" name="year_to" class="form-control" placeholder="To"
+                        aria-label="Vintage to" value=[filter.year_to] {}
+                }
+            }
+            div class="mb-3" {
+                label class="form-label" for="filter-style" { "Style" }
+                select name="style" id="filter-style" class="form-select form-select-sm" {
+                    option value="" { "All" }
+                    @for style in db::WineStyle::ALL {
+                        option value=(style.as_str()) selected[filter.style == Some(style)] {
+                            (style.label())
                         }
                     }
-                  }
                 }
-                h3 { "Note" }
-                (note_read_view(&wine))
             }
-            div class="col" {
-                img src=(format!("/wines/{wine_id}/image"));
+            div class="mb-3" {
+                label class="form-label" for="filter-producer" { "Producer" }
+                select name="producer" id="filter-producer" class="form-select form-select-sm" {
+                    option value="" { "All" }
+                    @for producer in producers {
+                        option value=(producer.producer_id)
+                            selected[filter.producer_id == Some(producer.producer_id)]
+                            { (producer.name) }
+                    }
+                }
+            }
+            div class="mb-3" {
+                label class="form-label" for="filter-region" { "Origin" }
+                select name="region" id="filter-region" class="form-select form-select-sm" {
+                    option value="" { "All" }
+                    (region_options(regions, filter.region_id))
+                }
+            }
+            div class="mb-3" {
+                label class="form-label" for="filter-pairing" { "Food pairing" }
+                input type="search" name="pairing" id="filter-pairing"
+                    class="form-control form-control-sm" placeholder="e.g. lamb"
+                    value=[filter.pairing.as_deref()] {}
+            }
+            @if !grapes.is_empty() {
+                fieldset class="mb-3" {
+                    legend class="form-label fs-6" { "Grapes" }
+                    div style="max-height: 16rem; overflow-y: auto" {
+                        @for (i, grape) in grapes.iter().enumerate() {
+                            div class="form-check" {
+                                input class="form-check-input" type="checkbox" name="grape"
+                                    id=(format!("filter-grape-{i}")) value=(grape.name)
+                                    checked[filter.grapes.contains(&grape.name)] {}
+                                label class="form-check-label" for=(format!("filter-grape-{i}")) {
+                                    (grape.name) " "
+                                    span class="text-body-secondary" { "(" (grape.wines) ")" }
+                                }
+                            }
+                        }
+                    }
+                }
             }
         }
-    })
+    }
+}
+
+/// The wine table with its filters. Share links get it without the links to the rest of
+/// the app.
+async fn wine_table_html(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+    query: &TableQuery,
+    body: Option<Markup>,
+    audience: Audience<'_>,
+) -> MDResult {
+    let producers = db::producers(&state.db, cellar_id).await?;
+    let regions = db::regions(&state.db).await?;
+    let grapes = db::grape_facets(&state.db, cellar_id).await?;
+    let order = query.order;
+    let body_url = audience.url(&format!("/wine-table-body?{}", query.query_string()));
+    Ok(maud::html! {
+        (page_header("Wine Cellar"))
+        @if !audience.read_only() {
+        a href="#" data-bs-toggle="modal" data-bs-target="#addWineModal" {"Add Wine"}
+        " "
+        a href="#"
+          class="ms-2"
+          hx-get="/pairings/search"
+          hx-target="#main"
+          hx-target-error="#error"
+        { "Food Pairings Search" }
+        a href="/export.csv" class="ms-2" download { "Export CSV" }
+        a href="#"
+          class="ms-2"
+          hx-get="/import"
+          hx-target="#main"
+          hx-target-error="#error"
+        { "Import CSV" }
+        a href="#"
+          class="ms-2"
+          hx-get="/admin"
+          hx-target="#main"
+          hx-target-error="#error"
+        { "Admin" }
+        a href="#"
+          class="ms-2"
+          hx-get="/drinking-window"
+          hx-target="#main"
+          hx-target-error="#error"
+        { "Ready to Drink" }
+        a href="#"
+          class="ms-2"
+          hx-get="/valuation"
+          hx-target="#main"
+          hx-target-error="#error"
+        { "Valuation" }
+        a href="#"
+          class="ms-2"
+          hx-get="/locations"
+          hx-target="#main"
+          hx-target-error="#error"
+        { "Locations" }
+        a href="#"
+          class="ms-2"
+          hx-get="/history"
+          hx-target="#main"
+          hx-target-error="#error"
+        { "History" }
+        a href="#"
+          class="ms-2"
+          hx-get="/trash"
+          hx-target="#main"
+          hx-target-error="#error"
+        { "Trash" }
+        }
+        div id="error" {}
+        input type="hidden" name="sort" class="wine-order" value=(order.sort.as_str()) {}
+        input type="hidden" name="dir" class="wine-order"
+            value=(if order.descending { "desc" } else { "asc" }) {}
+        div class="row mt-3" {
+            aside class="col-lg-3 mb-3" {
+                (filter_sidebar(&query.filter, order, &grapes, &producers, &regions, audience))
+            }
+            div class="col-lg-9" {
+                table class="table table-striped" {
+                    thead {
+                        tr {
+                            th scope="col" {}
+                            th scope="col" { (sort_header("Name", db::WineSort::Name, order, audience)) }
+                            th scope="col" { (sort_header("Year", db::WineSort::Year, order, audience)) }
+                            th scope="col" { "Style" }
+                            th scope="col" { "Origin" }
+                            th scope="col" { (sort_header("Bottles", db::WineSort::Bottles, order, audience)) }
+                            th scope="col" { (sort_header("Score", db::WineSort::Score, order, audience)) }
+                            th scope="col" {
+                                (sort_header("Last activity", db::WineSort::LastActivity, order, audience))
+                            }
+                            th scope="col" { "Comment" }
+                            th scope="col" { "Pairings" }
+                            th scope="col" {}
+                        }
+                    }
+                    tbody id="wineTableBody" {
+                        @if let Some(body) = body {
+                            (body)
+                        } @else {
+                            tr hx-get=(body_url) hx-trigger="load" hx-swap="outerHTML"
+                                hx-target-error="#error" {}
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn wine_table(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::RawQuery(raw): axum::extract::RawQuery,
+) -> Result<impl axum::response::IntoResponse, crate::web::AppError> {
+    tracing::info!("wine_table");
+    wine_table_page(&state, ctx.cellar_id(), raw.as_deref(), Audience::Members).await
+}
+
+/// The wine table for the query string `raw`, see [`TableQuery`].
+pub(crate) async fn wine_table_page(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+    raw: Option<&str>,
+    audience: Audience<'_>,
+) -> Result<impl axum::response::IntoResponse + use<>, crate::web::AppError> {
+    let query = table_query(raw)?;
+    Ok((
+        replace_url(&query, audience),
+        wine_table_html(state, cellar_id, &query, None, audience).await?,
+    ))
+}
+
+pub(crate) async fn wine_table_populated(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+) -> MDResult {
+    let query = TableQuery::default();
+    let rows = render_wine_rows(state, cellar_id, &query, Audience::Members).await?;
+    wine_table_html(state, cellar_id, &query, Some(rows), Audience::Members).await
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn wine_information(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    tracing::info!("enter");
+    wine_information_html(&state, ctx.cellar_id(), wine_id, Audience::Members).await
+}
+
+/// The wine's detail page, also returned after one of its events is edited or deleted.
+/// Share links get it without the buttons and forms that change the wine.
+pub(crate) async fn wine_information_html(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+    wine_id: i64,
+    audience: Audience<'_>,
+) -> MDResult {
+    let wine = db::get_wine(&state.db, cellar_id, wine_id).await?;
+    let row = db::wine_row(&state.db, cellar_id, wine_id).await?;
+    let events = db::wine_inventory_events(&state.db, cellar_id, wine_id).await?;
+    let read_only = audience.read_only();
+    // Visitors see what there is, not what it cost or where it is kept
+    let (costs, placed, moves) = if read_only {
+        Default::default()
+    } else {
+        (
+            db::wine_costs(&state.db, cellar_id, Some(wine_id)).await?,
+            db::placed_bottles(&state.db, cellar_id, Some(wine_id), None).await?,
+            db::bottle_moves(&state.db, cellar_id, wine_id).await?,
+        )
+    };
+    let unplaced = row.bottles - placed.len() as i64;
+    let tastings = db::wine_tastings(&state.db, cellar_id, wine_id).await?;
+    let aromas = db::aromas(&state.db).await?;
+    Ok(maud::html! {
+        (page_header(&wine.name))
+        a href=(audience.home()) { "Back" }
+        div id="error" {}
+        @if !read_only {
+            (wine_tabs(wine_id, false))
+        }
+        div class="row align-items-start" {
+            div class="col" {
+                dl class="row" {
+                    dt class="col-sm-3" { "Producer" }
+                    dd class="col-sm-9" { (row.producer.as_deref().unwrap_or("Unknown")) }
+                    dt class="col-sm-3" { "Origin" }
+                    dd class="col-sm-9" { (row.origin.as_deref().unwrap_or("Unknown")) }
+                    dt class="col-sm-3" { "Drinking window" }
+                    dd class="col-sm-9" {
+                        (drinking_window_label(row.drink_from, row.drink_until)
+                            .unwrap_or_else(|| "Unknown".to_owned()))
+                    }
+                    dt class="col-sm-3" { "Style" }
+                    dd class="col-sm-9" {
+                        @match row.style {
+                            Some(style) => (style_badge(style)),
+                            None => "Unknown",
+                        }
+                    }
+                    @if let Some(score) = row.score {
+                        dt class="col-sm-3" { "Average score" }
+                        dd class="col-sm-9" {
+                            (state.score_scale.format(score))
+                            " from " (tastings.len()) " tasting(s)"
+                        }
+                    }
+                    @if !costs.is_empty() {
+                        dt class="col-sm-3" { "Average cost" }
+                        dd class="col-sm-9" {
+                            @for cost in &costs {
+                                div { (format_money(cost.average_cost(), &cost.currency)) }
+                            }
+                        }
+                        dt class="col-sm-3" { "Value" }
+                        dd class="col-sm-9" {
+                            @for cost in &costs {
+                                div { (format_money(cost.value(), &cost.currency)) }
+                            }
+                        }
+                    }
+                }
+                @if !read_only {
+                    h3 { "Where is it" }
+                    @if !placed.is_empty() {
+                        ul class="list-group mb-2" {
+                            @for bottle in &placed {
+                                li class="list-group-item d-flex justify-content-between align-items-center" {
+                                    (bottle_place(bottle))
+                                    div class="text-nowrap" {
+                                        button class="btn btn-sm btn-outline-secondary me-1"
+                                            hx-get=(format!("/wines/{wine_id}/bottles/{}/move", bottle.slot()))
+                                            hx-target="#main" hx-target-error="#error"
+                                        { "Move" }
+                                        button class="btn btn-sm btn-outline-secondary"
+                                            hx-delete=(format!("/wines/{wine_id}/bottles/{}", bottle.slot()))
+                                            hx-target="#main" hx-target-error="#error"
+                                            title="Take the bottle out of its slot; it stays in stock"
+                                        { "Take out" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    @if unplaced > 0 {
+                        p {
+                            (unplaced) " bottle(s) not in a rack. "
+                            a href="#" hx-get=(format!("/wines/{wine_id}/place"))
+                                hx-target="#main" hx-target-error="#error"
+                            { "Put in a rack" }
+                        }
+                    } @else if placed.is_empty() {
+                        p { "No bottles in stock." }
+                    }
+                    @if !moves.is_empty() {
+                        details class="mb-3" {
+                            summary { "Moves" }
+                            ul class="small mb-0" {
+                                @for m in &moves {
+                                    li {
+                                        (m.dt.date()) ": from " (m.from_rack) ", row " (m.from.row)
+                                        ", column " (m.from.col) " to " (m.to_rack) ", row " (m.to.row)
+                                        ", column " (m.to.col)
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                h3 { "Events" }
+                table class="table table-striped" {
+                  thead {
+                      tr {
+                          th { "Date" }
+                          th { "Kind" }
+                          th { "Bottles" }
+                          @if !read_only {
+                              th { "Price" }
+                              th { "Vendor" }
+                              th {}
+                          }
+                      }
+                  }
+                  tbody {
+                    @for evt in events {
+                        tr {
+                            td {(evt.dt.date())}
+                            td {(evt.kind.label())}
+                            td {(evt.bottles)}
+                            @if !read_only {
+                            td {
+                                @if let (Some(price), Some(currency)) = (evt.unit_price, &evt.currency) {
+                                    (format_money(price, currency))
+                                }
+                            }
+                            td {(evt.vendor.as_deref().unwrap_or_default())}
+                            td class="text-end text-nowrap" {
+                                button class="btn btn-sm btn-outline-secondary me-1"
+                                    hx-get=(format!("/wines/{wine_id}/events/{}/edit", evt.event_id))
+                                    hx-target="#main" hx-target-error="#error"
+                                { "Edit" }
+                                button class="btn btn-sm btn-outline-danger"
+                                    hx-delete=(format!("/wines/{wine_id}/events/{}", evt.event_id))
+                                    hx-confirm="Delete this event?"
+                                    hx-target="#main" hx-target-error="#error"
+                                { "Delete" }
+                            }
+                            }
+                        }
+                    }
+                  }
+                }
+                h3 { "Tastings" }
+                (tasting_timeline(wine_id, &tastings, read_only))
+                @if read_only {
+                    @if let Some(comment) = &wine.comment {
+                        h3 { "Note" }
+                        p { (comment) }
+                    }
+                } @else {
+                    (add_tasting_form(wine_id, state.score_scale, &aromas))
+                    h3 { "Note" }
+                    (note_read_view(&wine))
+                }
+            }
+            div class="col" {
+                @if row.has_image {
+                    img src=(audience.url(&format!("/wines/{wine_id}/image")));
+                }
+            }
+        }
+    })
+}
+
+/// The wine's tastings, newest first, each scored on the scale it was recorded with.
+fn tasting_timeline(wine_id: i64, tastings: &[db::Tasting], read_only: bool) -> Markup {
+    maud::html! {
+        @if tastings.is_empty() {
+            p { "Not tasted yet." }
+        } @else {
+            ul class="list-group mb-3" {
+                @for t in tastings {
+                    li class="list-group-item" {
+                        div class="d-flex justify-content-between align-items-start" {
+                            div {
+                                strong { (t.tasted_on) }
+                                @if let Some(occasion) = &t.occasion {
+                                    span class="text-body-secondary" { " · " (occasion) }
+                                }
+                            }
+                            div class="text-nowrap" {
+                                span class="badge text-bg-primary me-2" { (t.score) "/" (t.scale) }
+                                @if !read_only {
+                                    button class="btn btn-sm btn-outline-danger"
+                                        hx-delete=(format!("/wines/{wine_id}/tastings/{}", t.tasting_id))
+                                        hx-confirm="Delete this tasting?"
+                                        hx-target="#main" hx-target-error="#error"
+                                    { "Delete" }
+                                }
+                            }
+                        }
+                        @if let Some(note) = &t.note {
+                            p class="mb-0 mt-1" style="white-space: pre-wrap" { (note) }
+                        }
+                        @if let Some(sheet) = &t.sheet {
+                            div class="d-flex flex-wrap align-items-center gap-3 mt-2" {
+                                (radar::radar(sheet))
+                                @if !sheet.aromas.is_empty() {
+                                    div {
+                                        @for aroma in &sheet.aromas {
+                                            span class="badge text-bg-light border me-1" { (aroma) }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The optional structured part of the tasting form, posted along with the tasting.
+fn tasting_sheet_inputs(aromas: &[db::Aroma]) -> Markup {
+    maud::html! {
+        details class="mb-2" {
+            summary { "Tasting sheet" }
+            div class="row g-2 mt-1 mb-2" {
+                @for dimension in db::SheetDimension::ALL {
+                    div class="col-sm" {
+                        label for=(dimension.as_str()) class="form-label" { (dimension.label()) }
+                        select name=(dimension.as_str()) id=(dimension.as_str()) class="form-select form-select-sm" {
+                            option value="" { "–" }
+                            @for (i, level) in dimension.levels().iter().enumerate() {
+                                option value=(i + 1) { (level) }
+                            }
+                        }
+                    }
+                }
+            }
+            label class="form-label" { "Aromas" }
+            @for family in aromas.chunk_by(|a, b| a.family == b.family) {
+                div class="mb-1" {
+                    span class="small text-body-secondary me-2" { (family[0].family) }
+                    @for aroma in family {
+                        div class="form-check form-check-inline" {
+                            input class="form-check-input" type="checkbox" name="aroma"
+                                id=(format!("aroma-{}", aroma.aroma_id)) value=(aroma.name) {}
+                            label class="form-check-label" for=(format!("aroma-{}", aroma.aroma_id)) {
+                                (aroma.name)
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn add_tasting_form(wine_id: i64, scale: crate::web::ScoreScale, aromas: &[db::Aroma]) -> Markup {
+    let today = chrono::Local::now().date_naive();
+    maud::html! {
+        details class="mb-3" {
+            summary { "Add a tasting" }
+            form class="mt-2" hx-post=(format!("/wines/{wine_id}/tastings"))
+                hx-target="#main" hx-target-error="#error" {
+                div class="row g-2 mb-2" {
+                    div class="col-sm-4" {
+                        label for="tasted_on" class="form-label" { "Date" }
+                        input name="tasted_on" id="tasted_on" type="date" class="form-control"
+                            value=(today) required {}
+                    }
+                    div class="col-sm-3" {
+                        label for="score" class="form-label" { "Score (0–" (scale.max()) ")" }
+                        input name="score" id="score" type="number" class="form-control"
+                            min="0" max=(scale.max()) required {}
+                    }
+                    div class="col-sm-5" {
+                        label for="occasion" class="form-label" { "Occasion" }
+                        input name="occasion" id="occasion" class="form-control" maxlength="100"
+                            placeholder="Optional" {}
+                    }
+                }
+                div class="mb-2" {
+                    label for="tasting_note" class="form-label" { "Note" }
+                    textarea name="note" id="tasting_note" class="form-control" rows="3" {}
+                }
+                (tasting_sheet_inputs(aromas))
+                button type="submit" class="btn btn-primary" { "Add tasting" }
+            }
+        }
+    }
+}
+
+/// Lists the wines in stock that are past, in or approaching their drinking window, most
+/// urgent first.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn drinking_window(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> MDResult {
+    use super::drinking::Status;
+
+    let filter = db::WineFilter {
+        in_stock: true,
+        ..Default::default()
+    };
+    let rows = db::wine_rows(&state.db, ctx.cellar_id(), &filter).await?;
+    let wines = super::drinking::by_urgency(rows, i64::from(chrono::Local::now().year()));
+    Ok(maud::html! {
+        (page_header("Ready to Drink"))
+        a href="/" { "Back" }
+        div id="error" {}
+        @if wines.is_empty() {
+            p class="mt-3" {
+                "Nothing in the cellar is in or near its drinking window. Set a window when "
+                "adding or editing a wine."
+            }
+        }
+        @for status in [Status::Past, Status::Ready, Status::Approaching] {
+            @let group: Vec<_> = wines.iter().filter(|(s, _)| *s == status).map(|(_, w)| w).collect();
+            @if !group.is_empty() {
+                h3 class="mt-4" { (status.label()) }
+                table class="table table-striped" {
+                    thead {
+                        tr {
+                            th scope="col" { "Name" }
+                            th scope="col" { "Year" }
+                            th scope="col" { "Window" }
+                            th scope="col" { "Bottles" }
+                        }
+                    }
+                    tbody {
+                        @for w in group {
+                            tr {
+                                td {
+                                    a href="#"
+                                      class="link-primary"
+                                      hx-target="#main" hx-target-error="#error"
+                                      hx-get=(format!("/wines/{}", w.wine_id))
+                                    { (w.name) }
+                                    @if let Some(producer) = &w.producer {
+                                        div class="small text-body-secondary" { (producer) }
+                                    }
+                                }
+                                td { (w.year) }
+                                td { (drinking_window_label(w.drink_from, w.drink_until).unwrap_or_default()) }
+                                td { (w.bottles) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// What the bottles in the cellar cost: each wine's weighted average purchase price and the
+/// value of its remaining bottles, with a total per currency. Bottles bought without a price
+/// aren't counted.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn valuation(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> MDResult {
+    let costs = db::wine_costs(&state.db, ctx.cellar_id(), None).await?;
+    let mut totals = std::collections::BTreeMap::<&str, i64>::new();
+    for cost in &costs {
+        *totals.entry(&cost.currency).or_default() += cost.value();
+    }
+    Ok(maud::html! {
+        (page_header("Valuation"))
+        a href="/" { "Back" }
+        div id="error" {}
+        @if costs.is_empty() {
+            p class="mt-3" {
+                "No bottles in the cellar have a purchase price. Enter the price per bottle when "
+                "buying a wine."
+            }
+        } @else {
+            table class="table table-striped mt-3" {
+                thead {
+                    tr {
+                        th scope="col" { "Name" }
+                        th scope="col" { "Year" }
+                        th scope="col" { "Bottles" }
+                        th scope="col" class="text-end" { "Average cost" }
+                        th scope="col" class="text-end" { "Value" }
+                    }
+                }
+                tbody {
+                    @for cost in &costs {
+                        tr {
+                            td {
+                                a href="#"
+                                  class="link-primary"
+                                  hx-target="#main" hx-target-error="#error"
+                                  hx-get=(format!("/wines/{}", cost.wine_id))
+                                { (cost.name) }
+                            }
+                            td { (cost.year) }
+                            td { (cost.bottles) }
+                            td class="text-end" { (format_money(cost.average_cost(), &cost.currency)) }
+                            td class="text-end" { (format_money(cost.value(), &cost.currency)) }
+                        }
+                    }
+                }
+                tfoot {
+                    @for (currency, total) in &totals {
+                        tr class="fw-bold" {
+                            td colspan="4" { "Total" }
+                            td class="text-end" { (format_money(*total, currency)) }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Renders the read-only note partial for a wine. Used on the detail page and returned by
+/// GET /wines/{id}/comment and POST /wines/{id}/comment on success.
+pub(crate) fn note_read_view(wine: &db::Wine) -> Markup {
+    let wine_id = wine.wine_id;
+    maud::html! {
+        div id=(format!("wine-note-{wine_id}")) {
+            @if let Some(ref text) = wine.comment {
+                p { (text) }
+                @if let Some(ref updated_at) = wine.comment_updated_at {
+                    p class="text-muted small" { "Last updated: " (updated_at.date()) }
+                }
+            } @else {
+                p class="text-muted" { "Add a note…" }
+            }
+            button class="btn btn-sm btn-outline-secondary"
+                hx-get=(format!("/wines/{wine_id}/comment/edit"))
+                hx-target=(format!("#wine-note-{wine_id}"))
+                hx-swap="outerHTML"
+            { "✏ Edit" }
+        }
+    }
+}
+
+/// Renders the inline edit form for the note. Used by GET /wines/{id}/comment/edit and
+/// returned by POST /wines/{id}/comment on DB error (edit mode stays open with text preserved).
+pub(crate) fn note_edit_form(wine_id: i64, current_text: &str, error: Option<&str>) -> Markup {
+    maud::html! {
+        div id=(format!("wine-note-{wine_id}")) {
+            @if let Some(msg) = error {
+                div class="alert alert-danger" role="alert" { (msg) }
+            }
+            form
+                hx-post=(format!("/wines/{wine_id}/comment"))
+                hx-target=(format!("#wine-note-{wine_id}"))
+                hx-swap="outerHTML"
+            {
+                textarea name="comment" class="form-control mb-2" rows="4" { (current_text) }
+                button type="submit" class="btn btn-sm btn-primary me-2" { "Save" }
+                button type="button" class="btn btn-sm btn-secondary"
+                    hx-get=(format!("/wines/{wine_id}/comment"))
+                    hx-target=(format!("#wine-note-{wine_id}"))
+                    hx-swap="outerHTML"
+                { "Cancel" }
+            }
+        }
+    }
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn edit_wine_grapes(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    let wine_grapes = db::get_wine_grapes(&state.db, ctx.cellar_id(), wine_id).await?;
+    let all_grapes = db::get_grapes(&state.db).await?;
+    Ok(maud::html! {
+        (page_header("Grapes"))
+        div id="error" {}
+        form hx-post=(format!("/wines/{}/grapes", wine_id))
+            hx-target="#main"
+            hx-target-error="#error"
+            {
+            div class="mb-3" {
+                input type="submit" value="Set Grapes" class="btn btn-primary me-3" {}
+                button hx-trigger="click" hx-target="#main" hx-get="/" class="btn btn-secondary" {
+                    "Cancel"
+                }
+            }
+            @for grape in all_grapes {
+                @let id=format!("grape-{}", grape.rowid);
+                div class="form-check" {
+                    input class="form-check-input" name=(id) type="checkbox" value=(grape.name) id=(id) checked[(wine_grapes.contains(&grape.name))];
+                    label class="form-check-label" for=(id) { (grape.name) };
+                }
+            }
+        }
+    })
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn edit_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    let wine = db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let producers = db::producers(&state.db, ctx.cellar_id()).await?;
+    let regions = db::regions(&state.db).await?;
+    Ok(maud::html! {
+        (page_header("Edit Wine"))
+        div id="error" {}
+        form id="edit-wine"
+            hx-put=(format!("/wines/{wine_id}"))
+            hx-target="#main"
+            hx-target-error="#error" {
+            div class="mb-3" {
+                label for="name" class="form-label" { "Name" }
+                input name="name" id="name" class="form-control" value=(wine.name) required {}
+            }
+            div class="mb-3" {
+                label for="year" class="form-label" { "Year" }
+                input name="year" id="year" type="number" class="form-control" value=(wine.year) {}
+            }
+            div class="mb-3" {
+                label for="style" class="form-label" { "Style" }
+                (style_select(wine.style))
+            }
+            div class="mb-3" {
+                label for="producer" class="form-label" { "Producer" }
+                (producer_input(&producers, wine.producer.as_deref()))
+            }
+            div class="mb-3" {
+                label for="region_id" class="form-label" { "Region" }
+                (region_select(&regions, wine.region_id))
+            }
+            (drinking_window_inputs(wine.drink_from, wine.drink_until))
+            div class="mb-3" {
+                input type="submit" value="Save" class="btn btn-primary me-3" {}
+                button hx-trigger="click" hx-target="#main" hx-get="/wines" class="btn btn-secondary" {
+                    "Cancel"
+                }
+            }
+        }
+    })
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn consume_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    tracing::info!("consume_wine");
+    let today = chrono::Local::now().date_naive();
+    let racks = db::racks(&state.db, ctx.cellar_id(), None).await?;
+    let placed = db::placed_bottles(&state.db, ctx.cellar_id(), Some(wine_id), None).await?;
+    Ok(maud::html! {
+        (page_header("Consume Wine"))
+        div id="error" {}
+        form id="consume-wine"
+            hx-post=(format!("/wines/{wine_id}/consume"))
+            hx-target="#main"
+            hx-target-error="#error" {
+            div class="mb-3" {
+                label for="dt" class="form-label" { "Date" }
+                input name="dt" id="dt" type="date" class="form-control" value=(today) {}
+            }
+            div class="mb-3" {
+                label for="bottles" class="form-label" { "Bottles" }
+                input name="bottles" id="bottles" type="number" min="1" value="1" class="form-control" {}
+            }
+            @if !placed.is_empty() {
+                div class="mb-3" {
+                    label class="form-label" { "Take from" }
+                    div class="form-text mb-2" { "Tick the slot of each bottle you take." }
+                    (slot_picker(&racks, &placed, Some(wine_id), false))
+                }
+            }
+            div class="mb-3" {
+                input type="submit" value="Consume" class="btn btn-primary me-3" {}
+                button hx-trigger="click" hx-target="#main" hx-get="/wines" class="btn btn-secondary" {
+                    "Cancel"
+                }
+            }
+        }
+    })
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn buy_wine(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    tracing::info!("buy_wine");
+    let today = chrono::Local::now().date_naive();
+    let currency = db::last_currency(&state.db, ctx.cellar_id())
+        .await?
+        .unwrap_or_else(|| "EUR".to_owned());
+    let racks = db::racks(&state.db, ctx.cellar_id(), None).await?;
+    let placed = db::placed_bottles(&state.db, ctx.cellar_id(), None, None).await?;
+    Ok(maud::html! {
+        (page_header("Buy Wine"))
+        div id="error" {}
+        form id="buy-wine" hx-post=(format!("/wines/{wine_id}/buy"))
+            hx-target="#main"
+            hx-target-error="#error" {
+            div class="mb-3" {
+                label for="dt" class="form-label" { "Date" }
+                input name="dt" id="dt" type="date" class="form-control" value=(today) {}
+            }
+            div class="mb-3" {
+                label for="bottles" class="form-label" { "Bottles" }
+                input name="bottles" id="bottles" type="number" min="1" value="6" class="form-control" {}
+            }
+            div class="mb-3" {
+                label for="price" class="form-label" { "Price per bottle" }
+                div class="input-group" {
+                    input name="price" id="price" class="form-control" inputmode="decimal"
+                        placeholder="Optional" {}
+                    input name="currency" class="form-control" style="max-width: 6em"
+                        maxlength="3" aria-label="Currency" value=(currency) {}
+                }
+            }
+            div class="mb-3" {
+                label for="vendor" class="form-label" { "Vendor" }
+                input name="vendor" id="vendor" class="form-control" placeholder="Optional" {}
+            }
+            @if !racks.is_empty() {
+                div class="mb-3" {
+                    label class="form-label" { "Put in" }
+                    div class="form-text mb-2" {
+                        "Tick an empty slot for each bottle. Bottles without a slot can be put in a rack later."
+                    }
+                    (slot_picker(&racks, &placed, None, false))
+                }
+            }
+            div class="mb-3" {
+                input type="submit" value="Buy" class="btn btn-primary me-3" {}
+                button hx-trigger="click" hx-target="#main" hx-get="/wines" class="btn btn-secondary" {
+                    "Cancel"
+                }
+            }
+        }
+    })
+}
+
+/// Form to put bottles that are in stock but not in a rack into empty slots.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn place_bottles(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    let wine = db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let racks = db::racks(&state.db, ctx.cellar_id(), None).await?;
+    let placed = db::placed_bottles(&state.db, ctx.cellar_id(), None, None).await?;
+    Ok(maud::html! {
+        (page_header(&format!("Put {} in a rack", wine.name)))
+        div id="error" {}
+        @if racks.is_empty() {
+            p {
+                "There are no racks yet. "
+                a href="#" hx-get="/locations" hx-target="#main" hx-target-error="#error"
+                { "Add a location and a rack" }
+                " first."
+            }
+        } @else {
+            form hx-post=(format!("/wines/{wine_id}/place"))
+                hx-target="#main"
+                hx-target-error="#error" {
+                div class="mb-3" { (slot_picker(&racks, &placed, None, false)) }
+                div class="mb-3" {
+                    input type="submit" value="Put in" class="btn btn-primary me-3" {}
+                    button type="button" hx-get=(format!("/wines/{wine_id}")) hx-target="#main"
+                        class="btn btn-secondary" {
+                        "Cancel"
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Form to move one of the wine's bottles to an empty slot.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn move_bottle(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, slot)): axum::extract::Path<(i64, String)>,
+) -> MDResult {
+    let from: db::Slot = slot.parse().map_err(crate::web::AppError::bad_request)?;
+    let wine = db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let bottle = db::placed_bottles(&state.db, ctx.cellar_id(), Some(wine_id), None)
+        .await?
+        .into_iter()
+        .find(|b| b.slot() == from)
+        .ok_or_else(|| crate::web::AppError::bad_request(db::InventoryError::NotInSlot(from)))?;
+    let racks = db::racks(&state.db, ctx.cellar_id(), None).await?;
+    let placed = db::placed_bottles(&state.db, ctx.cellar_id(), None, None).await?;
+    Ok(maud::html! {
+        (page_header(&format!("Move {}", wine.name)))
+        div id="error" {}
+        p { "From " (bottle_place(&bottle)) " to:" }
+        form hx-post=(format!("/wines/{wine_id}/bottles/{from}/move"))
+            hx-target="#main"
+            hx-target-error="#error" {
+            div class="mb-3" { (slot_picker(&racks, &placed, None, true)) }
+            div class="mb-3" {
+                input type="submit" value="Move" class="btn btn-primary me-3" {}
+                button type="button" hx-get=(format!("/wines/{wine_id}")) hx-target="#main"
+                    class="btn btn-secondary" {
+                    "Cancel"
+                }
+            }
+        }
+    })
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn locations(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> MDResult {
+    locations_html(&state, ctx.cellar_id()).await
+}
+
+/// Every location with how full its racks are, and a form to add one.
+pub(crate) async fn locations_html(state: &crate::web::StateInner, cellar_id: i64) -> MDResult {
+    let locations = db::locations(&state.db, cellar_id).await?;
+    let racks = db::racks(&state.db, cellar_id, None).await?;
+    let placed = db::placed_bottles(&state.db, cellar_id, None, None).await?;
+    Ok(maud::html! {
+        (page_header("Locations"))
+        a href="/" { "Back" }
+        div id="error" {}
+        table class="table table-striped mt-3" {
+            thead {
+                tr {
+                    th scope="col" { "Location" }
+                    th scope="col" { "Racks" }
+                    th scope="col" { "Bottles" }
+                    th scope="col" { "Free slots" }
+                }
+            }
+            tbody {
+                @for location in &locations {
+                    @let location_racks: Vec<_> = racks.iter().filter(|r| r.location_id == location.location_id).collect();
+                    @let slots: i64 = location_racks.iter().map(|r| r.row_count * r.column_count).sum();
+                    @let bottles = placed.iter().filter(|b| b.location_id == location.location_id).count() as i64;
+                    tr {
+                        td {
+                            a href="#"
+                              class="link-primary"
+                              hx-get=(format!("/locations/{}", location.location_id))
+                              hx-target="#main" hx-target-error="#error"
+                            { (location.name) }
+                        }
+                        td { (location_racks.len()) }
+                        td { (bottles) }
+                        td { (slots - bottles) }
+                    }
+                }
+            }
+        }
+        form class="row g-2" hx-post="/locations" hx-target="#main" hx-target-error="#error" {
+            div class="col-auto" {
+                input name="name" class="form-control" placeholder="New location, e.g. Fridge"
+                    aria-label="Location name" {}
+            }
+            div class="col-auto" {
+                input type="submit" value="Add location" class="btn btn-primary" {}
+            }
+        }
+    })
+}
+
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn location(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(location_id): axum::extract::Path<i64>,
+) -> MDResult {
+    location_html(&state, ctx.cellar_id(), location_id).await
+}
+
+/// What's in a location: its racks and every bottle in them, and a form to add a rack.
+pub(crate) async fn location_html(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+    location_id: i64,
+) -> MDResult {
+    let location = db::get_location(&state.db, cellar_id, location_id).await?;
+    let racks = db::racks(&state.db, cellar_id, Some(location_id)).await?;
+    let placed = db::placed_bottles(&state.db, cellar_id, None, Some(location_id)).await?;
+    Ok(maud::html! {
+        (page_header(&location.name))
+        a href="#" hx-get="/locations" hx-target="#main" hx-target-error="#error" { "Back" }
+        div id="error" {}
+        h3 class="mt-3" { "Racks" }
+        (rack_map::legend(&placed))
+        div class="d-flex flex-wrap gap-4 mb-3" {
+            @for rack in &racks {
+                @let bottles = placed.iter().filter(|b| b.rack_id == rack.rack_id).count();
+                div {
+                    div class="fw-semibold" { (rack.name) }
+                    div class="small text-body-secondary mb-1" {
+                        (bottles) " of " (rack.row_count * rack.column_count) " slots used"
+                    }
+                    (rack_map::rack_map(rack, &placed))
+                }
+            }
+        }
+        form class="row g-2 mb-4" hx-post=(format!("/locations/{location_id}/racks"))
+            hx-target="#main" hx-target-error="#error" {
+            div class="col-auto" {
+                input name="name" class="form-control" placeholder="New rack" aria-label="Rack name" {}
+            }
+            div class="col-auto" {
+                input name="row_count" type="number" min="1" max="100" value="6"
+                    class="form-control" aria-label="Rows" title="Rows" {}
+            }
+            div class="col-auto" {
+                input name="column_count" type="number" min="1" max="100" value="8"
+                    class="form-control" aria-label="Columns" title="Columns" {}
+            }
+            div class="col-auto" {
+                input type="submit" value="Add rack" class="btn btn-primary" {}
+            }
+        }
+        h3 { "Contents" }
+        @if placed.is_empty() {
+            p { "No bottles here yet." }
+        } @else {
+            table class="table table-striped" {
+                thead {
+                    tr {
+                        th scope="col" { "Rack" }
+                        th scope="col" { "Row" }
+                        th scope="col" { "Column" }
+                        th scope="col" { "Wine" }
+                        th scope="col" { "Year" }
+                    }
+                }
+                tbody {
+                    @for bottle in &placed {
+                        tr {
+                            td { (bottle.rack) }
+                            td { (bottle.row) }
+                            td { (bottle.col) }
+                            td {
+                                a href="#"
+                                  class="link-primary"
+                                  hx-get=(format!("/wines/{}", bottle.wine_id))
+                                  hx-target="#main" hx-target-error="#error"
+                                { (bottle.name) }
+                            }
+                            td { (bottle.year) }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Form to correct an inventory event. Like [`record_event`], the bottles are entered as a
+/// count and a direction.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn edit_event(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path((wine_id, event_id)): axum::extract::Path<(i64, i64)>,
+) -> MDResult {
+    let event = db::get_wine_event(&state.db, ctx.cellar_id(), wine_id, event_id).await?;
+    let price = event
+        .unit_price
+        .map(|p| format!("{}.{:02}", p / 100, p % 100));
+    Ok(maud::html! {
+        (page_header("Edit Event"))
+        div id="error" {}
+        form id="edit-event" hx-put=(format!("/wines/{wine_id}/events/{event_id}"))
+            hx-target="#main"
+            hx-target-error="#error" {
+            div class="mb-3" {
+                label for="dt" class="form-label" { "Date" }
+                input name="dt" id="dt" type="date" class="form-control" value=(event.dt.date()) {}
+            }
+            div class="mb-3" {
+                label for="kind" class="form-label" { "Kind" }
+                select name="kind" id="kind" class="form-select" {
+                    @for kind in db::EventKind::ALL {
+                        option value=(kind.as_str()) selected[kind == event.kind] { (kind.label()) }
+                    }
+                }
+            }
+            div class="mb-3" {
+                label for="direction" class="form-label" { "Direction" }
+                select name="direction" id="direction" class="form-select" {
+                    option value="out" selected[event.bottles < 0] { "Bottles leave the cellar" }
+                    option value="in" selected[event.bottles > 0] { "Bottles come into the cellar" }
+                }
+            }
+            div class="mb-3" {
+                label for="bottles" class="form-label" { "Bottles" }
+                input name="bottles" id="bottles" type="number" min="1" value=(event.bottles.abs())
+                    class="form-control" {}
+            }
+            div class="mb-3" {
+                label for="price" class="form-label" { "Price per bottle" }
+                div class="input-group" {
+                    input name="price" id="price" class="form-control" inputmode="decimal"
+                        placeholder="Purchases only" value=[price] {}
+                    input name="currency" class="form-control" style="max-width: 6em"
+                        maxlength="3" aria-label="Currency" value=[event.currency] {}
+                }
+            }
+            div class="mb-3" {
+                label for="vendor" class="form-label" { "Vendor" }
+                input name="vendor" id="vendor" class="form-control" value=[event.vendor] {}
+            }
+            div class="mb-3" {
+                input type="submit" value="Save" class="btn btn-primary me-3" {}
+                button type="button" hx-get=(format!("/wines/{wine_id}")) hx-target="#main"
+                    class="btn btn-secondary" {
+                    "Cancel"
+                }
+            }
+        }
+    })
+}
+
+/// Form for the events that aren't a purchase or a consumption: gifts, losses, corrections and
+/// transfers, each of which can add or remove bottles.
+pub(crate) async fn record_event(axum::extract::Path(wine_id): axum::extract::Path<i64>) -> Markup {
+    let today = chrono::Local::now().date_naive();
+    maud::html! {
+        (page_header("Record Event"))
+        div id="error" {}
+        form id="record-event" hx-post=(format!("/wines/{wine_id}/event"))
+            hx-target="#main"
+            hx-target-error="#error" {
+            div class="mb-3" {
+                label for="dt" class="form-label" { "Date" }
+                input name="dt" id="dt" type="date" class="form-control" value=(today) {}
+            }
+            div class="mb-3" {
+                label for="kind" class="form-label" { "Kind" }
+                select name="kind" id="kind" class="form-select" {
+                    @for kind in [db::EventKind::Gift, db::EventKind::Loss, db::EventKind::Correction, db::EventKind::Transfer] {
+                        option value=(kind.as_str()) { (kind.label()) }
+                    }
+                }
+            }
+            div class="mb-3" {
+                label for="direction" class="form-label" { "Direction" }
+                select name="direction" id="direction" class="form-select" {
+                    option value="out" { "Bottles leave the cellar" }
+                    option value="in" { "Bottles come into the cellar" }
+                }
+            }
+            div class="mb-3" {
+                label for="bottles" class="form-label" { "Bottles" }
+                input name="bottles" id="bottles" type="number" min="1" value="1" class="form-control" {}
+            }
+            div class="mb-3" {
+                input type="submit" value="Save" class="btn btn-primary me-3" {}
+                button hx-trigger="click" hx-target="#main" hx-get="/wines" class="btn btn-secondary" {
+                    "Cancel"
+                }
+            }
+        }
+    }
+}
+
+pub(crate) async fn upload_wine_image(
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> Markup {
+    maud::html! {
+        (page_header("Upload Image"))
+        div id="error" {}
+        form hx-encoding="multipart/form-data"
+            hx-target="#main"
+            hx-target-error="#error"
+            hx-post=(format!("/wines/{wine_id}/image")) {
+           input type="file" name="image";
+           input type="submit" value="Upload" class="btn btn-primary" {}
+        }
+    }
+}
+
+// ── History ──────────────────────────────────────────────────────────────────
+
+/// Entries per page of a history; the "Older" button loads the next page.
+const HISTORY_PAGE_SIZE: i64 = 50;
+
+/// Parses a history's query string, see [`HistoryQuery`].
+fn history_query(raw: Option<&str>) -> Result<HistoryQuery, crate::web::AppError> {
+    HistoryQuery::parse(raw.unwrap_or_default()).map_err(crate::web::AppError::bad_request)
+}
+
+/// The cellar's audit log, newest first, filtered by the [`HistoryQuery`] in the query
+/// string. Deleted wines keep their history; it is found through the links on the entries.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn history(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::RawQuery(raw): axum::extract::RawQuery,
+) -> MDResult {
+    let query = history_query(raw.as_deref())?;
+    let filter = &query.filter;
+    let usernames = db::audit_usernames(&state.db, ctx.cellar_id()).await?;
+    // The wine may be gone; its newest entry has the name it had last
+    let wine = match filter.wine_id {
+        Some(wine_id) => {
+            let only = db::AuditFilter {
+                wine_id: Some(wine_id),
+                ..Default::default()
+            };
+            db::audit_log(&state.db, ctx.cellar_id(), &only, None, 1)
+                .await?
+                .into_iter()
+                .next()
+                .and_then(|entry| entry.wine)
+        }
+        None => None,
+    };
+    Ok(maud::html! {
+        (page_header("History"))
+        a href="/" { "Back" }
+        div id="error" {}
+        div class="row mt-3" {
+            aside class="col-lg-3 mb-3" {
+                form class="small" hx-get="/history/entries" hx-trigger="input delay:400ms, submit"
+                    hx-target="#historyBody" hx-target-error="#error" {
+                    div class="d-flex justify-content-between align-items-baseline" {
+                        h2 class="h5" { "Filters" }
+                        a href="#" hx-get="/history" hx-target="#main" hx-target-error="#error"
+                        { "Clear" }
+                    }
+                    @if let Some(wine_id) = filter.wine_id {
+                        input type="hidden" name="wine" value=(wine_id);
+                        p { "Wine: " strong { (wine.unwrap_or_else(|| format!("#{wine_id}"))) } }
+                    }
+                    div class="mb-3" {
+                        label class="form-label" for="history-entity" { "What" }
+                        select name="entity" id="history-entity" class="form-select form-select-sm" {
+                            option value="" { "All" }
+                            @for entity in db::AuditEntity::ALL {
+                                option value=(entity.as_str()) selected[filter.entity == Some(entity)] {
+                                    (entity.label())
+                                }
+                            }
+                        }
+                    }
+                    div class="mb-3" {
+                        label class="form-label" for="history-action" { "Change" }
+                        select name="action" id="history-action" class="form-select form-select-sm" {
+                            option value="" { "All" }
+                            @for action in db::AuditAction::ALL {
+                                option value=(action.as_str()) selected[filter.action == Some(action)] {
+                                    (action.label())
+                                }
+                            }
+                        }
+                    }
+                    div class="mb-3" {
+                        label class="form-label" for="history-user" { "Who" }
+                        select name="user" id="history-user" class="form-select form-select-sm" {
+                            option value="" { "Everyone" }
+                            @for username in &usernames {
+                                option value=(username)
+                                    selected[filter.username.as_ref() == Some(username)]
+                                    { (username) }
+                            }
+                        }
+                    }
+                    div class="mb-3" {
+                        label class="form-label" for="history-from" { "When" }
+                        div class="input-group input-group-sm" {
+                            input type="date" name="from" id="history-from" class="form-control"
+                                value=[filter.from] {}
+                            input type="date" name="until" class="form-control"
+                                aria-label="Until" value=[filter.until] {}
+                        }
+                    }
+                }
+            }
+            div class="col-lg-9" {
+                (history_table(&state, ctx.cellar_id(), &query).await?)
+            }
+        }
+    })
+}
+
+/// The entries a history's filters and its "Older" button ask for.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn history_entries(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::RawQuery(raw): axum::extract::RawQuery,
+) -> MDResult {
+    let query = history_query(raw.as_deref())?;
+    history_rows(&state, ctx.cellar_id(), &query).await
+}
+
+/// A wine's own history, the second tab of its page. Share links don't get it.
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn wine_history(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+) -> MDResult {
+    let wine = db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let query = HistoryQuery {
+        filter: db::AuditFilter {
+            wine_id: Some(wine_id),
+            ..Default::default()
+        },
+        older_than: None,
+    };
+    Ok(maud::html! {
+        (page_header(&wine.name))
+        a href="/" { "Back" }
+        div id="error" {}
+        (wine_tabs(wine_id, true))
+        (history_table(&state, ctx.cellar_id(), &query).await?)
+    })
+}
+
+/// Switches a wine's page between its details and its history.
+fn wine_tabs(wine_id: i64, history: bool) -> Markup {
+    let tab = |label: &str, url: String, active: bool| {
+        maud::html! {
+            li class="nav-item" {
+                a href="#" class=(if active { "nav-link active" } else { "nav-link" })
+                    aria-current=[active.then_some("page")]
+                    hx-get=(url) hx-target="#main" hx-target-error="#error" { (label) }
+            }
+        }
+    };
+    maud::html! {
+        ul class="nav nav-tabs my-3" {
+            (tab("Details", format!("/wines/{wine_id}"), !history))
+            (tab("History", format!("/wines/{wine_id}/history"), history))
+        }
+    }
+}
+
+/// A history's table. Histories of one wine leave out the wine column.
+async fn history_table(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+    query: &HistoryQuery,
+) -> MDResult {
+    let show_wine = query.filter.wine_id.is_none();
+    Ok(maud::html! {
+        table class="table table-striped" {
+            thead {
+                tr {
+                    th scope="col" { "When" }
+                    th scope="col" { "Who" }
+                    th scope="col" { "What" }
+                    @if show_wine {
+                        th scope="col" { "Wine" }
+                    }
+                    th scope="col" { "Changes" }
+                }
+            }
+            tbody id="historyBody" {
+                (history_rows(state, cellar_id, query).await?)
+            }
+        }
+    })
+}
+
+/// One page of a history's entries, followed by a row with a button that replaces it with
+/// the next page, if there is one.
+async fn history_rows(
+    state: &crate::web::StateInner,
+    cellar_id: i64,
+    query: &HistoryQuery,
+) -> MDResult {
+    // One more than fits, to know whether there are older entries
+    let mut entries = db::audit_log(
+        &state.db,
+        cellar_id,
+        &query.filter,
+        query.older_than,
+        HISTORY_PAGE_SIZE + 1,
+    )
+    .await?;
+    let more = entries.len() as i64 > HISTORY_PAGE_SIZE;
+    entries.truncate(HISTORY_PAGE_SIZE as usize);
+    let show_wine = query.filter.wine_id.is_none();
+    let columns = if show_wine { 5 } else { 4 };
+    let next = HistoryQuery {
+        older_than: entries.last().map(|entry| entry.audit_id),
+        ..query.clone()
+    };
+    Ok(maud::html! {
+        @for entry in &entries {
+            (history_row(entry, show_wine))
+        }
+        @if entries.is_empty() && query.older_than.is_none() {
+            tr {
+                td colspan=(columns) class="text-center text-body-secondary" {
+                    "No changes recorded."
+                }
+            }
+        }
+        @if more {
+            tr {
+                td colspan=(columns) class="text-center" {
+                    button class="btn btn-sm btn-outline-secondary"
+                        hx-get=(format!("/history/entries?{}", next.query_string()))
+                        hx-target="closest tr" hx-swap="outerHTML" hx-target-error="#error"
+                    { "Older" }
+                }
+            }
+        }
+    })
+}
+
+/// An audit entry: the fields it changed, then its snapshots as recorded.
+fn history_row(entry: &db::AuditEntry, show_wine: bool) -> Markup {
+    let badge = match entry.action {
+        db::AuditAction::Add | db::AuditAction::Restore => "badge text-bg-success",
+        db::AuditAction::Update => "badge text-bg-secondary",
+        db::AuditAction::Delete | db::AuditAction::Purge => "badge text-bg-danger",
+    };
+    let json = |value: &serde_json::Value| serde_json::to_string_pretty(value).unwrap_or_default();
+    maud::html! {
+        tr {
+            td class="text-nowrap" { (entry.dt.format("%Y-%m-%d %H:%M")) }
+            td { (entry.username) }
+            td class="text-nowrap" {
+                (entry.entity.label()) " " span class=(badge) { (entry.action.label()) }
+            }
+            @if show_wine {
+                td {
+                    // Locations and racks aren't any wine's
+                    @if let (Some(wine_id), Some(wine)) = (entry.wine_id, &entry.wine) {
+                        a href="#" hx-get=(format!("/history?wine={wine_id}"))
+                            hx-target="#main" hx-target-error="#error" { (wine) }
+                    }
+                }
+            }
+            td {
+                ul class="list-unstyled small mb-1" {
+                    @for change in super::history::field_changes(entry) {
+                        li {
+                            span class="text-body-secondary" { (change.field) ": " }
+                            @match entry.action {
+                                db::AuditAction::Add | db::AuditAction::Restore => {
+                                    (change.new.unwrap_or_default())
+                                }
+                                db::AuditAction::Update => {
+                                    (change.old.as_deref().unwrap_or("—")) " → "
+                                    (change.new.as_deref().unwrap_or("—"))
+                                }
+                                db::AuditAction::Delete | db::AuditAction::Purge => {
+                                    del { (change.old.unwrap_or_default()) }
+                                }
+                            }
+                        }
+                    }
+                }
+                details class="small" {
+                    summary { "JSON" }
+                    @if let Some(before) = &entry.before {
+                        div class="fw-semibold" { "Before" }
+                        pre class="mb-1" { (json(before)) }
+                    }
+                    @if let Some(after) = &entry.after {
+                        div class="fw-semibold" { "After" }
+                        pre class="mb-1" { (json(after)) }
+                    }
+                }
+            }
+        }
+    }
 }
 
-/// Renders the read-only note partial for a wine. Used on the detail page and returned by
-/// GET /wines/{id}/comment and POST /wines/{id}/comment on success.
-pub(crate) fn note_read_view(wine: &db::Wine) -> Markup {
-    let wine_id = wine.wine_id;
+// ── Trash ────────────────────────────────────────────────────────────────────
+
+/// Where the toast offering to undo a delete goes: swapped in out of band by the delete's
+/// response, which only removes the wine's row, and cleared by the undo's. Bootstrap hides
+/// it again after a while.
+pub(crate) fn trash_toast(trashed: Option<&db::Wine>) -> Markup {
     maud::html! {
-        div id=(format!("wine-note-{wine_id}")) {
-            @if let Some(ref text) = wine.comment {
-                p { (text) }
-                @if let Some(ref updated_at) = wine.comment_updated_at {
-                    p class="text-muted small" { "Last updated: " (updated_at.date()) }
+        div id="toasts" class="toast-container position-fixed bottom-0 end-0 p-3" hx-swap-oob="true"
+            hx-on::load="this.querySelectorAll('.toast').forEach(t => bootstrap.Toast.getOrCreateInstance(t).show())" {
+            @if let Some(wine) = trashed {
+                div class="toast" role="status" aria-live="polite" aria-atomic="true"
+                    data-bs-delay="10000" {
+                    div class="toast-body d-flex align-items-center gap-2" {
+                        span class="me-auto" {
+                            (wine.name) " " (wine.year) " was moved to the trash."
+                        }
+                        button class="btn btn-sm btn-primary"
+                            hx-post=(format!("/wines/{}/restore", wine.wine_id))
+                            hx-target="#main" hx-target-error="#error"
+                        { "Undo" }
+                        button type="button" class="btn-close" data-bs-dismiss="toast"
+                            aria-label="Close" {}
+                    }
                 }
-            } @else {
-                p class="text-muted" { "Add a note…" }
             }
-            button class="btn btn-sm btn-outline-secondary"
-                hx-get=(format!("/wines/{wine_id}/comment/edit"))
-                hx-target=(format!("#wine-note-{wine_id}"))
-                hx-swap="outerHTML"
-            { "✏ Edit" }
         }
     }
 }
 
-/// Renders the inline edit form for the note. Used by GET /wines/{id}/comment/edit and
-/// returned by POST /wines/{id}/comment on DB error (edit mode stays open with text preserved).
-pub(crate) fn note_edit_form(wine_id: i64, current_text: &str, error: Option<&str>) -> Markup {
-    maud::html! {
-        div id=(format!("wine-note-{wine_id}")) {
-            @if let Some(msg) = error {
-                div class="alert alert-danger" role="alert" { (msg) }
+#[tracing::instrument(skip(state, ctx))]
+pub(crate) async fn trash(
+    axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
+) -> MDResult {
+    trash_html(&state, ctx.cellar_id()).await
+}
+
+/// The cellar's deleted wines, to restore or to delete for good. Also returned after either.
+pub(crate) async fn trash_html(state: &crate::web::StateInner, cellar_id: i64) -> MDResult {
+    let wines = db::trash(&state.db, cellar_id).await?;
+    Ok(maud::html! {
+        (page_header("Trash"))
+        a href="/" { "Back" }
+        div id="error" {}
+        p class="mt-3 text-body-secondary" {
+            @match state.trash_days {
+                Some(days) => {
+                    "Deleted wines are kept here with everything about them for " (days)
+                    " day(s), then purged for good."
+                }
+                None => "Deleted wines are kept here with everything about them until purged.",
             }
-            form
-                hx-post=(format!("/wines/{wine_id}/comment"))
-                hx-target=(format!("#wine-note-{wine_id}"))
-                hx-swap="outerHTML"
-            {
-                textarea name="comment" class="form-control mb-2" rows="4" { (current_text) }
-                button type="submit" class="btn btn-sm btn-primary me-2" { "Save" }
-                button type="button" class="btn btn-sm btn-secondary"
-                    hx-get=(format!("/wines/{wine_id}/comment"))
-                    hx-target=(format!("#wine-note-{wine_id}"))
-                    hx-swap="outerHTML"
-                { "Cancel" }
+        }
+        @if wines.is_empty() {
+            p { "The trash is empty." }
+        } @else {
+            table class="table table-striped" {
+                thead {
+                    tr {
+                        th scope="col" { "Name" }
+                        th scope="col" { "Year" }
+                        th scope="col" { "Deleted" }
+                        th scope="col" { "By" }
+                        @if state.trash_days.is_some() {
+                            th scope="col" { "Purged on" }
+                        }
+                        th scope="col" {}
+                    }
+                }
+                tbody {
+                    @for w in &wines {
+                        tr {
+                            td {
+                                a href="#" hx-get=(format!("/history?wine={}", w.wine_id))
+                                    hx-target="#main" hx-target-error="#error"
+                                    title="Its history"
+                                { (w.name) }
+                                @if let Some(producer) = &w.producer {
+                                    div class="small text-body-secondary" { (producer) }
+                                }
+                            }
+                            td { (w.year) }
+                            td class="text-nowrap" { (w.deleted_at.format("%Y-%m-%d %H:%M")) }
+                            td { (w.deleted_by.as_deref().unwrap_or_default()) }
+                            @if let Some(days) = state.trash_days {
+                                td class="text-nowrap" {
+                                    (crate::trash::purge_at(w.deleted_at, days).date())
+                                }
+                            }
+                            td class="text-end text-nowrap" {
+                                button class="btn btn-sm btn-outline-secondary me-1"
+                                    hx-post=(format!("/trash/{}/restore", w.wine_id))
+                                    hx-target="#main" hx-target-error="#error"
+                                { "Restore" }
+                                button class="btn btn-sm btn-outline-danger"
+                                    hx-delete=(format!("/trash/{}", w.wine_id))
+                                    hx-confirm="Delete this wine and everything about it for good? This can't be undone."
+                                    hx-target="#main" hx-target-error="#error"
+                                { "Delete forever" }
+                            }
+                        }
+                    }
+                }
             }
         }
-    }
+    })
 }
 
+// ── Admin ────────────────────────────────────────────────────────────────────
+
+/// Owners manage the members and share links of the current cellar here, admins the
+/// accounts, cellars and backups, and everyone their password.
 #[tracing::instrument(skip(state))]
-pub(crate) async fn edit_wine_grapes(
+pub(crate) async fn admin_page(
     axum::extract::State(state): axum::extract::State<State>,
-    axum::extract::Path(wine_id): axum::extract::Path<i64>,
+    ctx: Option<auth::Context>,
+    axum::Extension(user): axum::Extension<db::User>,
 ) -> MDResult {
-    let wine_grapes = db::get_wine_grapes(&state.db, wine_id).await?;
-    let all_grapes = db::get_grapes(&state.db).await?;
+    let (members, shares) = match &ctx {
+        Some(ctx) if ctx.is_owner() => (
+            Some(members_html(&state, ctx).await?),
+            Some(share_links_html(&state, ctx, None).await?),
+        ),
+        _ => (None, None),
+    };
+    let users = if user.is_admin {
+        Some(users_html(&state, &user).await?)
+    } else {
+        None
+    };
+    let (config, last_success, last_error) = {
+        let status = state.backups.lock().expect("backup status lock poisoned");
+        (
+            status.config.clone(),
+            status.last_success,
+            status.last_error.clone(),
+        )
+    };
+    let snapshots = match &config {
+        Some(config) => crate::backup::list_snapshots(&config.dir)
+            .await
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
     Ok(maud::html! {
-        (page_header("Grapes"))
+        (page_header("Admin"))
         div id="error" {}
-        form hx-post=(format!("/wines/{}/grapes", wine_id))
+        button class="btn btn-secondary mb-3"
+            hx-get="/wines"
             hx-target="#main"
+            hx-trigger="click"
+        { "← Back" }
+        @if let (Some(ctx), Some(members), Some(shares)) = (&ctx, members, shares) {
+            h3 { "Members of " (ctx.cellar.name) }
+            div id="members" { (members) }
+            h3 class="mt-4" { "Share Links" }
+            p {
+                "Anyone with a share link can browse the cellar's wines without an account, "
+                "but can't change anything."
+            }
+            div id="shares" { (shares) }
+        }
+        @if let Some(users) = users {
+        h3 class="mt-4" { "Cellars" }
+        p { "A new cellar starts out empty, with you as its owner." }
+        form hx-post="/cellars" hx-target-error="#error" class="row g-2 mb-4" {
+            div class="col-md-6" {
+                input type="text" name="name" class="form-control" placeholder="Name"
+                    aria-label="Cellar name" maxlength="100" required {}
+            }
+            div class="col-auto" {
+                input type="submit" value="Add cellar" class="btn btn-primary" {}
+            }
+        }
+        h3 { "Backup" }
+        p { "Download a consistent snapshot of the whole database, every cellar and image included." }
+        a href="/backup" class="btn btn-primary mb-4" download { "Download Backup" }
+        h3 { "Restore" }
+        div class="alert alert-warning" role="alert" {
+            "Restoring replaces every cellar, wine, event, grape, pairing, note and image with "
+            "the contents of the backup. Accounts are kept."
+        }
+        form hx-encoding="multipart/form-data"
+            hx-post="/restore"
             hx-target-error="#error"
-            {
-            div class="mb-3" {
-                input type="submit" value="Set Grapes" class="btn btn-primary me-3" {}
-                button hx-trigger="click" hx-target="#main" hx-get="/" class="btn btn-secondary" {
-                    "Cancel"
+            hx-confirm="Replace every cellar with this backup?"
+        {
+            input type="file" name="file" accept=".db,.sqlite,.sqlite3" class="form-control mb-2" required;
+            input type="submit" value="Restore" class="btn btn-danger" {}
+        }
+        h3 class="mt-4" { "Scheduled Backups" }
+        @if let Some(config) = config {
+            dl class="row" {
+                dt class="col-sm-3" { "Directory" }
+                dd class="col-sm-9" { code { (config.dir.display()) } }
+                dt class="col-sm-3" { "Interval" }
+                dd class="col-sm-9" { (config.interval.as_secs() / 3600) " hours" }
+                dt class="col-sm-3" { "Retention" }
+                dd class="col-sm-9" {
+                    (config.keep_daily) " daily, " (config.keep_weekly) " weekly"
+                }
+                dt class="col-sm-3" { "Last success" }
+                dd class="col-sm-9" {
+                    @if let Some(dt) = last_success {
+                        (dt.format("%Y-%m-%d %H:%M"))
+                    } @else {
+                        span class="text-muted" { "Never" }
+                    }
+                }
+                @if let Some((dt, err)) = last_error {
+                    dt class="col-sm-3" { "Last error" }
+                    dd class="col-sm-9 text-danger" { (dt.format("%Y-%m-%d %H:%M")) ": " (err) }
                 }
             }
-            @for grape in all_grapes {
-                @let id=format!("grape-{}", grape.rowid);
-                div class="form-check" {
-                    input class="form-check-input" name=(id) type="checkbox" value=(grape.name) id=(id) checked[(wine_grapes.contains(&grape.name))];
-                    label class="form-check-label" for=(id) { (grape.name) };
+            table class="table table-striped" {
+                thead { tr { th { "File" } th { "Taken" } th { "Size" } } }
+                tbody {
+                    @for snapshot in &snapshots {
+                        tr {
+                            td { code { (snapshot.file_name()) } }
+                            td { (snapshot.taken_at.format("%Y-%m-%d %H:%M")) }
+                            td { (snapshot.size / 1024) " KiB" }
+                        }
+                    }
+                }
+            }
+        } @else {
+            p class="text-muted" {
+                "Disabled. Set " code { "WINE_BACKUP_DIR" } " to enable scheduled backups."
+            }
+        }
+        h3 class="mt-4" { "Users" }
+        div id="users" { (users) }
+        }
+        h3 class="mt-4" { "Change Password" }
+        div id="password-result" {}
+        form hx-post="/account/password" hx-target="#password-result" hx-target-error="#error"
+            hx-on::after-request="if (event.detail.successful) this.reset()" {
+            div class="row g-2 mb-2" {
+                div class="col-md-4" {
+                    input type="password" name="current_password" class="form-control"
+                        placeholder="Current password" aria-label="Current password"
+                        autocomplete="current-password" required {}
+                }
+                div class="col-md-4" {
+                    input type="password" name="password" class="form-control"
+                        placeholder="New password" aria-label="New password"
+                        autocomplete="new-password" required {}
+                }
+                div class="col-md-4" {
+                    input type="password" name="confirm" class="form-control"
+                        placeholder="Repeat new password" aria-label="Repeat new password"
+                        autocomplete="new-password" required {}
                 }
             }
+            input type="submit" value="Change password" class="btn btn-primary" {}
         }
     })
 }
 
-pub(crate) async fn consume_wine(axum::extract::Path(wine_id): axum::extract::Path<i64>) -> Markup {
-    tracing::info!("consume_wine");
-    let today = chrono::Local::now().date_naive();
-    maud::html! {
-        (page_header("Consume Wine"))
-        div id="error" {}
-        form id="consume-wine"
-            hx-post=(format!("/wines/{wine_id}/consume"))
-            hx-target="#main"
-            hx-target-error="#error" {
-            div class="mb-3" {
-                label for="dt" class="form-label" { "Date" }
-                input name="dt" id="dt" type="date" class="form-control" value=(today) {}
+/// The accounts, with a form to add one. Only admins manage them, and nobody can delete
+/// their own.
+pub(crate) async fn users_html(state: &crate::web::StateInner, current: &db::User) -> MDResult {
+    let users = db::users(&state.db).await?;
+    Ok(maud::html! {
+        table class="table table-striped" {
+            thead { tr { th { "Username" } th { "Created" } th {} } }
+            tbody {
+                @for user in &users {
+                    tr {
+                        td {
+                            (user.username)
+                            @if user.is_admin {
+                                " " span class="badge text-bg-secondary" { "admin" }
+                            }
+                        }
+                        td { (user.created_at.format("%Y-%m-%d")) }
+                        td class="text-end" {
+                            @if user.user_id != current.user_id {
+                                button class="btn btn-outline-danger btn-sm"
+                                    hx-delete=(format!("/users/{}", user.user_id))
+                                    hx-target="#users" hx-target-error="#error"
+                                    hx-confirm=(format!("Delete the account {}?", user.username))
+                                { "Delete" }
+                            }
+                        }
+                    }
+                }
             }
-            div class="mb-3" {
-                label for="bottles" class="form-label" { "Bottles" }
-                input name="bottles" id="bottles" type="number" value="1" class="form-control" {}
+        }
+        form hx-post="/users" hx-target="#users" hx-target-error="#error" {
+            div class="row g-2 mb-2" {
+                div class="col-md-4" {
+                    input type="text" name="username" class="form-control" placeholder="Username"
+                        aria-label="Username" autocomplete="off" required {}
+                }
+                div class="col-md-4" {
+                    input type="password" name="password" class="form-control"
+                        placeholder="Password" aria-label="Password" autocomplete="new-password"
+                        required {}
+                }
+                div class="col-md-4" {
+                    input type="password" name="confirm" class="form-control"
+                        placeholder="Repeat password" aria-label="Repeat password"
+                        autocomplete="new-password" required {}
+                }
             }
-            div class="mb-3" {
-                input type="submit" value="Consume" class="btn btn-primary me-3" {}
-                button hx-trigger="click" hx-target="#main" hx-get="/wines" class="btn btn-secondary" {
-                    "Cancel"
+            div class="form-check mb-2" {
+                input type="checkbox" name="admin" id="new-user-admin" class="form-check-input" {}
+                label for="new-user-admin" class="form-check-label" {
+                    "Admin: can manage accounts, cellars and backups"
                 }
             }
+            input type="submit" value="Add user" class="btn btn-primary" {}
         }
-    }
+    })
 }
-pub(crate) async fn buy_wine(axum::extract::Path(wine_id): axum::extract::Path<i64>) -> Markup {
-    tracing::info!("buy_wine");
-    let today = chrono::Local::now().date_naive();
-    maud::html! {
-        (page_header("Buy Wine"))
-        div id="error" {}
-        form id="buy-wine" hx-post=(format!("/wines/{wine_id}/buy"))
-            hx-target="#main"
-            hx-target-error="#error" {
-            div class="mb-3" {
-                label for="dt" class="form-label" { "Date" }
-                input name="dt" id="dt" type="date" class="form-control" value=(today) {}
+
+/// The members of the current cellar and their roles, for its owners. There is always at
+/// least one owner left, and owners can't remove themselves.
+pub(crate) async fn members_html(state: &crate::web::StateInner, ctx: &auth::Context) -> MDResult {
+    let members = db::cellar_members(&state.db, ctx.cellar_id()).await?;
+    Ok(maud::html! {
+        table class="table table-striped" {
+            thead { tr { th { "Username" } th { "Role" } th {} } }
+            tbody {
+                @for member in &members {
+                    tr {
+                        td { (member.username) }
+                        td {
+                            form hx-post="/cellar/members" hx-trigger="change"
+                                hx-target="#members" hx-target-error="#error" {
+                                input type="hidden" name="username" value=(member.username);
+                                select name="role" class="form-select form-select-sm w-auto"
+                                    aria-label="Role" {
+                                    (role_options(member.role))
+                                }
+                            }
+                        }
+                        td class="text-end" {
+                            @if member.user_id != ctx.user.user_id {
+                                button class="btn btn-outline-danger btn-sm"
+                                    hx-delete=(format!("/cellar/members/{}", member.user_id))
+                                    hx-target="#members" hx-target-error="#error"
+                                    hx-confirm=(format!("Remove {} from the cellar?", member.username))
+                                { "Remove" }
+                            }
+                        }
+                    }
+                }
             }
-            div class="mb-3" {
-                label for="bottles" class="form-label" { "Bottles" }
-                input name="bottles" id="bottles" type="number" value="6" class="form-control" {}
+        }
+        form hx-post="/cellar/members" hx-target="#members" hx-target-error="#error"
+            class="row g-2 mb-2" {
+            div class="col-md-6" {
+                input type="text" name="username" class="form-control" placeholder="Username"
+                    aria-label="Username" autocomplete="off" required {}
             }
-            div class="mb-3" {
-                input type="submit" value="Buy" class="btn btn-primary me-3" {}
-                button hx-trigger="click" hx-target="#main" hx-get="/wines" class="btn btn-secondary" {
-                    "Cancel"
+            div class="col-md-3" {
+                select name="role" class="form-select" aria-label="Role" {
+                    (role_options(db::CellarRole::Viewer))
+                }
+            }
+            div class="col-md-3" {
+                input type="submit" value="Add member" class="btn btn-primary" {}
+            }
+        }
+    })
+}
+
+/// The current cellar's share links, for its owners, with a form to make one. `new_token`
+/// is the token of a link just made: its address is shown this once.
+pub(crate) async fn share_links_html(
+    state: &crate::web::StateInner,
+    ctx: &auth::Context,
+    new_token: Option<&str>,
+) -> MDResult {
+    let links = db::share_links(&state.db, ctx.cellar_id()).await?;
+    let now = chrono::Utc::now().naive_utc();
+    Ok(maud::html! {
+        @if let Some(token) = new_token {
+            @let path = Audience::Shared(token).home();
+            div class="alert alert-success" role="alert" {
+                "Copy the link now, it won't be shown again: "
+                a href=(path) target="_blank" rel="noopener" class="alert-link text-break" { (path) }
+            }
+        }
+        @if !links.is_empty() {
+            table class="table table-striped" {
+                thead { tr { th { "For" } th { "Made" } th { "Expires" } th {} } }
+                tbody {
+                    @for link in &links {
+                        tr {
+                            td { (link.label) }
+                            td { (link.created_at.format("%Y-%m-%d")) }
+                            td {
+                                @match link.expires_at {
+                                    Some(dt) if dt <= now => {
+                                        span class="badge text-bg-secondary" { "Expired" }
+                                    }
+                                    Some(dt) => (dt.format("%Y-%m-%d %H:%M")),
+                                    None => span class="text-body-secondary" { "Never" },
+                                }
+                            }
+                            td class="text-end" {
+                                button class="btn btn-outline-danger btn-sm"
+                                    hx-delete=(format!("/cellar/shares/{}", link.share_id))
+                                    hx-target="#shares" hx-target-error="#error"
+                                    hx-confirm=(format!("Revoke the link for {}?", link.label))
+                                { "Revoke" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        form hx-post="/cellar/shares" hx-target="#shares" hx-target-error="#error"
+            class="row g-2 mb-2" {
+            div class="col-md-6" {
+                input type="text" name="label" class="form-control"
+                    placeholder="Who is it for, e.g. Saturday's dinner guests"
+                    aria-label="Who the link is for" maxlength="100" required {}
+            }
+            div class="col-md-3" {
+                select name="days" class="form-select" aria-label="Expires" {
+                    option value="" { "Never expires" }
+                    option value="1" { "Expires in a day" }
+                    option value="7" selected { "Expires in a week" }
+                    option value="30" { "Expires in a month" }
                 }
             }
+            div class="col-md-3" {
+                input type="submit" value="Make link" class="btn btn-primary" {}
+            }
+        }
+    })
+}
+
+fn role_options(selected: db::CellarRole) -> Markup {
+    maud::html! {
+        @for role in db::CellarRole::ALL {
+            option value=(role.as_str()) selected[role == selected] { (role.label()) }
         }
     }
 }
 
-pub(crate) async fn upload_wine_image(
-    axum::extract::Path(wine_id): axum::extract::Path<i64>,
-) -> Markup {
+// ── CSV Import ───────────────────────────────────────────────────────────────
+
+pub(crate) async fn import_page() -> Markup {
     maud::html! {
-        (page_header("Upload Image"))
+        (page_header("Import CSV"))
         div id="error" {}
-        form hx-encoding="multipart/form-data"
+        button class="btn btn-secondary mb-3"
+            hx-get="/wines"
             hx-target="#main"
+            hx-trigger="click"
+        { "← Back" }
+        p {
+            "Columns: "
+            code { (crate::web::import::HEADER.join(", ")) }
+            ". Grapes and pairings are separated by " code { ";" } ". "
+            "Wines are matched on name and year; " code { "bottles" }
+            " is the current number of bottles. Grapes and pairings are only added, never removed."
+        }
+        form hx-encoding="multipart/form-data"
+            hx-post="/import"
+            hx-target="#import-result"
             hx-target-error="#error"
-            hx-post=(format!("/wines/{wine_id}/image")) {
-           input type="file" name="image";
-           input type="submit" value="Upload" class="btn btn-primary" {}
+            class="mb-3"
+        {
+            input type="file" name="file" accept=".csv,text/csv" class="form-control mb-2" required;
+            input type="submit" value="Preview" class="btn btn-primary" {}
+        }
+        div id="import-result" {}
+    }
+}
+
+/// Dry-run result of an import: what would change per line, and any per-line errors. The
+/// "Apply" form re-posts the same CSV text, so nothing has to be kept server side.
+pub(crate) fn import_preview(plan: &crate::web::import::ImportPlan, csv: &str) -> Markup {
+    maud::html! {
+        @if !plan.errors.is_empty() {
+            div class="alert alert-danger" role="alert" {
+                "Fix the lines below and upload the file again. Nothing has been imported."
+            }
+            table class="table table-sm" {
+                thead { tr { th { "Line" } th { "Error" } } }
+                tbody {
+                    @for err in &plan.errors {
+                        tr { td { (err.line) } td { (err.message) } }
+                    }
+                }
+            }
+        }
+        @if plan.changes.is_empty() {
+            p class="text-muted" { "No changes to import." }
+        } @else {
+            table class="table table-striped" {
+                thead {
+                    tr {
+                        th { "Line" }
+                        th { "Wine" }
+                        th { "Year" }
+                        th {}
+                        th { "Bottles" }
+                        th { "Grapes added" }
+                        th { "Pairings added" }
+                        th { "Note" }
+                    }
+                }
+                tbody {
+                    @for change in &plan.changes {
+                        @let import = &change.import;
+                        tr {
+                            td { (change.line) }
+                            td { (import.name) }
+                            td { (import.year) }
+                            td {
+                                @if import.wine_id.is_none() {
+                                    span class="badge text-bg-success" { "New" }
+                                } @else {
+                                    span class="badge text-bg-info" { "Update" }
+                                }
+                            }
+                            td {
+                                @if import.bottles_delta != 0 {
+                                    (format!("{:+}", import.bottles_delta))
+                                }
+                            }
+                            td { (import.grapes.join(", ")) }
+                            td { (import.pairings.join(", ")) }
+                            td { @if let Some(note) = &import.note { (note) } }
+                        }
+                    }
+                }
+            }
+        }
+        @if plan.unchanged > 0 {
+            p class="text-muted" { (plan.unchanged) " wines already match and will not change." }
+        }
+        @if plan.errors.is_empty() && !plan.changes.is_empty() {
+            form hx-encoding="multipart/form-data"
+                hx-post="/import"
+                hx-target="#main"
+                hx-target-error="#error"
+            {
+                textarea name="file" hidden { (csv) }
+                input type="hidden" name="apply" value="1";
+                input type="submit" value="Apply Import" class="btn btn-primary" {}
+            }
         }
     }
 }
@@ -510,13 +2847,14 @@ pub(crate) fn food_pairings_list_items(pairings: &[db::FoodPairing], wine_id: i6
     }
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, ctx))]
 pub(crate) async fn edit_wine_pairings(
     axum::extract::State(state): axum::extract::State<State>,
+    ctx: auth::Context,
     axum::extract::Path(wine_id): axum::extract::Path<i64>,
 ) -> MDResult {
-    let wine = db::get_wine(&state.db, wine_id).await?;
-    let pairings = db::get_wine_food_pairings(&state.db, wine_id).await?;
+    let wine = db::get_wine(&state.db, ctx.cellar_id(), wine_id).await?;
+    let pairings = db::get_wine_food_pairings(&state.db, ctx.cellar_id(), wine_id).await?;
     Ok(maud::html! {
         (page_header(&format!("Food Pairings — {}", wine.name)))
         div id="error" {}
@@ -550,6 +2888,51 @@ pub(crate) async fn edit_wine_pairings(
 
 // ── Pairings Search ──────────────────────────────────────────────────────────
 
+/// Renders a [`db::SearchHit`] text with its matches marked.
+fn highlighted(text: &str) -> Markup {
+    maud::html! {
+        @for (i, part) in text.split([db::MATCH_START, db::MATCH_END]).enumerate() {
+            // Parts alternate between unmatched and matched text
+            @if i % 2 == 1 {
+                mark { (part) }
+            } @else {
+                (part)
+            }
+        }
+    }
+}
+
+pub(crate) fn search_results(hits: &[db::SearchHit], q: &str) -> Markup {
+    maud::html! {
+        (page_header("Search"))
+        a href="/" { "Back" }
+        div id="error" {}
+        @if q.is_empty() {
+            p class="text-muted mt-3" {
+                "Search the wines' names, producers, notes, tastings, grapes and pairings."
+            }
+        } @else if hits.is_empty() {
+            p class="text-muted mt-3" { "No wines found matching \"" (q) "\"." }
+        } @else {
+            div class="list-group mt-3" {
+                @for hit in hits {
+                    a href="#" class="list-group-item list-group-item-action"
+                        hx-get=(format!("/wines/{}", hit.wine_id))
+                        hx-target="#main" hx-target-error="#error" {
+                        div class="fw-semibold" {
+                            (highlighted(&hit.name)) " "
+                            span class="text-body-secondary fw-normal" { (hit.year) }
+                        }
+                        @if hit.snippet != hit.name {
+                            div class="small text-body-secondary" { (highlighted(&hit.snippet)) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub(crate) fn pairings_search_page() -> Markup {
     maud::html! {
         (page_header("Food Pairings Search"))
@@ -0,0 +1,87 @@
+//! An SVG picture of a rack: one circle per slot, filled with the colour of the wine's style
+//! when it holds a bottle. Clicking a bottle opens its wine.
+
+use maud::Markup;
+
+use crate::db;
+
+/// Distance between slot centres, in SVG units.
+const CELL: i64 = 36;
+const RADIUS: i64 = 14;
+/// Room for the row and column numbers.
+const MARGIN: i64 = 24;
+
+/// Fill for bottles of wines without a style.
+const NO_STYLE: &str = "#adb5bd";
+
+/// Draws `rack` with the bottles in `bottles` that are in it.
+pub(super) fn rack_map(rack: &db::Rack, bottles: &[db::PlacedBottle]) -> Markup {
+    let occupied: std::collections::HashMap<_, _> = bottles
+        .iter()
+        .filter(|b| b.rack_id == rack.rack_id)
+        .map(|b| ((b.row, b.col), b))
+        .collect();
+    let width = MARGIN + rack.column_count * CELL;
+    let height = MARGIN + rack.row_count * CELL;
+    let centre = |n: i64| MARGIN + (n - 1) * CELL + CELL / 2;
+    maud::html! {
+        svg xmlns="http://www.w3.org/2000/svg" class="rack-map"
+            width=(width) height=(height) viewBox=(format!("0 0 {width} {height}"))
+            role="img" aria-label=(format!("Rack {}", rack.name)) {
+            @for col in 1..=rack.column_count {
+                text x=(centre(col)) y=(MARGIN / 2) text-anchor="middle"
+                    dominant-baseline="middle" font-size="11" fill="#6c757d" { (col) }
+            }
+            @for row in 1..=rack.row_count {
+                text x=(MARGIN / 2) y=(centre(row)) text-anchor="middle"
+                    dominant-baseline="middle" font-size="11" fill="#6c757d" { (row) }
+                @for col in 1..=rack.column_count {
+                    @match occupied.get(&(row, col)) {
+                        Some(bottle) => {
+                            @let (fill, _) = bottle.style.map_or((NO_STYLE, "black"), super::style_colours);
+                            g style="cursor: pointer"
+                                hx-get=(format!("/wines/{}", bottle.wine_id))
+                                hx-target="#main" hx-target-error="#error" {
+                                title {
+                                    (bottle.name) " " (bottle.year)
+                                    " (row " (row) ", column " (col) ")"
+                                }
+                                circle cx=(centre(col)) cy=(centre(row)) r=(RADIUS)
+                                    fill=(fill) stroke="#212529" stroke-width="1" {}
+                            }
+                        }
+                        None => {
+                            circle cx=(centre(col)) cy=(centre(row)) r=(RADIUS)
+                                fill="none" stroke="#ced4da" stroke-width="1"
+                                stroke-dasharray="3 2" {
+                                title { "Empty (row " (row) ", column " (col) ")" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The colours used in the rack maps, for the styles of the bottles in them.
+pub(super) fn legend(bottles: &[db::PlacedBottle]) -> Markup {
+    let styles = db::WineStyle::ALL
+        .into_iter()
+        .map(Some)
+        .chain([None])
+        .filter(|&style| bottles.iter().any(|b| b.style == style));
+    maud::html! {
+        div class="d-flex flex-wrap gap-3 small mb-3" {
+            @for style in styles {
+                @let (fill, _) = style.map_or((NO_STYLE, "black"), super::style_colours);
+                span {
+                    svg width="12" height="12" class="me-1" {
+                        circle cx="6" cy="6" r="5" fill=(fill) stroke="#212529" {}
+                    }
+                    (style.map_or("No style", db::WineStyle::label))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! A radar chart of a tasting sheet's structure: one spoke per [`db::SheetDimension`], with
+//! rings for levels 1 to 5 and the tasting's levels joined up on top. Dimensions that weren't
+//! assessed sit in the centre and have their label greyed out.
+
+use maud::Markup;
+
+use crate::db;
+
+const SIZE: f64 = 260.0;
+/// Length of a spoke, i.e. level 5.
+const RADIUS: f64 = 64.0;
+/// How far outside the outer ring the labels go.
+const LABEL_GAP: f64 = 12.0;
+
+/// Where `level` (0 to 5) sits on the spoke of the `index`th dimension, starting at the top
+/// and going clockwise.
+fn point(index: usize, level: f64) -> (f64, f64) {
+    let angle = std::f64::consts::TAU * index as f64 / db::SheetDimension::ALL.len() as f64
+        - std::f64::consts::FRAC_PI_2;
+    let r = RADIUS * level / 5.0;
+    (SIZE / 2.0 + r * angle.cos(), SIZE / 2.0 + r * angle.sin())
+}
+
+/// The `points` attribute of a polygon through the spokes at the given levels.
+fn polygon(levels: impl Iterator<Item = f64>) -> String {
+    levels
+        .enumerate()
+        .map(|(i, level)| {
+            let (x, y) = point(i, level);
+            format!("{x:.1},{y:.1}")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub(super) fn radar(sheet: &db::TastingSheet) -> Markup {
+    let dimensions = db::SheetDimension::ALL;
+    let levels = || {
+        dimensions
+            .iter()
+            .map(|&d| sheet.level(d).unwrap_or_default() as f64)
+    };
+    maud::html! {
+        svg xmlns="http://www.w3.org/2000/svg" class="tasting-radar"
+            width=(SIZE) height=(SIZE) viewBox=(format!("0 0 {SIZE} {SIZE}"))
+            role="img" aria-label="Structure" {
+            @for ring in 1..=5 {
+                polygon points=(polygon(dimensions.iter().map(|_| f64::from(ring))))
+                    fill="none" stroke="#dee2e6" stroke-width="1" {}
+            }
+            @for (i, dimension) in dimensions.iter().enumerate() {
+                @let (x, y) = point(i, 5.0);
+                line x1=(SIZE / 2.0) y1=(SIZE / 2.0) x2=(format!("{x:.1}")) y2=(format!("{y:.1}"))
+                    stroke="#dee2e6" stroke-width="1" {}
+                @let (lx, ly) = point(i, 5.0 * (RADIUS + LABEL_GAP) / RADIUS);
+                @let anchor = if (lx - SIZE / 2.0).abs() < 1.0 {
+                    "middle"
+                } else if lx < SIZE / 2.0 {
+                    "end"
+                } else {
+                    "start"
+                };
+                @let level = sheet.level(*dimension);
+                text x=(format!("{lx:.1}")) y=(format!("{ly:.1}")) text-anchor=(anchor)
+                    dominant-baseline="middle" font-size="11"
+                    fill=(if level.is_some() { "#212529" } else { "#adb5bd" }) {
+                    title {
+                        (dimension.label()) ": "
+                        (level.map_or("not assessed", |l| dimension.levels()[l as usize - 1]))
+                    }
+                    (dimension.label())
+                }
+            }
+            polygon points=(polygon(levels())) fill="#7b1e3a" fill-opacity="0.3"
+                stroke="#7b1e3a" stroke-width="2" {}
+            @for (i, level) in levels().enumerate() {
+                @if level > 0.0 {
+                    @let (x, y) = point(i, level);
+                    circle cx=(format!("{x:.1}")) cy=(format!("{y:.1}")) r="3" fill="#7b1e3a" {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point() {
+        // The first spoke points straight up, level 0 is the centre
+        let (x, y) = point(0, 5.0);
+        assert!((x - SIZE / 2.0).abs() < 1e-9);
+        assert!((y - (SIZE / 2.0 - RADIUS)).abs() < 1e-9);
+        assert_eq!(point(3, 0.0), (SIZE / 2.0, SIZE / 2.0));
+    }
+}
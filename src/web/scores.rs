@@ -0,0 +1,93 @@
+//! Tasting scores. New tastings are scored on the scale configured with `WINE_SCORE_SCALE`;
+//! each tasting keeps the scale it was scored on, so averages are taken over fractions of
+//! the scale and shown on the current one.
+
+/// The scale new tastings are scored on: `0..=max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ScoreScale(i64);
+
+impl ScoreScale {
+    /// The scales in common use: stars, tenths, the French 20 point and Parker's 100 point.
+    pub(crate) const ALLOWED: [i64; 4] = [5, 10, 20, 100];
+
+    /// Reads `WINE_SCORE_SCALE`, defaulting to 100 points.
+    pub(crate) fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("WINE_SCORE_SCALE") {
+            Ok(v) => v.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub(crate) fn max(self) -> i64 {
+        self.0
+    }
+
+    /// Checks that `score` is on this scale.
+    pub(crate) fn validate(self, score: i64) -> anyhow::Result<i64> {
+        anyhow::ensure!(
+            (0..=self.0).contains(&score),
+            "Score must be between 0 and {}",
+            self.0
+        );
+        Ok(score)
+    }
+
+    /// An average score, given as a fraction of the scale, in points on this scale. Scales
+    /// below 100 get one decimal, since e.g. 3.5 stars is quite different from 4.
+    pub(crate) fn format(self, fraction: f64) -> String {
+        let points = fraction * self.0 as f64;
+        if self.0 >= 100 {
+            format!("{points:.0}/{}", self.0)
+        } else {
+            format!("{points:.1}/{}", self.0)
+        }
+    }
+}
+
+impl Default for ScoreScale {
+    fn default() -> Self {
+        Self(100)
+    }
+}
+
+impl std::str::FromStr for ScoreScale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let max: i64 = s
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid WINE_SCORE_SCALE: {s}"))?;
+        anyhow::ensure!(
+            Self::ALLOWED.contains(&max),
+            "WINE_SCORE_SCALE must be one of {:?}, not {max}",
+            Self::ALLOWED
+        );
+        Ok(Self(max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scale() {
+        assert_eq!("20".parse::<ScoreScale>().unwrap().max(), 20);
+        assert_eq!(" 5 ".parse::<ScoreScale>().unwrap().max(), 5);
+        assert!("7".parse::<ScoreScale>().is_err());
+        assert!("ten".parse::<ScoreScale>().is_err());
+        assert_eq!(ScoreScale::default().max(), 100);
+    }
+
+    #[test]
+    fn test_validate_and_format() {
+        let stars: ScoreScale = "5".parse().unwrap();
+        assert_eq!(stars.validate(0).unwrap(), 0);
+        assert_eq!(stars.validate(5).unwrap(), 5);
+        assert!(stars.validate(6).is_err());
+        assert!(stars.validate(-1).is_err());
+        assert_eq!(stars.format(0.6), "3.0/5");
+        assert_eq!(ScoreScale::default().format(0.916), "92/100");
+    }
+}
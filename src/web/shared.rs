@@ -0,0 +1,101 @@
+//! Read-only share links. A link's token opens one cellar's wine table and wine pages to
+//! anyone who has it, without an account and without anything that changes the cellar.
+//! Owners make and revoke links on the Admin page; as with sessions, the database only has
+//! the SHA-256 of a link's token.
+
+use super::markup::{self, Audience};
+use super::{AppError, MDResult, State, auth};
+use crate::db;
+
+pub(crate) fn router() -> axum::Router<State> {
+    axum::Router::new()
+        .route("/shared/{token}", axum::routing::get(index))
+        .route("/shared/{token}/wines", axum::routing::get(wine_table))
+        .route(
+            "/shared/{token}/wine-table-body",
+            axum::routing::get(wine_table_body),
+        )
+        .route(
+            "/shared/{token}/wines/{wine_id}",
+            axum::routing::get(wine_information),
+        )
+        .route(
+            "/shared/{token}/wines/{wine_id}/image",
+            axum::routing::get(wine_image),
+        )
+}
+
+/// The cellar the link opens. Revoked, expired and made up links are all just not found.
+async fn shared_cellar(
+    state: &super::StateInner,
+    token: &str,
+) -> Result<db::SharedCellar, AppError> {
+    let now = chrono::Utc::now().naive_utc();
+    let cellar = db::shared_cellar(&state.db, &auth::token_hash(token), now)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+    Ok(cellar)
+}
+
+#[tracing::instrument(skip_all)]
+async fn index(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::extract::Path(token): axum::extract::Path<String>,
+    axum::extract::RawQuery(raw): axum::extract::RawQuery,
+) -> MDResult {
+    let cellar = shared_cellar(&state, &token).await?;
+    markup::shared_index(&cellar, Audience::Shared(&token), raw.as_deref())
+}
+
+#[tracing::instrument(skip_all)]
+async fn wine_table(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::extract::Path(token): axum::extract::Path<String>,
+    axum::extract::RawQuery(raw): axum::extract::RawQuery,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let cellar = shared_cellar(&state, &token).await?;
+    markup::wine_table_page(
+        &state,
+        cellar.cellar_id,
+        raw.as_deref(),
+        Audience::Shared(&token),
+    )
+    .await
+}
+
+#[tracing::instrument(skip_all)]
+async fn wine_table_body(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::extract::Path(token): axum::extract::Path<String>,
+    axum::extract::RawQuery(raw): axum::extract::RawQuery,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let cellar = shared_cellar(&state, &token).await?;
+    markup::wine_table_body_html(
+        &state,
+        cellar.cellar_id,
+        raw.as_deref(),
+        Audience::Shared(&token),
+    )
+    .await
+}
+
+#[tracing::instrument(skip_all, fields(wine_id))]
+async fn wine_information(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::extract::Path((token, wine_id)): axum::extract::Path<(String, i64)>,
+) -> MDResult {
+    tracing::Span::current().record("wine_id", wine_id);
+    let cellar = shared_cellar(&state, &token).await?;
+    markup::wine_information_html(&state, cellar.cellar_id, wine_id, Audience::Shared(&token)).await
+}
+
+#[tracing::instrument(skip_all, fields(wine_id))]
+async fn wine_image(
+    axum::extract::State(state): axum::extract::State<State>,
+    axum::extract::Path((token, wine_id)): axum::extract::Path<(String, i64)>,
+) -> Result<axum::response::Response, AppError> {
+    tracing::Span::current().record("wine_id", wine_id);
+    let cellar = shared_cellar(&state, &token).await?;
+    let img = db::wine_image(&state.db, cellar.cellar_id, wine_id).await?;
+    super::handlers::image_response(img)
+}
@@ -0,0 +1,167 @@
+//! The wine table's state: its filters, its order and, for the infinite scroll, the page of
+//! rows. It travels in the query string, so that e.g. `/?grape=Merlot&sort=year&dir=desc`
+//! opens a filtered, sorted table, and each page of rows knows what to fetch next.
+//!
+//! Grapes are a multi-select and repeat the `grape` key, which `serde_urlencoded` can't
+//! deserialize into a struct, so the pairs are read one by one.
+
+use crate::db;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct TableQuery {
+    pub filter: db::WineFilter,
+    pub order: db::WineOrder,
+    /// Zero-based.
+    pub page: i64,
+}
+
+/// Checkboxes send `on`; anything but an empty value counts as ticked.
+fn flag(value: &str) -> bool {
+    !value.is_empty()
+}
+
+fn number(key: &str, value: &str) -> anyhow::Result<i64> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid {key}: {value}"))
+}
+
+impl TableQuery {
+    /// Parses a query string. Empty values and unknown keys are ignored.
+    pub(crate) fn parse(query: &str) -> anyhow::Result<Self> {
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(query)?;
+        let mut res = Self::default();
+        for (key, value) in pairs.iter().filter(|(_, value)| !value.is_empty()) {
+            let filter = &mut res.filter;
+            match key.as_str() {
+                "grape" if !filter.grapes.contains(value) => filter.grapes.push(value.clone()),
+                "style" => filter.style = Some(value.parse()?),
+                "producer" => filter.producer_id = Some(number(key, value)?),
+                "region" => filter.region_id = Some(number(key, value)?),
+                "year_from" => filter.year_from = Some(number(key, value)?),
+                "year_to" => filter.year_to = Some(number(key, value)?),
+                "in_stock" => filter.in_stock = flag(value),
+                "has_image" => filter.has_image = flag(value),
+                "has_note" => filter.has_note = flag(value),
+                "pairing" => {
+                    let pairing = value.trim();
+                    filter.pairing = (!pairing.is_empty()).then(|| pairing.to_owned());
+                }
+                "sort" => res.order.sort = value.parse()?,
+                "dir" => {
+                    res.order.descending = match value.as_str() {
+                        "asc" => false,
+                        "desc" => true,
+                        other => anyhow::bail!("Unknown sort direction: {other}"),
+                    }
+                }
+                "page" => res.page = number(key, value)?.max(0),
+                _ => {}
+            }
+        }
+        Ok(res)
+    }
+
+    /// The query string [`TableQuery::parse`] reads back, leaving out what is unset.
+    pub(crate) fn query_string(&self) -> String {
+        let filter = &self.filter;
+        let mut pairs: Vec<(&str, String)> = filter
+            .grapes
+            .iter()
+            .map(|grape| ("grape", grape.clone()))
+            .collect();
+        let numbers = [
+            ("producer", filter.producer_id),
+            ("region", filter.region_id),
+            ("year_from", filter.year_from),
+            ("year_to", filter.year_to),
+        ];
+        if let Some(style) = filter.style {
+            pairs.push(("style", style.as_str().to_owned()));
+        }
+        pairs.extend(
+            numbers
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, value?.to_string()))),
+        );
+        let flags = [
+            ("in_stock", filter.in_stock),
+            ("has_image", filter.has_image),
+            ("has_note", filter.has_note),
+        ];
+        pairs.extend(
+            flags
+                .into_iter()
+                .filter(|&(_, set)| set)
+                .map(|(key, _)| (key, "on".to_owned())),
+        );
+        if let Some(pairing) = &filter.pairing {
+            pairs.push(("pairing", pairing.clone()));
+        }
+        if self.order != db::WineOrder::default() {
+            pairs.push(("sort", self.order.sort.as_str().to_owned()));
+            if self.order.descending {
+                pairs.push(("dir", "desc".to_owned()));
+            }
+        }
+        if self.page > 0 {
+            pairs.push(("page", self.page.to_string()));
+        }
+        serde_urlencoded::to_string(pairs).unwrap_or_default()
+    }
+
+    /// Whether any filter is set.
+    pub(crate) fn is_filtered(&self) -> bool {
+        self.filter != db::WineFilter::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let query = TableQuery {
+            filter: db::WineFilter {
+                grapes: vec!["Merlot".to_owned(), "Cabernet franc".to_owned()],
+                style: Some(db::WineStyle::Red),
+                year_from: Some(2010),
+                in_stock: true,
+                pairing: Some("beef & lamb".to_owned()),
+                ..Default::default()
+            },
+            order: db::WineOrder {
+                sort: db::WineSort::Score,
+                descending: true,
+            },
+            page: 2,
+        };
+        let qs = query.query_string();
+        assert_eq!(TableQuery::parse(&qs).unwrap(), query);
+        assert_eq!(TableQuery::default().query_string(), "");
+    }
+
+    #[test]
+    fn test_parse_form_values() {
+        // What the filter form sends when little is filled in
+        let query = TableQuery::parse(concat!(
+            "style=&producer=&region=3&year_from=&year_to=2020&pairing=+&has_note=on",
+            "&grape=Syrah&grape=Syrah",
+        ))
+        .unwrap();
+        assert_eq!(query.filter.region_id, Some(3));
+        assert_eq!(query.filter.year_to, Some(2020));
+        assert_eq!(query.filter.pairing, None);
+        assert!(query.filter.has_note);
+        assert!(!query.filter.in_stock);
+        assert_eq!(query.filter.grapes, ["Syrah"]);
+        assert!(query.is_filtered());
+
+        assert!(TableQuery::parse("year_from=old").is_err());
+        assert!(TableQuery::parse("sort=colour").is_err());
+        assert!(TableQuery::parse("dir=up").is_err());
+        assert!(!TableQuery::parse("hx=1").unwrap().is_filtered());
+    }
+}